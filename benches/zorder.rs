@@ -0,0 +1,54 @@
+#[macro_use]
+extern crate criterion;
+extern crate slippy_map_tiles;
+
+use criterion::{black_box, Criterion, Throughput};
+use slippy_map_tiles::*;
+
+fn zorder(c: &mut Criterion) {
+    // One row of zoom 14
+    let mut group = c.benchmark_group("zorder");
+    group.throughput(Throughput::Elements(1 << 14));
+    group.bench_function("xy_to_zorder", |b| {
+        b.iter(|| {
+            let mut total = 0u64;
+            for x in 0..(1u32 << 14) {
+                total ^= xy_to_zorder(black_box(x), black_box(12_345));
+            }
+            total
+        })
+    });
+    group.bench_function("zorder_to_xy", |b| {
+        b.iter(|| {
+            let mut total = 0u32;
+            for i in 0..(1u64 << 14) {
+                let (x, y) = zorder_to_xy(black_box(i * 7919));
+                total ^= x ^ y;
+            }
+            total
+        })
+    });
+    group.finish();
+}
+
+fn iterate_z14(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate z14");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(1 << 28));
+
+    group.bench_function("all_tiles", |b| {
+        b.iter(|| {
+            all_tiles()
+                .skip_while(|t| t.zoom() < 14)
+                .take_while(|t| t.zoom() == 14)
+                .count()
+        })
+    });
+    group.bench_function("metatiles", |b| {
+        b.iter(|| MetatilesIterator::new_for_bbox_zoom(1, &None, 14, 14).count())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, zorder, iterate_z14);
+criterion_main!(benches);
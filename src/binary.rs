@@ -0,0 +1,317 @@
+//! A compact binary format for storing sets of tiles, e.g. to send a large expiry list to another
+//! machine.
+//!
+//! Tiles are sorted by zoom, and then by z-order (`xy_to_zorder`). Within a zoom, each tile is
+//! stored as a [LEB128](https://en.wikipedia.org/wiki/LEB128) varint of the difference from the
+//! previous tile's z-order, so dense areas take ~1 byte per tile.
+//!
+//! The format is:
+//!
+//! * 4 bytes magic `SMTS`, then a 1 byte version (currently `1`)
+//! * 1 byte minimum zoom, 1 byte maximum zoom
+//! * varint, number of tiles
+//! * For each tile, a varint: 0 means "move to a later zoom", and is followed by a varint of how
+//!   many zooms to go forward. Otherwise it's the z-order of this tile minus the z-order of the
+//!   previous tile in the same zoom. The "previous" z-order at the start of a zoom is -1.
+//!
+//! ```
+//! # use slippy_map_tiles::Tile;
+//! # use slippy_map_tiles::binary;
+//! let tiles = vec![Tile::new(4, 1, 1).unwrap(), Tile::new(2, 3, 0).unwrap(), Tile::new(4, 1, 0).unwrap()];
+//! let mut bytes = Vec::new();
+//! binary::write_tiles(&mut bytes, tiles).unwrap();
+//!
+//! let read: Vec<Tile> = binary::TileSetDecoder::new(&bytes[..]).unwrap()
+//!     .collect::<Result<_, _>>().unwrap();
+//! assert_eq!(read, vec![Tile::new(2, 3, 0).unwrap(), Tile::new(4, 1, 0).unwrap(), Tile::new(4, 1, 1).unwrap()]);
+//! ```
+
+use std::io;
+use std::io::{Read, Write};
+
+use super::{xy_to_zorder, zorder_to_xy, Tile};
+
+const MAGIC: &[u8; 4] = b"SMTS";
+const VERSION: u8 = 1;
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_varint<W: Write>(w: &mut W, mut value: u64) -> io::Result<()> {
+    let mut buf = [0u8; 10];
+    let mut i = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf[i] = byte;
+            i += 1;
+            break;
+        } else {
+            buf[i] = byte | 0x80;
+            i += 1;
+        }
+    }
+    w.write_all(&buf[..i])
+}
+
+fn read_varint<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut value: u64 = 0;
+    let mut byte = [0u8; 1];
+    for shift in (0..64).step_by(7) {
+        r.read_exact(&mut byte)?;
+        let bits = (byte[0] & 0x7f) as u64;
+        if shift == 63 && bits > 1 {
+            return Err(invalid_data("varint is too large"));
+        }
+        value |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid_data("varint is too large"))
+}
+
+/// The sort key used in the binary format
+fn sort_key(tile: &Tile) -> (u8, u64) {
+    (tile.zoom(), xy_to_zorder(tile.x(), tile.y()))
+}
+
+/// Writes tiles in the binary format, one at a time.
+///
+/// Since the header includes the zoom range and number of tiles, they must be known in advance.
+/// Tiles must be given in sorted order (by zoom, then z-order), with no duplicates. See
+/// `write_tiles` to write an unsorted collection.
+pub struct TileSetEncoder<W: Write> {
+    wtr: W,
+    min_zoom: u8,
+    max_zoom: u8,
+    count: u64,
+    written: u64,
+    // (zoom, zorder) of the last tile written
+    last: Option<(u8, u64)>,
+}
+
+impl<W: Write> TileSetEncoder<W> {
+    /// Write the header, for `count` tiles from `min_zoom` to `max_zoom` (inclusive).
+    pub fn new(mut wtr: W, min_zoom: u8, max_zoom: u8, count: u64) -> io::Result<Self> {
+        if min_zoom > max_zoom {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "min_zoom is greater than max_zoom",
+            ));
+        }
+        wtr.write_all(MAGIC)?;
+        wtr.write_all(&[VERSION, min_zoom, max_zoom])?;
+        write_varint(&mut wtr, count)?;
+
+        Ok(TileSetEncoder {
+            wtr,
+            min_zoom,
+            max_zoom,
+            count,
+            written: 0,
+            last: None,
+        })
+    }
+
+    /// Write the next tile.
+    ///
+    /// Returns an error if the tile is out of order, outside the zoom range, or more tiles than
+    /// the header count are written.
+    pub fn push(&mut self, tile: &Tile) -> io::Result<()> {
+        let (zoom, zorder) = sort_key(tile);
+        if zoom < self.min_zoom || zoom > self.max_zoom {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Tile is outside the zoom range",
+            ));
+        }
+        if self.written == self.count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "More tiles written than in header",
+            ));
+        }
+
+        let (curr_zoom, prev_zorder) = match self.last {
+            None => (self.min_zoom, None),
+            Some((z, zorder)) => (z, Some(zorder)),
+        };
+
+        let delta = if zoom > curr_zoom {
+            write_varint(&mut self.wtr, 0)?;
+            write_varint(&mut self.wtr, (zoom - curr_zoom) as u64)?;
+            zorder + 1
+        } else if zoom == curr_zoom {
+            match prev_zorder {
+                None => zorder + 1,
+                Some(prev) if zorder > prev => zorder - prev,
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Tiles are not sorted or have duplicates",
+                    ));
+                }
+            }
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Tiles are not sorted by zoom",
+            ));
+        };
+
+        write_varint(&mut self.wtr, delta)?;
+        self.last = Some((zoom, zorder));
+        self.written += 1;
+
+        Ok(())
+    }
+
+    /// Finish writing. Returns an error if fewer tiles were written than the header said.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.written != self.count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Fewer tiles written than in header",
+            ));
+        }
+        self.wtr.flush()?;
+        Ok(self.wtr)
+    }
+}
+
+/// Sort, deduplicate and write these tiles in the binary format.
+pub fn write_tiles<W: Write, I: IntoIterator<Item = Tile>>(wtr: W, tiles: I) -> io::Result<W> {
+    let mut tiles: Vec<Tile> = tiles.into_iter().collect();
+    tiles.sort_by_key(sort_key);
+    tiles.dedup();
+
+    let min_zoom = tiles.first().map_or(0, |t| t.zoom());
+    let max_zoom = tiles.last().map_or(0, |t| t.zoom());
+
+    let mut encoder = TileSetEncoder::new(wtr, min_zoom, max_zoom, tiles.len() as u64)?;
+    for t in tiles.iter() {
+        encoder.push(t)?;
+    }
+    encoder.finish()
+}
+
+/// Reads tiles from the binary format, one at a time.
+pub struct TileSetDecoder<R: Read> {
+    rdr: R,
+    min_zoom: u8,
+    max_zoom: u8,
+    count: u64,
+    read: u64,
+    curr_zoom: u8,
+    // z-order of the previous tile in this zoom, plus 1.
+    next_base: u64,
+    failed: bool,
+}
+
+impl<R: Read> TileSetDecoder<R> {
+    /// Read the header. Returns an error if this isn't the binary tile set format.
+    pub fn new(mut rdr: R) -> io::Result<Self> {
+        let mut header = [0u8; 7];
+        rdr.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid_data("Not a binary tile set"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data("Unsupported binary tile set version"));
+        }
+        let (min_zoom, max_zoom) = (header[5], header[6]);
+        if min_zoom > max_zoom {
+            return Err(invalid_data("min_zoom is greater than max_zoom"));
+        }
+        if max_zoom >= 32 {
+            return Err(invalid_data("max_zoom is too large"));
+        }
+        let count = read_varint(&mut rdr)?;
+
+        Ok(TileSetDecoder {
+            rdr,
+            min_zoom,
+            max_zoom,
+            count,
+            read: 0,
+            curr_zoom: min_zoom,
+            next_base: 0,
+            failed: false,
+        })
+    }
+
+    /// Minimum zoom in this tile set
+    pub fn min_zoom(&self) -> u8 {
+        self.min_zoom
+    }
+
+    /// Maximum zoom in this tile set
+    pub fn max_zoom(&self) -> u8 {
+        self.max_zoom
+    }
+
+    /// The total number of tiles in this tile set
+    pub fn total(&self) -> u64 {
+        self.count
+    }
+
+    fn next_tile(&mut self) -> io::Result<Tile> {
+        let mut delta = read_varint(&mut self.rdr)?;
+        if delta == 0 {
+            let zoom_step = read_varint(&mut self.rdr)?;
+            if zoom_step == 0 || zoom_step > (self.max_zoom - self.curr_zoom) as u64 {
+                return Err(invalid_data("Invalid zoom step"));
+            }
+            self.curr_zoom += zoom_step as u8;
+            self.next_base = 0;
+
+            delta = read_varint(&mut self.rdr)?;
+            if delta == 0 {
+                return Err(invalid_data("Unexpected zoom step"));
+            }
+        }
+
+        let zorder = self
+            .next_base
+            .checked_add(delta - 1)
+            .ok_or_else(|| invalid_data("z-order overflow"))?;
+        self.next_base = zorder + 1;
+
+        let (x, y) = zorder_to_xy(zorder);
+        Tile::new(self.curr_zoom, x, y).ok_or_else(|| invalid_data("Invalid tile"))
+    }
+}
+
+impl<R: Read> Iterator for TileSetDecoder<R> {
+    type Item = io::Result<Tile>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.read >= self.count {
+            return None;
+        }
+
+        let result = self.next_tile();
+        match result {
+            Ok(_) => self.read += 1,
+            Err(_) => self.failed = true,
+        }
+
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.failed {
+            return (0, Some(0));
+        }
+        let remaining = self.count - self.read;
+        if remaining > usize::MAX as u64 {
+            (usize::MAX, None)
+        } else {
+            (0, Some(remaining as usize))
+        }
+    }
+}
@@ -20,17 +20,47 @@ extern crate regex;
 #[cfg(feature = "world_file")]
 extern crate world_image_file;
 
+#[cfg(feature = "gzip")]
+extern crate flate2;
+
+#[cfg(feature = "image")]
+extern crate image;
+
+#[cfg(feature = "json")]
+#[macro_use]
+extern crate serde_json;
+
 use regex::Regex;
 use std::borrow::Borrow;
+use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
-use std::ops::Deref;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::{Deref, RangeInclusive};
+use std::path::Path;
 use std::str::FromStr;
 
 #[cfg(feature = "world_file")]
 use world_image_file::WorldFile;
 
+pub mod binary;
+pub mod mercator;
+pub mod osc;
+pub mod shard;
+pub mod staticmap;
+#[cfg(feature = "image")]
+pub mod stitch;
+pub mod tilelist;
+pub mod tilemap;
+pub mod tilematrixset;
+pub mod tilerange;
+pub mod tileset;
+pub mod viewport;
+
+use tilematrixset::TileMatrixSet;
+pub use tilerange::{TileRange, TileRangeHilbertIter, TileRangeIter};
+
 #[cfg(test)]
 mod tests;
 
@@ -45,23 +75,20 @@ pub struct Tile {
 impl Tile {
     /// Constucts a Tile with the following zoom, x and y values.
     ///
-    /// Returns None if the x/y are invalid for that zoom level, or if the zoom is >= 100.
+    /// Returns None if the x/y are invalid for that zoom level, or if the zoom is > 31.
     /// # Examples
     /// ```
     /// # use slippy_map_tiles::Tile;
     /// assert!(Tile::new(0, 3, 3).is_none());
     /// ```
     pub fn new(zoom: u8, x: u32, y: u32) -> Option<Tile> {
-        if zoom >= 100 {
-            None
-        } else if x < 2u32.pow(zoom as u32) && y < 2u32.pow(zoom as u32) {
-            Some(Tile {
+        match 2u32.checked_pow(zoom as u32) {
+            Some(max) if x < max && y < max => Some(Tile {
                 zoom: zoom,
                 x: x,
                 y: y,
-            })
-        } else {
-            None
+            }),
+            _ => None,
         }
     }
 
@@ -227,6 +254,55 @@ impl Tile {
         tile_nw_lat_lon(self.zoom, (self.x as f32) + 1.0, (self.y as f32) + 1.0)
     }
 
+    /// The lat/lon of pixel `px`,`py` in the image of this tile, which is `tile_size` pixels wide.
+    /// `0,0` is the top left corner of the image, and `tile_size,tile_size` the bottom right.
+    /// Pixels outside the image are allowed. Returns None if the pixel is outside the world.
+    ///
+    /// See `lat_lon_to_global_pixel` for `tile_size`.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{Tile, LatLon};
+    /// let t = Tile::new(1, 1, 0).unwrap();
+    /// assert_eq!(t.pixel_to_latlon(0., 256., 256), LatLon::new(0., 0.));
+    /// // The same place on a @2x tile
+    /// assert_eq!(t.pixel_to_latlon(0., 512., 512), LatLon::new(0., 0.));
+    /// ```
+    pub fn pixel_to_latlon(&self, px: f64, py: f64, tile_size: u32) -> Option<LatLon> {
+        let tile_size = tile_size as f64;
+        tile_fraction_to_lat_lon(
+            self.x as f64 + px / tile_size,
+            self.y as f64 + py / tile_size,
+            self.zoom,
+        )
+    }
+
+    /// The pixel x,y of this point in the image of this tile, which is `tile_size` pixels wide.
+    /// The opposite of `pixel_to_latlon`. Points outside this tile have pixels outside the image,
+    /// e.g. negative.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{Tile, LatLon};
+    /// let t = Tile::new(1, 1, 0).unwrap();
+    /// assert_eq!(t.latlon_to_pixel(&LatLon::new(0., 90.).unwrap(), 256), (128., 256.));
+    /// ```
+    pub fn latlon_to_pixel(&self, point: &LatLon, tile_size: u32) -> (f64, f64) {
+        let (x, y) = lat_lon_to_tile_fraction(point.lat as f64, point.lon as f64, self.zoom);
+        let tile_size = tile_size as f64;
+
+        (
+            (x - self.x as f64) * tile_size,
+            (y - self.y as f64) * tile_size,
+        )
+    }
+
+    /// The global pixel x,y of the top left corner of this tile, see `lat_lon_to_global_pixel`.
+    pub fn global_pixel(&self, tile_size: u32) -> (u64, u64) {
+        (
+            self.x as u64 * tile_size as u64,
+            self.y as u64 * tile_size as u64,
+        )
+    }
+
     pub fn top(&self) -> f32 {
         self.nw_corner().lat
     }
@@ -277,6 +353,222 @@ impl Tile {
         format!("{}/{}/{}.{}", self.zoom, self.x, self.y, ext)
     }
 
+    /// Returns the [Bing Maps
+    /// quadkey](https://docs.microsoft.com/en-us/bingmaps/articles/bing-maps-tile-system) for
+    /// this tile. Zoom 0 is the empty string.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::Tile;
+    /// assert_eq!(Tile::new(3, 3, 5).unwrap().quadkey(), "213");
+    /// ```
+    pub fn quadkey(&self) -> String {
+        (1..=self.zoom)
+            .rev()
+            .map(|i| {
+                let mask = 1 << (i - 1);
+                let mut digit = b'0';
+                if self.x & mask != 0 {
+                    digit += 1;
+                }
+                if self.y & mask != 0 {
+                    digit += 2;
+                }
+                digit as char
+            })
+            .collect()
+    }
+
+    /// Constructs a Tile from a Bing Maps quadkey. Returns None if the quadkey is invalid.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::Tile;
+    /// assert_eq!(Tile::from_quadkey("213"), Tile::new(3, 3, 5));
+    /// assert_eq!(Tile::from_quadkey("214"), None);
+    /// ```
+    pub fn from_quadkey(quadkey: &str) -> Option<Tile> {
+        if quadkey.len() >= 32 {
+            return None;
+        }
+        let mut x = 0;
+        let mut y = 0;
+        for c in quadkey.bytes() {
+            let digit = match c {
+                b'0'..=b'3' => (c - b'0') as u32,
+                _ => {
+                    return None;
+                }
+            };
+            x = (x << 1) | (digit & 1);
+            y = (y << 1) | (digit >> 1);
+        }
+
+        Tile::new(quadkey.len() as u8, x, y)
+    }
+
+    /// The position of this tile along the Hilbert curve which covers this zoom level.
+    pub fn hilbert_index(&self) -> u64 {
+        xy_to_hilbert(self.x, self.y, self.zoom)
+    }
+
+    /// A single number for this tile, which is unique across all zoom levels, and is the same as
+    /// the [PMTiles](https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md) Tile ID.
+    /// All the tiles at lower zooms come first, then tiles in Hilbert order.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::Tile;
+    /// assert_eq!(Tile::new(0, 0, 0).unwrap().tile_id(), 0);
+    /// assert_eq!(Tile::new(1, 1, 0).unwrap().tile_id(), 4);
+    /// assert_eq!(Tile::new(2, 0, 0).unwrap().tile_id(), 5);
+    /// ```
+    pub fn tile_id(&self) -> u64 {
+        tiles_in_lower_zooms(self.zoom) + self.hilbert_index()
+    }
+
+    /// Constructs the Tile for this Tile ID (see `tile_id()`). Returns None for IDs past zoom 31.
+    pub fn from_tile_id(tile_id: u64) -> Option<Tile> {
+        let mut tiles_in_lower_zooms = 0;
+        for zoom in 0..32u8 {
+            let tiles_in_zoom = 1u64 << (2 * zoom as u64);
+            if tile_id - tiles_in_lower_zooms < tiles_in_zoom {
+                let (x, y) = hilbert_to_xy(tile_id - tiles_in_lower_zooms, zoom);
+                return Tile::new(zoom, x, y);
+            }
+            tiles_in_lower_zooms += tiles_in_zoom;
+        }
+
+        None
+    }
+
+    /// A single `u64` key for this tile, which includes the zoom. This is the position of the tile
+    /// in a depth first walk of all the tiles, from `0/0/0` to zoom 31, visiting subtiles in
+    /// z-order. So a tile's ancestors sort before it, and all it's descendants come straight after
+    /// it, which is useful as a key in a B-tree or database. `Tile`'s `Ord` uses this order.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::Tile;
+    /// assert_eq!(Tile::new(0, 0, 0).unwrap().packed_id(), 0);
+    /// assert_eq!(Tile::new(1, 0, 0).unwrap().packed_id(), 1);
+    /// assert_eq!(Tile::new(2, 0, 0).unwrap().packed_id(), 2);
+    /// assert!(Tile::new(2, 1, 1).unwrap() < Tile::new(1, 1, 0).unwrap());
+    /// ```
+    pub fn packed_id(&self) -> u64 {
+        if self.zoom == 0 {
+            return 0;
+        }
+        // Each level down adds 1, plus the size of the earlier sibling subtrees. A subtree of a
+        // tile at zoom z, down to zoom 31, has (4^(32-z) - 1)/3 tiles.
+        let zorder = xy_to_zorder(self.x, self.y);
+        let digit_sum = (self.x.count_ones() + 2 * self.y.count_ones()) as u64;
+        self.zoom as u64 + ((zorder << (2 * (32 - self.zoom as u64))) - digit_sum) / 3
+    }
+
+    /// Constructs the Tile for this packed ID (see `packed_id()`). Returns None if the ID is too
+    /// large.
+    pub fn from_packed_id(packed_id: u64) -> Option<Tile> {
+        let mut remaining = packed_id;
+        let (mut x, mut y) = (0, 0);
+        for zoom in 0..32u8 {
+            if remaining == 0 {
+                return Tile::new(zoom, x, y);
+            }
+            if zoom == 31 {
+                break;
+            }
+            remaining -= 1;
+            // The number of tiles in the subtree of each subtile
+            let subtree_size = ((1u64 << (2 * (31 - zoom as u64))) - 1) / 3;
+            let child = remaining / subtree_size;
+            if child > 3 {
+                return None;
+            }
+            x = x * 2 + (child & 1) as u32;
+            y = y * 2 + (child >> 1) as u32;
+            remaining %= subtree_size;
+        }
+
+        None
+    }
+
+    /// The range of packed IDs (see `packed_id()`) which contains this tile, and all it's
+    /// descendants down to `max_zoom`, and no other tiles up to `max_zoom`. e.g. for
+    /// `BTreeMap::range`. If `max_zoom` is not more than this tile's zoom, the range is just this
+    /// tile. `max_zoom` is capped at 31.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::Tile;
+    /// # use std::collections::BTreeMap;
+    /// let mut map = BTreeMap::new();
+    /// for t in ["1/0/0", "2/0/0", "2/1/1", "2/2/0", "3/3/3"].iter() {
+    ///     let t: Tile = t.parse().unwrap();
+    ///     map.insert(t.packed_id(), t);
+    /// }
+    /// let range = Tile::new(1, 0, 0).unwrap().descendant_id_range(3);
+    /// assert_eq!(map.range(range).count(), 4);
+    /// ```
+    pub fn descendant_id_range(&self, max_zoom: u8) -> RangeInclusive<u64> {
+        let max_zoom = max_zoom.min(31);
+        let start = self.packed_id();
+        if max_zoom <= self.zoom {
+            return start..=start;
+        }
+        let shift = max_zoom - self.zoom;
+        let last = Tile {
+            zoom: max_zoom,
+            x: (((self.x as u64 + 1) << shift) - 1) as u32,
+            y: (((self.y as u64 + 1) << shift) - 1) as u32,
+        };
+        start..=last.packed_id()
+    }
+
+    /// The position of this tile in `all_tiles()`, i.e. all the tiles in lower zooms, then the
+    /// z-order (see `xy_to_zorder`) of this tile. See `column_pyramid_index()` for the position
+    /// in `Tile::all_to_zoom()`.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{all_tiles, Tile};
+    /// let tile = Tile::new(3, 2, 5).unwrap();
+    /// assert_eq!(tile.pyramid_index(), 21 + 38);
+    /// assert_eq!(all_tiles().nth(59), Some(tile));
+    /// assert_eq!(Tile::from_pyramid_index(59), Some(tile));
+    /// ```
+    pub fn pyramid_index(&self) -> u64 {
+        tiles_in_lower_zooms(self.zoom) + xy_to_zorder(self.x, self.y)
+    }
+
+    /// Constructs the Tile at this position in `all_tiles()` (see `pyramid_index()`). Returns None
+    /// for indexes past zoom 31.
+    pub fn from_pyramid_index(index: u64) -> Option<Tile> {
+        let zoom = zoom_for_pyramid_index(index)?;
+        let (x, y) = zorder_to_xy(index - tiles_in_lower_zooms(zoom));
+        Tile::new(zoom, x, y)
+    }
+
+    /// The position of this tile in `Tile::all_to_zoom()`, i.e. all the tiles in lower zooms,
+    /// then the tiles in earlier columns (`x * 2^zoom`), then `y`.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::Tile;
+    /// let tile = Tile::new(2, 1, 2).unwrap();
+    /// assert_eq!(tile.column_pyramid_index(), 5 + 6);
+    /// assert_eq!(Tile::all_to_zoom(3).nth(11), Some(tile));
+    /// assert_eq!(Tile::from_column_pyramid_index(11), Some(tile));
+    /// ```
+    pub fn column_pyramid_index(&self) -> u64 {
+        tiles_in_lower_zooms(self.zoom) + ((self.x as u64) << self.zoom) + self.y as u64
+    }
+
+    /// Constructs the Tile at this position in `Tile::all_to_zoom()` (see
+    /// `column_pyramid_index()`). Returns None for indexes past zoom 31.
+    pub fn from_column_pyramid_index(index: u64) -> Option<Tile> {
+        let zoom = zoom_for_pyramid_index(index)?;
+        let index = index - tiles_in_lower_zooms(zoom);
+        Tile::new(
+            zoom,
+            (index >> zoom) as u32,
+            (index & ((1 << zoom) - 1)) as u32,
+        )
+    }
+
     /// Returns the ModTileMetatile path for storing this tile
     pub fn mt_path<T: std::fmt::Display>(&self, ext: T) -> String {
         let tc = xy_to_mt(self.x, self.y);
@@ -301,6 +593,8 @@ impl Tile {
     /// Returns an iterator that yields all the tiles from zoom 0 down to, and including, all the
     /// tiles at `max_zoom` zoom level.  Tiles are
     /// generated in a breath first manner, with all zoom 1 tiles before zoom 2 etc.
+    /// Within a zoom, tiles are in columns (all the `y` for `x = 0` first), unlike `all_tiles()`
+    /// which uses z-order. `nth` & `skip` jump straight to the tile.
     pub fn all_to_zoom(max_zoom: u8) -> AllTilesToZoomIterator {
         AllTilesToZoomIterator {
             max_zoom: max_zoom,
@@ -310,6 +604,47 @@ impl Tile {
         }
     }
 
+    /// The Web Mercator (EPSG:3857) extent of this tile in metres
+    ///
+    /// ```
+    /// # use slippy_map_tiles::Tile;
+    /// let extent = Tile::new(1, 1, 0).unwrap().merc_bbox();
+    /// assert_eq!((extent.min_x(), extent.min_y()), (0., 0.));
+    /// assert_eq!(extent.max_x(), slippy_map_tiles::mercator::EXTENT);
+    /// ```
+    pub fn merc_bbox(&self) -> mercator::Extent {
+        mercator::tile_extent(self)
+    }
+
+    /// The envelope of this tile in Web Mercator (EPSG:3857), with `margin` (a fraction of the
+    /// tile size) added on each side. The same as PostGIS's `ST_TileEnvelope(z, x, y, margin =>
+    /// margin)`. Returns None if `margin` is less than -0.5.
+    ///
+    /// See `mercator::tile_envelope` for other bounds.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::Tile;
+    /// let envelope = Tile::new(2, 1, 1).unwrap().envelope(0.125).unwrap();
+    /// assert_eq!(envelope.min_x(), -11271098.442818949);
+    /// assert_eq!(envelope.max_y(), 11271098.442818949);
+    /// ```
+    pub fn envelope(&self, margin: f64) -> Option<mercator::Extent> {
+        mercator::tile_envelope(self, &mercator::Extent::world(), margin)
+    }
+
+    /// The extent of this tile in the CRS of `tms`, with the zoom, x & y as the tile matrix,
+    /// column & row. None if `tms` doesn't have this tile. For `WebMercatorQuad`, this is
+    /// `merc_bbox()`.
+    pub fn extent_in(&self, tms: &TileMatrixSet) -> Option<mercator::Extent> {
+        tms.tile_extent(self.zoom, self.x, self.y)
+    }
+
+    /// The lat/lon bbox of this tile in `tms` (see `extent_in`). None if `tms` doesn't have this
+    /// tile, or its CRS is `Other`. For `WebMercatorQuad`, this is `bbox()`.
+    pub fn bbox_in(&self, tms: &TileMatrixSet) -> Option<BBox> {
+        tms.tile_bbox(self.zoom, self.x, self.y)
+    }
+
     /// The BBox for this tile.
     pub fn bbox(&self) -> BBox {
         let nw = self.nw_corner();
@@ -327,11 +662,10 @@ impl Tile {
     }
 
     #[cfg(feature = "world_file")]
-    /// Return the World File (in EPSG:3857 / Web Mercator SRID) for this tile
+    /// Return the World File (in EPSG:3857 / Web Mercator SRID) for this tile, as a 256 pixel
+    /// image. The coordinates are of the centre of the top left pixel.
     pub fn world_file(&self) -> WorldFile {
-        let total_merc_width = 20037508.342789244;
-        let tile_merc_width = (2. * total_merc_width) / 2f64.powi(self.zoom as i32);
-        let scale = tile_merc_width / 256.;
+        let scale = mercator::resolution(self.zoom, 256);
 
         WorldFile {
             x_scale: scale,
@@ -340,10 +674,28 @@ impl Tile {
             x_skew: 0.,
             y_skew: 0.,
 
-            x_coord: tile_merc_width * (self.x as f64) - total_merc_width,
-            y_coord: -tile_merc_width * (self.y as f64) + total_merc_width,
+            x_coord: (self.x as f64 * 256. + 0.5) * scale - mercator::EXTENT,
+            y_coord: -(self.y as f64 * 256. + 0.5) * scale + mercator::EXTENT,
         }
     }
+
+    /// The resolution of this tile in metres per pixel, when it's `tile_size` pixels wide. See
+    /// `mercator::resolution`.
+    pub fn resolution(&self, tile_size: u32) -> f64 {
+        mercator::resolution(self.zoom, tile_size)
+    }
+
+    /// The size of a pixel on the ground in this tile (at its centre), in metres, when it's
+    /// `tile_size` pixels wide.
+    pub fn ground_resolution(&self, tile_size: u32) -> f64 {
+        mercator::ground_resolution(self.centre_point().lat as f64, self.zoom, tile_size)
+    }
+
+    /// The OGC scale denominator of this tile (with 0.28mm pixels), when it's `tile_size` pixels
+    /// wide. e.g. zoom 0 with 256 pixel tiles is 1:559,082,264.
+    pub fn scale_denominator(&self, tile_size: u32) -> f64 {
+        mercator::scale_denominator(self.resolution(tile_size), mercator::STANDARD_PIXEL_SIZE)
+    }
 }
 
 impl FromStr for Tile {
@@ -363,10 +715,25 @@ impl FromStr for Tile {
         }
         let caps = caps.unwrap();
 
-        // If the regex matches, then none of these should fail, right?
-        let zoom = caps.name("zoom").unwrap().as_str().parse().unwrap();
-        let x = caps.name("x").unwrap().as_str().parse().unwrap();
-        let y = caps.name("y").unwrap().as_str().parse().unwrap();
+        // The regex only limits the number of digits, so these can still be too large
+        let zoom = caps
+            .name("zoom")
+            .unwrap()
+            .as_str()
+            .parse()
+            .map_err(|_| "Invalid zoom")?;
+        let x = caps
+            .name("x")
+            .unwrap()
+            .as_str()
+            .parse()
+            .map_err(|_| "Invalid X")?;
+        let y = caps
+            .name("y")
+            .unwrap()
+            .as_str()
+            .parse()
+            .map_err(|_| "Invalid Y")?;
 
         match Tile::new(zoom, x, y) {
             None => {
@@ -378,10 +745,119 @@ impl FromStr for Tile {
     }
 }
 
-/// Iterates over all the tiles in the world.
+/// Tiles are ordered by `packed_id()`, i.e. depth first, so a tile comes after it's ancestors and
+/// before any other tiles that aren't it's descendants.
+impl Ord for Tile {
+    fn cmp(&self, other: &Tile) -> Ordering {
+        self.packed_id().cmp(&other.packed_id())
+    }
+}
+
+impl PartialOrd for Tile {
+    fn partial_cmp(&self, other: &Tile) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The order that tiles in a zoom level are returned in by some iterators.
+///
+/// The [Hilbert curve](https://en.wikipedia.org/wiki/Hilbert_curve) has no long jumps, so tiles
+/// next to each other in the order are always next to each other on the map.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
+pub enum TileOrder {
+    /// Z-order, see `xy_to_zorder`
+    #[default]
+    ZOrder,
+    /// Hilbert curve order, see `xy_to_hilbert`
+    Hilbert,
+}
+
+impl TileOrder {
+    /// The position of tile `x`/`y` along this curve at `zoom`
+    pub fn index(&self, x: u32, y: u32, zoom: u8) -> u64 {
+        match *self {
+            TileOrder::ZOrder => xy_to_zorder(x, y),
+            TileOrder::Hilbert => xy_to_hilbert(x, y, zoom),
+        }
+    }
+
+    /// The x & y of the tile at this position along this curve at `zoom`
+    pub fn xy(&self, index: u64, zoom: u8) -> (u32, u32) {
+        match *self {
+            TileOrder::ZOrder => zorder_to_xy(index),
+            TileOrder::Hilbert => hilbert_to_xy(index, zoom),
+        }
+    }
+}
+
+impl std::fmt::Display for TileOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            TileOrder::ZOrder => write!(f, "zorder"),
+            TileOrder::Hilbert => write!(f, "hilbert"),
+        }
+    }
+}
+
+impl FromStr for TileOrder {
+    type Err = &'static str;
+
+    /// Parses `zorder` or `hilbert`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zorder" => Ok(TileOrder::ZOrder),
+            "hilbert" => Ok(TileOrder::Hilbert),
+            _ => Err("Unknown tile order"),
+        }
+    }
+}
+
+/// The range of metatiles (in metatile coordinates, i.e. x/scale) at `zoom` which cover this
+/// bbox (or the whole world). The range's zoom is lower than `zoom` by log2(scale).
+fn metatile_range(bbox: Option<&BBox>, zoom: u8, scale: u8) -> TileRange {
+    let shift = scale.trailing_zeros() as u8;
+    let range = match bbox {
+        None => TileRange::whole_world(zoom).unwrap(),
+        Some(bbox) => TileRange::from_bbox(bbox, zoom),
+    };
+
+    if zoom >= shift {
+        range.at_zoom(zoom - shift).unwrap()
+    } else {
+        // Everything is in the one metatile
+        TileRange::whole_world(0).unwrap()
+    }
+}
+
+/// The number of tiles in all the zooms before `zoom`. `zoom` can be up to 32.
+fn tiles_in_lower_zooms(zoom: u8) -> u64 {
+    if zoom == 32 {
+        // (4^32 - 1)/3, without overflowing
+        u64::MAX / 3
+    } else {
+        ((1u64 << (2 * zoom as u64)) - 1) / 3
+    }
+}
+
+/// The zoom of the tile at this position in `all_tiles()`, or None if it's past zoom 31.
+fn zoom_for_pyramid_index(index: u64) -> Option<u8> {
+    if index >= tiles_in_lower_zooms(32) {
+        return None;
+    }
+    // tiles_in_lower_zooms(z) <= index iff 4^z <= 3*index + 1
+    let zoom = (63 - (3 * index + 1).leading_zeros()) / 2;
+    Some(zoom as u8)
+}
+
+/// Iterates over all the tiles in the world, up to zoom 31.
+///
+/// `nth` (and hence `skip`) jump straight to the tile, so a large job can be split into ranges of
+/// `Tile::pyramid_index()`.
 pub struct AllTilesIterator {
     next_zoom: u8,
+    // position along the curve (z-order or Hilbert) in this zoom
     next_zorder: u64,
+    order: TileOrder,
 }
 
 impl Iterator for AllTilesIterator {
@@ -389,11 +865,17 @@ impl Iterator for AllTilesIterator {
 
     fn next(&mut self) -> Option<Tile> {
         let zoom = self.next_zoom;
-        let (x, y) = zorder_to_xy(self.next_zorder);
+        if zoom >= 32 {
+            return None;
+        }
+        let (x, y) = match self.order {
+            TileOrder::ZOrder => zorder_to_xy(self.next_zorder),
+            TileOrder::Hilbert => hilbert_to_xy(self.next_zorder, zoom),
+        };
         let tile = Tile::new(zoom, x, y);
 
-        let max_tile_no = 2u32.pow(zoom as u32) - 1;
-        if x == max_tile_no && y == max_tile_no {
+        let max_zorder = (1u64 << (2 * zoom as u64)) - 1;
+        if self.next_zorder == max_zorder {
             // we're at the end
             self.next_zoom = zoom + 1;
             self.next_zorder = 0;
@@ -403,12 +885,67 @@ impl Iterator for AllTilesIterator {
 
         tile
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.remaining()) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Tile> {
+        let index = self.next_index().saturating_add(n as u64);
+        match zoom_for_pyramid_index(index) {
+            None => {
+                self.next_zoom = 32;
+                self.next_zorder = 0;
+            }
+            Some(zoom) => {
+                self.next_zoom = zoom;
+                self.next_zorder = index - tiles_in_lower_zooms(zoom);
+            }
+        }
+        self.next()
+    }
+}
+
+/// There are more tiles than fit in a 32 bit `usize`, so this is only on 64 bit platforms.
+#[cfg(target_pointer_width = "64")]
+impl ExactSizeIterator for AllTilesIterator {}
+
+impl AllTilesIterator {
+    /// The position of the next tile, i.e. how many tiles have been returned (or skipped) so far.
+    /// With `TileOrder::ZOrder` this is the next tile's `pyramid_index()`, and with
+    /// `TileOrder::Hilbert` it's the `tile_id()`.
+    pub fn next_index(&self) -> u64 {
+        tiles_in_lower_zooms(self.next_zoom.min(32)) + self.next_zorder
+    }
+
+    /// The total number of tiles this iterator returns, i.e. all tiles from zoom 0 to 31.
+    pub fn total(&self) -> u64 {
+        tiles_in_lower_zooms(32)
+    }
+
+    /// How many tiles are left
+    pub fn remaining(&self) -> u64 {
+        self.total() - self.next_index()
+    }
 }
 
 pub fn all_tiles() -> AllTilesIterator {
     AllTilesIterator {
         next_zoom: 0,
         next_zorder: 0,
+        order: TileOrder::ZOrder,
+    }
+}
+
+/// All the tiles in the world, like `all_tiles()`, but each zoom is in Hilbert curve order.
+pub fn all_tiles_hilbert() -> AllTilesIterator {
+    AllTilesIterator {
+        next_zoom: 0,
+        next_zorder: 0,
+        order: TileOrder::Hilbert,
     }
 }
 
@@ -440,7 +977,7 @@ impl Iterator for AllTilesToZoomIterator {
     type Item = Tile;
 
     fn next(&mut self) -> Option<Tile> {
-        if self.next_zoom > self.max_zoom {
+        if self.next_zoom > self.max_zoom.min(31) {
             return None;
         }
         let tile = Tile::new(self.next_zoom, self.next_x, self.next_y);
@@ -460,7 +997,7 @@ impl Iterator for AllTilesToZoomIterator {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        if self.next_zoom > self.max_zoom {
+        if self.next_zoom > self.max_zoom.min(31) {
             return (0, Some(0));
         }
 
@@ -472,7 +1009,7 @@ impl Iterator for AllTilesToZoomIterator {
         let remaining_in_this_level = remaining_in_this_level.unwrap();
 
         let mut total: usize = remaining_in_this_level as usize;
-        for i in (self.next_zoom + 1)..(self.max_zoom + 1) {
+        for i in (self.next_zoom + 1)..(self.max_zoom.min(31) + 1) {
             let tiles_this_zoom = num_tiles_in_zoom(i);
             if tiles_this_zoom.is_none() {
                 return (std::usize::MAX, None);
@@ -490,19 +1027,82 @@ impl Iterator for AllTilesToZoomIterator {
         // If we've got to here, we know how big it is
         (total, Some(total))
     }
+
+    fn nth(&mut self, n: usize) -> Option<Tile> {
+        let index = self.next_index().saturating_add(n as u64);
+        match Tile::from_column_pyramid_index(index) {
+            Some(tile) if tile.zoom() <= self.max_zoom => {
+                self.next_zoom = tile.zoom();
+                self.next_x = tile.x();
+                self.next_y = tile.y();
+                self.next()
+            }
+            _ => {
+                self.next_zoom = self.max_zoom.min(31) + 1;
+                self.next_x = 0;
+                self.next_y = 0;
+                None
+            }
+        }
+    }
+}
+
+/// There are more tiles than fit in a 32 bit `usize`, so this is only on 64 bit platforms.
+#[cfg(target_pointer_width = "64")]
+impl ExactSizeIterator for AllTilesToZoomIterator {}
+
+impl AllTilesToZoomIterator {
+    /// The position of the next tile, i.e. how many tiles have been returned (or skipped) so far.
+    /// This is the next tile's `column_pyramid_index()`.
+    pub fn next_index(&self) -> u64 {
+        if self.next_zoom > self.max_zoom.min(31) {
+            return self.total();
+        }
+        tiles_in_lower_zooms(self.next_zoom)
+            + ((self.next_x as u64) << self.next_zoom)
+            + self.next_y as u64
+    }
+
+    /// The total number of tiles this iterator returns
+    pub fn total(&self) -> u64 {
+        tiles_in_lower_zooms(self.max_zoom.min(31) + 1)
+    }
+
+    /// How many tiles are left
+    pub fn remaining(&self) -> u64 {
+        self.total() - self.next_index()
+    }
 }
 
 pub struct AllSubTilesIterator {
     _tiles: Vec<Tile>,
+    total: u64,
+    remaining: u64,
 }
 
 impl AllSubTilesIterator {
     pub fn new_from_tile(base_tile: &Tile) -> Self {
         let new_tiles = match base_tile.subtiles() {
-            None => Vec::new(),
-            Some(t) => vec![t[0], t[1], t[2], t[3]],
+            Some(t) if base_tile.zoom() < 31 => vec![t[0], t[1], t[2], t[3]],
+            _ => Vec::new(),
         };
-        AllSubTilesIterator { _tiles: new_tiles }
+        // 4 + 16 + ... + 4^(31 - zoom)
+        let total = 4 * (tiles_in_lower_zooms(31 - base_tile.zoom().min(31)));
+        AllSubTilesIterator {
+            _tiles: new_tiles,
+            total,
+            remaining: total,
+        }
+    }
+
+    /// The total number of tiles this iterator returns, i.e. all the subtiles down to zoom 31.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The number of tiles which haven't been returned yet
+    pub fn remaining(&self) -> u64 {
+        self.remaining
     }
 }
 
@@ -514,11 +1114,22 @@ impl Iterator for AllSubTilesIterator {
             return None;
         }
         let next = self._tiles.remove(0);
-        if let Some(subtiles) = next.subtiles() {
-            self._tiles.extend_from_slice(&subtiles);
+        // Tiles only go to zoom 31
+        if next.zoom() < 31 {
+            if let Some(subtiles) = next.subtiles() {
+                self._tiles.extend_from_slice(&subtiles);
+            }
         }
+        self.remaining -= 1;
         Some(next)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
 }
 
 /// Metatiles are NxN tiles
@@ -535,20 +1146,19 @@ impl Metatile {
         if !scale.is_power_of_two() {
             return None;
         }
-        if zoom >= 100 {
-            None
-        } else if x < 2u32.pow(zoom as u32) && y < 2u32.pow(zoom as u32) {
-            let s = scale as u32;
-            let x = (x / s) * s;
-            let y = (y / s) * s;
-            Some(Metatile {
-                scale: scale,
-                zoom: zoom,
-                x: x,
-                y: y,
-            })
-        } else {
-            None
+        match 2u32.checked_pow(zoom as u32) {
+            Some(max) if x < max && y < max => {
+                let s = scale as u32;
+                let x = (x / s) * s;
+                let y = (y / s) * s;
+                Some(Metatile {
+                    scale: scale,
+                    zoom: zoom,
+                    x: x,
+                    y: y,
+                })
+            }
+            _ => None,
         }
     }
 
@@ -667,11 +1277,21 @@ impl FromStr for Metatile {
         }
         let caps = caps.unwrap();
 
-        // If the regex matches, then none of these should fail, right?
-        let scale = caps.name("scale").unwrap().as_str().parse().unwrap();
-        let zoom = caps.name("zoom").unwrap().as_str().parse().unwrap();
-        let x = caps.name("x").unwrap().as_str().parse().unwrap();
-        let y = caps.name("y").unwrap().as_str().parse().unwrap();
+        // The regex only limits the number of digits, so these can still be too large
+        let scale = caps
+            .name("scale")
+            .unwrap()
+            .as_str()
+            .parse()
+            .map_err(|_| ())?;
+        let zoom = caps
+            .name("zoom")
+            .unwrap()
+            .as_str()
+            .parse()
+            .map_err(|_| ())?;
+        let x = caps.name("x").unwrap().as_str().parse().map_err(|_| ())?;
+        let y = caps.name("y").unwrap().as_str().parse().map_err(|_| ())?;
 
         match Metatile::new(scale, zoom, x, y) {
             None => {
@@ -687,17 +1307,22 @@ impl FromStr for Metatile {
 #[derive(Debug)]
 pub struct MetatilesIterator {
     scale: u8,
+    minzoom: u8,
     curr_zoom: u8,
     maxzoom: u8,
+    // position along the curve in this zoom. For `TileOrder::ZOrder` it's the z-order of the
+    // metatile relative to the top left of the bbox, for Hilbert it's the Hilbert index.
     curr_zorder: u64,
     bbox: Option<BBox>,
+    order: TileOrder,
 
     // In metatile coords, i.e. x/scale
     curr_zoom_width_height: Option<(u32, u32)>,
     curr_zoom_start_xy: Option<(u32, u32)>,
 
-    // If we're reading from a file
+    // If we're reading from a file, the number of lines in it, and how many have been read
     total: Option<usize>,
+    lines_read: usize,
     tile_list_file: Option<BufReader<File>>,
 }
 
@@ -705,6 +1330,7 @@ impl MetatilesIterator {
     pub fn all(scale: u8) -> Self {
         MetatilesIterator {
             scale: scale,
+            minzoom: 0,
             curr_zoom: 0,
             curr_zorder: 0,
             bbox: None,
@@ -712,7 +1338,9 @@ impl MetatilesIterator {
             curr_zoom_width_height: None,
             curr_zoom_start_xy: None,
             total: None,
+            lines_read: 0,
             tile_list_file: None,
+            order: TileOrder::ZOrder,
         }
     }
 
@@ -724,6 +1352,7 @@ impl MetatilesIterator {
     pub fn new_for_bbox_zoom(scale: u8, bbox: &Option<BBox>, minzoom: u8, maxzoom: u8) -> Self {
         let mut it = MetatilesIterator {
             scale: scale,
+            minzoom: minzoom,
             curr_zoom: minzoom,
             curr_zorder: 0,
             bbox: bbox.clone(),
@@ -731,7 +1360,9 @@ impl MetatilesIterator {
             curr_zoom_width_height: None,
             curr_zoom_start_xy: None,
             total: None,
+            lines_read: 0,
             tile_list_file: None,
+            order: TileOrder::ZOrder,
         };
         it.set_zoom_width_height();
         it.set_zoom_start_xy();
@@ -739,6 +1370,10 @@ impl MetatilesIterator {
         it
     }
 
+    /// Iterate over the metatiles listed in this file, one per line.
+    ///
+    /// This panics on I/O errors, and stops at the first line that isn't a metatile, see
+    /// `tilelist::TileListReader` for a more robust reader.
     pub fn new_from_filelist(filename: String) -> Self {
         let mut file = BufReader::new(File::open(&filename).unwrap());
         file.seek(SeekFrom::Start(0)).unwrap();
@@ -751,6 +1386,7 @@ impl MetatilesIterator {
 
         MetatilesIterator {
             scale: 0,
+            minzoom: 0,
             curr_zoom: 0,
             curr_zorder: 0,
             bbox: None,
@@ -758,65 +1394,44 @@ impl MetatilesIterator {
             curr_zoom_width_height: None,
             curr_zoom_start_xy: None,
             total: Some(total),
+            lines_read: 0,
             tile_list_file: Some(file),
+            order: TileOrder::ZOrder,
         }
     }
 
+    /// Return the metatiles in each zoom in this order. Has no effect when reading from a file.
+    ///
+    /// With `TileOrder::Hilbert`, the Hilbert curve for all the metatiles in that zoom is used,
+    /// so the order of 2 metatiles doesn't depend on the bbox.
+    pub fn with_order(mut self, order: TileOrder) -> Self {
+        self.order = order;
+        self
+    }
+
     /// Update the `self.curr_zoom_width_height` variable with the correct value for this zoom
     /// (`self.curr_zoom`)
     fn set_zoom_width_height(&mut self) {
-        if let Some(ref bbox) = self.bbox {
-            let scale = self.scale as u32;
-            let zoom = self.curr_zoom;
-            // TODO is this x/y lat/lon the right way around?
-            let (x1, y1) = lat_lon_to_tile(bbox.top, bbox.left, zoom);
-            let (x1, y1) = (x1 / scale, y1 / scale);
-            let (x2, y2) = lat_lon_to_tile(bbox.bottom, bbox.right, zoom);
-            let (x2, y2) = (x2 / scale, y2 / scale);
-
-            let width = x2 - x1 + 1;
-            let height = y2 - y1 + 1;
-
-            self.curr_zoom_width_height = Some((width, height));
+        // TileRange only goes to zoom 31
+        if self.bbox.is_some() && self.curr_zoom < 32 {
+            let range = self.curr_zoom_metatile_range();
+            self.curr_zoom_width_height = Some((range.width(), range.height()));
         }
     }
 
     fn set_zoom_start_xy(&mut self) {
-        if self.bbox.is_none() {
-            return;
+        if self.bbox.is_some() && self.curr_zoom < 32 {
+            let range = self.curr_zoom_metatile_range();
+            self.curr_zoom_start_xy = Some((range.x_min(), range.y_min()));
         }
-
-        let top = match self.bbox {
-            None => 90.,
-            Some(ref b) => b.top,
-        };
-        let left = match self.bbox {
-            None => -180.,
-            Some(ref b) => b.left,
-        };
-        // TODO is this x/y lat/lon the right way around?
-        let (x1, y1) = lat_lon_to_tile(top, left, self.curr_zoom);
-        self.curr_zoom_start_xy = Some((x1 / self.scale as u32, y1 / self.scale as u32));
     }
 
     fn next_from_zorder(&mut self) -> Option<Metatile> {
-        // have to set a value, but we're never going to read it
-        #[allow(unused_assignments)]
-        let mut zoom = 0;
-        #[allow(unused_assignments)]
-        let mut x = 0;
-        #[allow(unused_assignments)]
-        let mut y = 0;
-
         let scale = self.scale as u32;
 
-        loop {
-            if self.curr_zoom > self.maxzoom {
-                // We're finished
-                return None;
-            }
-
-            zoom = self.curr_zoom;
+        // Tiles only go to zoom 31
+        while self.curr_zoom <= self.maxzoom && self.curr_zoom < 32 {
+            let zoom = self.curr_zoom;
             let (width, height) = match self.curr_zoom_width_height {
                 None => {
                     let max_num = 2u32.pow(zoom as u32);
@@ -831,37 +1446,61 @@ impl MetatilesIterator {
 
             let max_zorder_for_zoom = xy_to_zorder(width - 1, height - 1);
 
-            let (i, j) = zorder_to_xy(self.curr_zorder);
-            let bits = match self.curr_zoom_start_xy {
-                None => (i, j),
-                Some(start) => (start.0 + i, start.1 + j),
-            };
-            x = bits.0;
-            y = bits.1;
-
             if self.curr_zorder > max_zorder_for_zoom {
-                // Next zoom
-                // we're at the end
+                // we're at the end, go to the next zoom
                 self.curr_zoom = zoom + 1;
                 self.curr_zorder = 0;
                 self.set_zoom_start_xy();
                 self.set_zoom_width_height();
-            } else if i > width || j > height {
-                // If the bbox is non-square, there will be X (or Y) tiles which are outside
-                // the bbox. Rather than go to the next zoom level, we want to contine to look at
-                // the next tile in order, and keep going until we get a tile that's inside the
-                // bbox.  to the next tile
-                self.curr_zorder += 1;
                 continue;
-            } else {
-                // This z order is OK
-                self.curr_zorder += 1;
-                break;
             }
+
+            // The z-order is relative to the top left of the bbox
+            let (i, j) = zorder_to_xy(self.curr_zorder);
+            self.curr_zorder += 1;
+            if i >= width || j >= height {
+                // If the bbox is non-square, there will be X (or Y) tiles which are outside
+                // the bbox. Rather than go to the next zoom level, we want to contine to look at
+                // the next tile in order, and keep going until we get a tile that's inside the
+                // bbox.
+                continue;
+            }
+
+            let (x, y) = match self.curr_zoom_start_xy {
+                None => (i, j),
+                Some(start) => (start.0 + i, start.1 + j),
+            };
+            return Metatile::new(self.scale, zoom, x * scale, y * scale);
         }
 
-        let (x, y) = (x * scale, y * scale);
-        Metatile::new(self.scale, zoom, x, y)
+        None
+    }
+
+    /// The range of metatiles, in metatile coordinates (i.e. x/scale), for the current zoom
+    fn curr_zoom_metatile_range(&self) -> TileRange {
+        metatile_range(self.bbox.as_ref(), self.curr_zoom, self.scale)
+    }
+
+    fn next_from_hilbert(&mut self) -> Option<Metatile> {
+        let scale = self.scale as u32;
+
+        // TileRange only goes to zoom 31
+        while self.curr_zoom <= self.maxzoom && self.curr_zoom < 32 {
+            let range = self.curr_zoom_metatile_range();
+            match range.next_hilbert_index(self.curr_zorder) {
+                None => {
+                    self.curr_zoom += 1;
+                    self.curr_zorder = 0;
+                }
+                Some(index) => {
+                    self.curr_zorder = index + 1;
+                    let (x, y) = hilbert_to_xy(index, range.zoom());
+                    return Metatile::new(self.scale, self.curr_zoom, x * scale, y * scale);
+                }
+            }
+        }
+
+        None
     }
 
     fn next_from_file(&mut self) -> Option<Metatile> {
@@ -872,12 +1511,198 @@ impl MetatilesIterator {
         // remove trailing newline
         let s = s.trim_end();
 
+        self.lines_read += 1;
+
         s.parse().ok()
     }
 
+    /// The number of metatiles in this zoom
+    fn metatiles_in_zoom(&self, zoom: u8) -> u64 {
+        metatile_range(self.bbox.as_ref(), zoom, self.scale).count()
+    }
+
+    /// When reading from a file, the number of lines in the file, otherwise None. See
+    /// `total_metatiles()` for the number of metatiles from a bbox.
     pub fn total(&self) -> Option<usize> {
         self.total
     }
+
+    /// The total number of metatiles this iterator returns, from `minzoom`. When reading from a
+    /// file, it's the number of lines in the file.
+    pub fn total_metatiles(&self) -> u64 {
+        match self.total {
+            Some(total) => total as u64,
+            None => (self.minzoom..=self.maxzoom.min(31))
+                .map(|zoom| self.metatiles_in_zoom(zoom))
+                .sum(),
+        }
+    }
+
+    /// The number of metatiles which haven't been returned yet. When reading from a file, it's
+    /// the number of lines which haven't been read.
+    pub fn remaining(&self) -> u64 {
+        if let Some(total) = self.total {
+            return total.saturating_sub(self.lines_read) as u64;
+        }
+        let last_zoom = self.maxzoom.min(31);
+        if self.curr_zoom > last_zoom {
+            return 0;
+        }
+
+        let range = self.curr_zoom_metatile_range();
+        let done_in_this_zoom = match self.order {
+            TileOrder::Hilbert => range.count_before(TileOrder::Hilbert, self.curr_zorder),
+            TileOrder::ZOrder => {
+                // z-order is relative to the top left of the range
+                TileRange::new(31, 0, range.width() - 1, 0, range.height() - 1)
+                    .unwrap()
+                    .count_before(TileOrder::ZOrder, self.curr_zorder)
+            }
+        };
+
+        let later_zooms: u64 = (self.curr_zoom + 1..=last_zoom)
+            .map(|zoom| self.metatiles_in_zoom(zoom))
+            .sum();
+
+        range.count() - done_in_this_zoom + later_zooms
+    }
+
+    /// The current position of this iterator, which can be saved (as a string), and later used
+    /// with `from_checkpoint` to carry on from the same place. None if reading from a file.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{BBox, MetatilesIterator};
+    /// let bbox = BBox::new(55.5, -10.7, 51.4, -5.3).unwrap();
+    /// let mut it = MetatilesIterator::new_for_bbox_zoom(8, &Some(bbox), 0, 14);
+    /// it.nth(100);
+    /// let token = it.checkpoint().unwrap().to_string();
+    ///
+    /// // ... the process restarts ...
+    /// let mut resumed = MetatilesIterator::from_checkpoint(&token.parse().unwrap());
+    /// assert_eq!(resumed.next(), it.next());
+    /// ```
+    pub fn checkpoint(&self) -> Option<MetatilesCheckpoint> {
+        if self.tile_list_file.is_some() {
+            return None;
+        }
+        Some(MetatilesCheckpoint {
+            scale: self.scale,
+            order: self.order,
+            minzoom: self.minzoom,
+            zoom: self.curr_zoom,
+            maxzoom: self.maxzoom,
+            position: self.curr_zorder,
+            bbox: self.bbox.clone(),
+        })
+    }
+
+    /// Create an iterator which carries on from this checkpoint, i.e. returns the same metatiles
+    /// as the iterator would have, after `checkpoint()` was called.
+    pub fn from_checkpoint(checkpoint: &MetatilesCheckpoint) -> Self {
+        let mut it = MetatilesIterator {
+            scale: checkpoint.scale,
+            minzoom: checkpoint.minzoom,
+            curr_zoom: checkpoint.zoom,
+            curr_zorder: checkpoint.position,
+            bbox: checkpoint.bbox.clone(),
+            maxzoom: checkpoint.maxzoom,
+            curr_zoom_width_height: None,
+            curr_zoom_start_xy: None,
+            total: None,
+            lines_read: 0,
+            tile_list_file: None,
+            order: checkpoint.order,
+        };
+        it.set_zoom_width_height();
+        it.set_zoom_start_xy();
+
+        it
+    }
+}
+
+/// The position of a `MetatilesIterator`, see `MetatilesIterator::checkpoint()`.
+///
+/// It can be converted to & from a short string like
+/// `zorder scale=8 minzoom=0 zoom=12 maxzoom=18 position=12345 bbox=-10.7,51.4,-5.3,55.5`, in the
+/// same style as a `shard::Shard`. The bbox is left out for the whole world.
+#[derive(PartialEq, Debug, Clone)]
+pub struct MetatilesCheckpoint {
+    scale: u8,
+    order: TileOrder,
+    minzoom: u8,
+    zoom: u8,
+    maxzoom: u8,
+    // position along the curve in this zoom
+    position: u64,
+    bbox: Option<BBox>,
+}
+
+impl MetatilesCheckpoint {
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    /// The zoom the iterator is at
+    pub fn zoom(&self) -> u8 {
+        self.zoom
+    }
+
+    pub fn bbox(&self) -> Option<&BBox> {
+        self.bbox.as_ref()
+    }
+}
+
+impl std::fmt::Display for MetatilesCheckpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} scale={} minzoom={} zoom={} maxzoom={} position={}",
+            self.order, self.scale, self.minzoom, self.zoom, self.maxzoom, self.position
+        )?;
+        if let Some(ref bbox) = self.bbox {
+            write!(f, " bbox={}", bbox)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MetatilesCheckpoint {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let order = parts.next().ok_or("Empty checkpoint")?.parse()?;
+        let (mut scale, mut minzoom, mut zoom, mut maxzoom, mut position, mut bbox) =
+            (None, None, None, None, None, None);
+        for part in parts {
+            let mut kv = part.splitn(2, '=');
+            let (k, v) = (kv.next().unwrap(), kv.next().ok_or("Missing =")?);
+            match k {
+                "scale" => scale = Some(v.parse::<u8>().map_err(|_| "Invalid scale")?),
+                "minzoom" => minzoom = Some(v.parse::<u8>().map_err(|_| "Invalid min zoom")?),
+                "zoom" => zoom = Some(v.parse::<u8>().map_err(|_| "Invalid zoom")?),
+                "maxzoom" => maxzoom = Some(v.parse::<u8>().map_err(|_| "Invalid max zoom")?),
+                "position" => position = Some(v.parse::<u64>().map_err(|_| "Invalid position")?),
+                "bbox" => bbox = Some(v.parse::<BBox>()?),
+                _ => return Err("Unknown checkpoint field"),
+            }
+        }
+
+        let scale = scale.ok_or("Missing scale")?;
+        if !scale.is_power_of_two() {
+            return Err("Invalid scale");
+        }
+
+        Ok(MetatilesCheckpoint {
+            scale,
+            order,
+            minzoom: minzoom.ok_or("Missing min zoom")?,
+            zoom: zoom.ok_or("Missing zoom")?,
+            maxzoom: maxzoom.ok_or("Missing max zoom")?,
+            position: position.ok_or("Missing position")?,
+            bbox,
+        })
+    }
 }
 
 impl Iterator for MetatilesIterator {
@@ -886,10 +1711,22 @@ impl Iterator for MetatilesIterator {
     fn next(&mut self) -> Option<Self::Item> {
         if self.tile_list_file.is_some() {
             self.next_from_file()
+        } else if self.order == TileOrder::Hilbert {
+            self.next_from_hilbert()
         } else {
             self.next_from_zorder()
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = usize::try_from(self.remaining()).ok();
+        if self.tile_list_file.is_some() {
+            // Reading stops at the first line that isn't a metatile
+            (0, remaining)
+        } else {
+            (remaining.unwrap_or(usize::MAX), remaining)
+        }
+    }
 }
 
 /// Metatiles as found by mod_tile, always 8x8
@@ -984,51 +1821,109 @@ fn tile_nw_lat_lon(zoom: u8, x: f32, y: f32) -> LatLon {
 
 /// Return the x,y of a tile which has this lat/lon for this zoom level
 pub fn lat_lon_to_tile(lat: f32, lon: f32, zoom: u8) -> (u32, u32) {
-    // TODO do this at compile time?
-    #[allow(non_snake_case)]
-    let MAX_LAT: f64 = std::f64::consts::PI.sinh().atan();
+    let (x, y) = lat_lon_to_tile_fraction(lat as f64, lon as f64, zoom);
 
-    let lat: f64 = lat as f64;
-    let lat = lat.to_radians();
+    (x.trunc() as u32, y.trunc() as u32)
+}
 
-    let lon: f64 = lon as f64;
+/// The x,y of this lat/lon, in tiles at this zoom, including the position within the tile. e.g.
+/// `(1.5, 2.25)` is half way across, and a quarter way down, tile x=1, y=2.
+fn lat_lon_to_tile_fraction(lat: f64, lon: f64, zoom: u8) -> (f64, f64) {
+    let max_lat = std::f64::consts::PI.sinh().atan();
+    let lat = lat.to_radians().max(-max_lat).min(max_lat);
 
-    // Clip the latitude to the max & min (~85.0511)
-    let lat = if lat > MAX_LAT {
-        MAX_LAT
-    } else if lat < -MAX_LAT {
-        -MAX_LAT
-    } else {
-        lat
-    };
+    let n = 2f64.powi(zoom as i32);
+    let x = n * ((lon + 180.) / 360.);
+    let y = n * (1. - ((lat.tan() + (1. / lat.cos())).ln() / std::f64::consts::PI)) / 2.;
+
+    (x, y)
+}
+
+/// The lat/lon of this x,y (in tiles) at this zoom, the opposite of `lat_lon_to_tile_fraction`.
+/// None if it's outside the world.
+fn tile_fraction_to_lat_lon(x: f64, y: f64, zoom: u8) -> Option<LatLon> {
+    let n = 2f64.powi(zoom as i32);
+    if !(0. ..=n).contains(&x) || !(0. ..=n).contains(&y) {
+        return None;
+    }
+    let lon = x / n * 360. - 180.;
+    let lat = ((1. - 2. * y / n) * std::f64::consts::PI)
+        .sinh()
+        .atan()
+        .to_degrees();
+
+    LatLon::new(lat as f32, lon as f32)
+}
+
+/// The global pixel x,y of this lat/lon at this zoom, i.e. the pixel in an image of the whole world
+/// made from tiles `tile_size` pixels wide. `0,0` is the top left (north west) corner of the world.
+///
+/// Tiles are usually 256 or 512 pixels. `@2x` (retina) images of 256 pixel tiles are 512 pixels
+/// wide, so use 512 for them.
+///
+/// ```
+/// # use slippy_map_tiles::lat_lon_to_global_pixel;
+/// assert_eq!(lat_lon_to_global_pixel(0., 0., 0, 256), (128., 128.));
+/// assert_eq!(lat_lon_to_global_pixel(0., 90., 1, 512), (768., 512.));
+/// ```
+pub fn lat_lon_to_global_pixel(lat: f32, lon: f32, zoom: u8, tile_size: u32) -> (f64, f64) {
+    let (x, y) = lat_lon_to_tile_fraction(lat as f64, lon as f64, zoom);
+    (x * tile_size as f64, y * tile_size as f64)
+}
 
-    let n: f64 = 2f64.powi(zoom as i32);
-    let xtile: u32 = (n * ((lon + 180.) / 360.)).trunc() as u32;
-    let ytile: u32 = (n * (1. - ((lat.tan() + (1. / lat.cos())).ln() / std::f64::consts::PI)) / 2.)
-        .trunc() as u32;
+/// The lat/lon of this global pixel at this zoom, the opposite of `lat_lon_to_global_pixel`.
+/// Returns None if the pixel is outside the world.
+pub fn global_pixel_to_lat_lon(px: f64, py: f64, zoom: u8, tile_size: u32) -> Option<LatLon> {
+    tile_fraction_to_lat_lon(px / tile_size as f64, py / tile_size as f64, zoom)
+}
+
+/// The tile which this global pixel is in at this zoom, and the pixel x,y within that tile's image.
+/// Returns None if the pixel is outside the world.
+///
+/// ```
+/// # use slippy_map_tiles::{global_pixel_to_tile, Tile};
+/// assert_eq!(
+///     global_pixel_to_tile(600., 300.5, 2, 256),
+///     Some((Tile::new(2, 2, 1).unwrap(), (88., 44.5)))
+/// );
+/// ```
+pub fn global_pixel_to_tile(
+    px: f64,
+    py: f64,
+    zoom: u8,
+    tile_size: u32,
+) -> Option<(Tile, (f64, f64))> {
+    let world_size = 2f64.powi(zoom as i32) * tile_size as f64;
+    if !(0. ..world_size).contains(&px) || !(0. ..world_size).contains(&py) {
+        return None;
+    }
+    let tile_size = tile_size as f64;
+    let (x, y) = ((px / tile_size).floor(), (py / tile_size).floor());
+    let tile = Tile::new(zoom, x as u32, y as u32)?;
 
-    (xtile, ytile)
+    Some((tile, (px - x * tile_size, py - y * tile_size)))
 }
 
 /// Return the x,y of a tile which (for this zoom) has this web mercator 3857 x/y, and then the x,y
-/// of the pixel within that image (presuming a 256x256 image)
+/// of the pixel within that image (presuming a 256x256 image). Points outside the world are moved
+/// to the nearest edge.
+///
+/// See `mercator::merc_to_tile` for other tile sizes.
+///
+/// ```
+/// # use slippy_map_tiles::merc_location_to_tile_coords;
+/// assert_eq!(
+///     merc_location_to_tile_coords(-696_861.5, 7_048_168.3, 10),
+///     ((494, 331), (49, 231))
+/// );
+/// ```
 pub fn merc_location_to_tile_coords(x: f64, y: f64, zoom: u8) -> ((u32, u32), (u32, u32)) {
-    let num_tiles = 2u32.pow(zoom as u32) as f64;
-    let global_extent = 20_037_508.342789244;
-    let tile_width = (2. * global_extent) / num_tiles;
+    let (px, py) = mercator::merc_to_global_pixel(x, y, zoom, 256);
+    let last_pixel = 2f64.powi(zoom as i32) * 256. - 1.;
+    let (px, py) = (px.max(0.).min(last_pixel), py.max(0.).min(last_pixel));
+    let (tile, (px, py)) = global_pixel_to_tile(px, py, zoom, 256).unwrap();
 
-    (
-        // location within the tile
-        (
-            ((x + global_extent) / tile_width) as u32,
-            ((y + global_extent) / tile_width) as u32,
-        ),
-        // Tile x/y
-        (
-            (((x + global_extent) % tile_width) / tile_width * 256.) as u32,
-            (num_tiles - ((y + global_extent) % tile_width) / tile_width * 256. - 1.) as u32,
-        ),
-    )
+    ((tile.x, tile.y), (px as u32, py as u32))
 }
 
 /// How many tiles does this bbox cover at this zoom
@@ -1090,14 +1985,23 @@ impl LatLon {
         self.lon
     }
 
-    /// Convert to Web Mercator format (SRID 3857)
+    /// Convert to Web Mercator format (SRID 3857). See the `mercator` module for `f64`.
     pub fn to_3857(&self) -> (f32, f32) {
-        let x = self.lon() * 20037508.34 / 180.;
-        let pi = std::f32::consts::PI;
-        let y = ((90. + self.lat()) * pi / 360.).tan().ln() / (pi / 180.);
-        let y = y * 20037508.34 / 180.;
+        let (x, y) = mercator::lat_lon_to_merc(self.lat as f64, self.lon as f64);
+
+        (x as f32, y as f32)
+    }
 
-        (x, y)
+    /// Convert from Web Mercator format (SRID 3857). Returns None if it's outside the world.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::LatLon;
+    /// let p = LatLon::from_3857(612_257.2, 7_342_482.3).unwrap();
+    /// assert!((p.lat() - 54.9).abs() < 1e-5 && (p.lon() - 5.5).abs() < 1e-5);
+    /// ```
+    pub fn from_3857(x: f64, y: f64) -> Option<LatLon> {
+        let (lat, lon) = mercator::merc_to_lat_lon(x, y);
+        LatLon::new(lat as f32, lon as f32)
     }
 
     /// What tile is this point at on this zoom level
@@ -1105,6 +2009,14 @@ impl LatLon {
         let (x, y) = lat_lon_to_tile(self.lat, self.lon, zoom);
         Tile::new(zoom, x, y).unwrap()
     }
+
+    /// What tile in `tms` is this point in at this zoom (see `Tile::extent_in`). None if the CRS
+    /// is `Other`, the point is outside the tile matrix, or the column or row is too big for a
+    /// `Tile`.
+    pub fn tile_in(&self, tms: &TileMatrixSet, zoom: u8) -> Option<Tile> {
+        let (x, y) = tms.lat_lon_to_tile(self.lat as f64, self.lon as f64, zoom)?;
+        Tile::new(zoom, x, y)
+    }
 }
 
 /// A Bounding box
@@ -1156,6 +2068,17 @@ impl BBox {
         tile.bbox()
     }
 
+    /// The Web Mercator (EPSG:3857) extent of this bbox in metres. Latitudes are clamped to
+    /// ±85.0511°. None if the bbox goes over the antimeridian, or the top is below the bottom.
+    pub fn to_3857(&self) -> Option<mercator::Extent> {
+        mercator::bbox_to_extent(self)
+    }
+
+    /// The bbox of this Web Mercator (EPSG:3857) extent. None if it's outside the world.
+    pub fn from_3857(extent: &mercator::Extent) -> Option<BBox> {
+        mercator::extent_to_bbox(extent)
+    }
+
     /// Return true iff this point is in this bbox
     pub fn contains_point(&self, point: &LatLon) -> bool {
         point.lat <= self.top
@@ -1173,15 +2096,17 @@ impl BBox {
             && self.bottom < other.top
     }
 
-    /// Iterate over all the tiles from z0 onwards that this bbox is in
-    pub fn tiles(&self) -> BBoxTilesIterator {
+    /// Iterate over all the tiles from z0 to z31 that this bbox is in. See `BBoxTilesIterator`
+    /// for how to change the zooms & order.
+    pub fn tiles(&self) -> BBoxTilesIterator<'_> {
         BBoxTilesIterator::new(self)
     }
 
     /// Iterate over all the metatiles from z0 onwards that this bbox is in
     pub fn metatiles(&self, scale: u8) -> MetatilesIterator {
         let bbox: BBox = (*self).clone();
-        MetatilesIterator {
+        let mut it = MetatilesIterator {
+            minzoom: 0,
             curr_zoom: 0,
             maxzoom: 32,
             bbox: Some(bbox),
@@ -1190,8 +2115,14 @@ impl BBox {
             curr_zoom_width_height: None,
             curr_zoom_start_xy: None,
             total: None,
+            lines_read: 0,
             tile_list_file: None,
-        }
+            order: TileOrder::ZOrder,
+        };
+        it.set_zoom_width_height();
+        it.set_zoom_start_xy();
+
+        it
     }
 
     /// Return the top value of this bbox
@@ -1216,12 +2147,41 @@ impl BBox {
 
     /// For this zoom level, return all the tiles that cover this bbox
     pub fn tiles_for_zoom(&self, zoom: u8) -> impl Iterator<Item = Tile> {
-        let top_left_tile = lat_lon_to_tile(self.top, self.left, zoom);
-        let bottom_right_tile = lat_lon_to_tile(self.bottom, self.right, zoom);
+        self.tile_range(zoom).iter()
+    }
+
+    /// For this zoom level, the range of tiles that cover this bbox
+    pub fn tile_range(&self, zoom: u8) -> TileRange {
+        TileRange::from_bbox(self, zoom)
+    }
+
+    /// The tiles at this zoom in `tms` which overlap this bbox, row by row (see
+    /// `Tile::extent_in`). None if `tms` has no tile matrix for this zoom, or it's more than
+    /// `2^zoom` tiles wide or high (e.g. `WorldCRS84Quad`), so its tiles can't be a `Tile`. Use
+    /// `TileMatrixSet::tiles_for_bbox` for those.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{BBox, Tile};
+    /// # use slippy_map_tiles::tilematrixset::TileMatrixSet;
+    /// let bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+    /// let tms = TileMatrixSet::world_mercator_wgs84_quad();
+    /// let tiles: Vec<Tile> = bbox.tiles_for_zoom_in(&tms, 5).unwrap().collect();
+    /// assert_eq!(tiles, vec![Tile::new(5, 14, 10).unwrap(), Tile::new(5, 15, 10).unwrap()]);
+    /// ```
+    pub fn tiles_for_zoom_in<'a>(
+        &self,
+        tms: &'a TileMatrixSet,
+        zoom: u8,
+    ) -> Option<impl Iterator<Item = Tile> + 'a> {
+        let (width, height) = tms.tile_matrix(zoom)?.matrix_size();
+        if zoom > 31 || width > 1 << zoom || height > 1 << zoom {
+            return None;
+        }
 
-        (top_left_tile.0..=bottom_right_tile.0)
-            .flat_map(move |x| (top_left_tile.1..=bottom_right_tile.1).map(move |y| (x, y)))
-            .map(move |(x, y)| Tile::new(zoom, x, y).unwrap())
+        Some(
+            tms.tiles_for_bbox(self, zoom)
+                .map(move |(x, y)| Tile::new(zoom, x, y).unwrap()),
+        )
     }
 
     /// Returns the LatLon for the centre of this bbox
@@ -1255,6 +2215,18 @@ impl BBox {
     }
 }
 
+/// Writes `$MINLON,$MINLAT,$MAXLON,$MAXLAT`, which can be parsed back into the same BBox.
+impl std::fmt::Display for BBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // f32's Display is the shortest string which parses back to the same number
+        write!(
+            f,
+            "{},{},{},{}",
+            self.left, self.bottom, self.right, self.top
+        )
+    }
+}
+
 impl FromStr for BBox {
     type Err = &'static str;
 
@@ -1301,20 +2273,369 @@ impl FromStr for BBox {
     }
 }
 
+/// How `BBoxTilesIterator` goes down the tile pyramid
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
+pub enum Traversal {
+    /// All the tiles in a zoom, then the tiles in the next zoom. Each zoom is in z-order.
+    #[default]
+    BreadthFirst,
+    /// A tile, then all of its subtiles, before the next tile in that zoom. Only one tile per zoom
+    /// is stored.
+    DepthFirst,
+}
+
+/// Returns true if the subtiles of this tile should be skipped
+type PruneFn<'a> = Box<dyn FnMut(&Tile) -> bool + 'a>;
+
+/// The tiles which a bbox is in, see `BBox::tiles()`.
+///
+/// By default, all the tiles from zoom 0 to 31 are returned. Use `with_min_zoom` & `with_max_zoom`
+/// to return fewer zooms, and `with_prune` to skip the subtiles of some tiles.
 pub struct BBoxTilesIterator<'a> {
     bbox: &'a BBox,
-    tiles: Vec<Tile>,
-    tile_index: usize,
+    minzoom: u8,
+    maxzoom: u8,
+    traversal: Traversal,
+    // The current zoom. More than maxzoom when finished. For depth first, the zoom of the top
+    // level tiles.
+    zoom: u8,
+    // The tiles in this zoom which overlap the bbox, None if not calculated yet
+    range: Option<TileRange>,
+    next_zorder: u64,
+    // Depth first: the tiles we are in, and the number of their subtiles we have looked at
+    stack: Vec<(Tile, u8)>,
+    prune: Option<PruneFn<'a>>,
+    // Breadth first: the z-orders in this zoom, and in the next zoom, which are pruned (i.e. the
+    // tile, or an ancestor, was pruned), as sorted non-touching intervals (inclusive)
+    pruned: Vec<(u64, u64)>,
+    next_pruned: Vec<(u64, u64)>,
+    // The first interval in `pruned` which could include `next_zorder`
+    pruned_pos: usize,
 }
 
 impl<'a> BBoxTilesIterator<'a> {
     pub fn new(bbox: &'a BBox) -> BBoxTilesIterator<'a> {
-        // Everything is in 0/0/0, so start with that.
         BBoxTilesIterator {
             bbox: bbox,
-            tiles: vec![Tile::new(0, 0, 0).unwrap()],
-            tile_index: 0,
+            minzoom: 0,
+            maxzoom: 31,
+            traversal: Traversal::BreadthFirst,
+            zoom: 0,
+            range: None,
+            next_zorder: 0,
+            stack: Vec::new(),
+            prune: None,
+            pruned: Vec::new(),
+            next_pruned: Vec::new(),
+            pruned_pos: 0,
+        }
+    }
+
+    /// Start at this zoom, rather than zoom 0
+    pub fn with_min_zoom(mut self, minzoom: u8) -> Self {
+        self.minzoom = minzoom;
+        self.zoom = minzoom;
+        self.range = None;
+        self.next_zorder = 0;
+        self
+    }
+
+    /// Stop after this zoom, rather than zoom 31
+    pub fn with_max_zoom(mut self, maxzoom: u8) -> Self {
+        self.maxzoom = std::cmp::min(maxzoom, 31);
+        self
+    }
+
+    /// Go through the tiles in this order
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{BBox, Traversal};
+    /// let bbox = BBox::new(53.61, -6.66, 53.08, -5.98).unwrap();
+    /// let tiles: Vec<_> = bbox
+    ///     .tiles()
+    ///     .with_min_zoom(5)
+    ///     .with_max_zoom(6)
+    ///     .with_traversal(Traversal::DepthFirst)
+    ///     .map(|t| (t.zoom(), t.x(), t.y()))
+    ///     .collect();
+    /// assert_eq!(tiles, vec![(5, 15, 10), (6, 30, 20)]);
+    /// ```
+    pub fn with_traversal(mut self, traversal: Traversal) -> Self {
+        self.traversal = traversal;
+        self
+    }
+
+    /// `prune` is called for each tile returned (apart from those at the max zoom). If it returns
+    /// true, the subtiles of that tile are not returned.
+    ///
+    /// With `Traversal::BreadthFirst`, the pruned parts of the current & next zoom are stored, as
+    /// ranges of z-orders, so pruning a few large areas uses little memory.
+    /// `total()` & `remaining()` don't know about pruning, so they are upper bounds.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::BBox;
+    /// let bbox = BBox::new(53.61, -6.66, 53.08, -5.98).unwrap();
+    /// // Nothing in zoom 4 or above, apart from the subtiles of 3/3/2
+    /// let tiles: Vec<_> = bbox
+    ///     .tiles()
+    ///     .with_max_zoom(5)
+    ///     .with_prune(|t| t.zoom() == 3 && t.x() != 4)
+    ///     .map(|t| (t.zoom(), t.x(), t.y()))
+    ///     .collect();
+    /// assert_eq!(tiles, vec![(0, 0, 0), (1, 0, 0), (2, 1, 1), (3, 3, 2)]);
+    /// ```
+    pub fn with_prune<F>(mut self, prune: F) -> Self
+    where
+        F: FnMut(&Tile) -> bool + 'a,
+    {
+        self.prune = Some(Box::new(prune));
+        self
+    }
+
+    /// The total number of tiles this iterator returns.
+    pub fn total(&self) -> u64 {
+        (self.minzoom..=self.maxzoom)
+            .filter_map(|zoom| overlapping_tile_range(self.bbox, zoom))
+            .map(|r| r.count())
+            .sum()
+    }
+
+    /// The number of tiles which haven't been returned yet
+    pub fn remaining(&self) -> u64 {
+        if self.zoom > self.maxzoom {
+            return 0;
+        }
+        let range = match self
+            .range
+            .or_else(|| overlapping_tile_range(self.bbox, self.zoom))
+        {
+            None => return 0,
+            Some(r) => r,
+        };
+
+        match self.traversal {
+            Traversal::BreadthFirst => {
+                let later_zooms: u64 = (self.zoom + 1..=self.maxzoom)
+                    .filter_map(|zoom| overlapping_tile_range(self.bbox, zoom))
+                    .map(|r| r.count())
+                    .sum();
+
+                range.count() - range.count_before(TileOrder::ZOrder, self.next_zorder)
+                    + later_zooms
+            }
+            Traversal::DepthFirst => {
+                // In z-order, the descendants of a block of tiles are a block of tiles in each
+                // zoom.
+                let ranges: Vec<(u8, TileRange)> = (self.minzoom..=self.maxzoom)
+                    .filter_map(|zoom| overlapping_tile_range(self.bbox, zoom).map(|r| (zoom, r)))
+                    .collect();
+                let count_in_block = |zoom: u8, start: u64, end: u64| -> u64 {
+                    ranges
+                        .iter()
+                        .filter(|&&(z, _)| z >= zoom)
+                        .map(|&(z, r)| {
+                            let shift = 2 * (z - zoom) as u64;
+                            r.count_before(TileOrder::ZOrder, end << shift)
+                                - r.count_before(TileOrder::ZOrder, start << shift)
+                        })
+                        .sum()
+                };
+
+                let top_level =
+                    count_in_block(self.zoom, self.next_zorder, 1 << (2 * self.zoom as u64));
+                let subtiles: u64 = self
+                    .stack
+                    .iter()
+                    .map(|&(tile, next_subtile)| {
+                        let first = xy_to_zorder(tile.x, tile.y) * 4;
+                        count_in_block(tile.zoom + 1, first + next_subtile as u64, first + 4)
+                    })
+                    .sum();
+
+                top_level + subtiles
+            }
+        }
+    }
+
+    fn next_breadth_first(&mut self) -> Option<Tile> {
+        // The tiles in each zoom are in z-order, as if going down a quadtree
+        while self.zoom <= self.maxzoom {
+            let range = match self.range {
+                Some(r) => r,
+                None => match overlapping_tile_range(self.bbox, self.zoom) {
+                    None => {
+                        self.zoom = 32;
+                        return None;
+                    }
+                    Some(r) => {
+                        self.range = Some(r);
+                        r
+                    }
+                },
+            };
+
+            match range.next_zorder_index(self.next_zorder) {
+                Some(zorder) => {
+                    if let Some(end) = self.pruned_end(zorder) {
+                        // Skip all the descendants of the pruned tiles in this zoom
+                        self.next_zorder = end + 1;
+                        continue;
+                    }
+                    self.next_zorder = zorder + 1;
+                    let (x, y) = zorder_to_xy(zorder);
+                    return Some(self.visit(Tile::new(self.zoom, x, y).unwrap()));
+                }
+                None => {
+                    self.zoom += 1;
+                    self.range = None;
+                    self.next_zorder = 0;
+                    self.next_zoom_pruned();
+                }
+            }
+        }
+
+        None
+    }
+
+    /// If the tile at `zorder` in the current zoom is pruned, the end of that pruned interval.
+    /// `zorder` must not be less than for the last call.
+    fn pruned_end(&mut self, zorder: u64) -> Option<u64> {
+        while self.pruned_pos < self.pruned.len() && self.pruned[self.pruned_pos].1 < zorder {
+            self.pruned_pos += 1;
+        }
+        match self.pruned.get(self.pruned_pos) {
+            Some(&(start, end)) if start <= zorder => Some(end),
+            _ => None,
+        }
+    }
+
+    /// Moving to the next zoom, the subtiles of all the pruned tiles are pruned
+    fn next_zoom_pruned(&mut self) {
+        let subtiles = |&(start, end): &(u64, u64)| (start * 4, end * 4 + 3);
+        let mut earlier = self.pruned.iter().map(subtiles).peekable();
+        let mut this_zoom = self.next_pruned.iter().map(subtiles).peekable();
+        let mut pruned: Vec<(u64, u64)> = Vec::new();
+        loop {
+            let interval = match (earlier.peek(), this_zoom.peek()) {
+                (None, None) => break,
+                (Some(a), Some(b)) if b.0 < a.0 => this_zoom.next(),
+                (Some(_), _) => earlier.next(),
+                (None, Some(_)) => this_zoom.next(),
+            };
+            push_pruned(&mut pruned, interval.unwrap());
+        }
+
+        self.pruned = pruned;
+        self.next_pruned.clear();
+        self.pruned_pos = 0;
+    }
+
+    fn next_depth_first(&mut self) -> Option<Tile> {
+        // The next subtile of the deepest tile which overlaps the bbox
+        while let Some(&(tile, next_subtile)) = self.stack.last() {
+            if next_subtile == 4 {
+                self.stack.pop();
+                continue;
+            }
+            self.stack.last_mut().unwrap().1 += 1;
+            let subtile = tile.subtiles().unwrap()[next_subtile as usize];
+            if self.bbox.overlaps_bbox(&subtile.bbox()) {
+                return Some(self.visit(subtile));
+            }
+        }
+
+        // Otherwise the next tile in the top zoom
+        let range = match self
+            .range
+            .or_else(|| overlapping_tile_range(self.bbox, self.zoom))
+        {
+            None => {
+                self.zoom = 32;
+                return None;
+            }
+            Some(r) => r,
+        };
+        self.range = Some(range);
+        match range.next_zorder_index(self.next_zorder) {
+            Some(zorder) => {
+                self.next_zorder = zorder + 1;
+                let (x, y) = zorder_to_xy(zorder);
+                Some(self.visit(Tile::new(self.zoom, x, y).unwrap()))
+            }
+            None => {
+                self.zoom = 32;
+                None
+            }
+        }
+    }
+
+    /// Called for each tile returned, to decide whether to go into its subtiles.
+    fn visit(&mut self, tile: Tile) -> Tile {
+        if tile.zoom >= self.maxzoom {
+            return tile;
+        }
+        let prune = match self.prune {
+            Some(ref mut prune) => prune(&tile),
+            None => false,
+        };
+        match (self.traversal, prune) {
+            (Traversal::DepthFirst, false) => self.stack.push((tile, 0)),
+            (Traversal::BreadthFirst, true) => {
+                let zorder = xy_to_zorder(tile.x, tile.y);
+                push_pruned(&mut self.next_pruned, (zorder, zorder));
+            }
+            _ => {}
+        }
+        tile
+    }
+}
+
+/// Add this z-order interval to the end of these sorted intervals, joining it to the last one if
+/// they overlap or touch
+fn push_pruned(intervals: &mut Vec<(u64, u64)>, (start, end): (u64, u64)) {
+    match intervals.last_mut() {
+        Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+        _ => intervals.push((start, end)),
+    }
+}
+
+/// The tiles at `zoom` which overlap this bbox (see `BBox::overlaps_bbox`). All of `0/0/0` is
+/// returned. None if there are no tiles (or `zoom` is more than 31).
+fn overlapping_tile_range(bbox: &BBox, zoom: u8) -> Option<TileRange> {
+    if zoom == 0 {
+        return TileRange::whole_world(0);
+    }
+    if zoom > 31 {
+        return None;
+    }
+
+    // The number of tiles, starting at 0, for which `pred` is true. `pred` must be true, and then
+    // false.
+    let partition_point = |pred: &dyn Fn(u32) -> bool| -> u32 {
+        let (mut lo, mut hi) = (0u64, 1u64 << zoom);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if pred(mid as u32) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
         }
+        lo as u32
+    };
+
+    // The x & y parts of `BBox::overlaps_bbox`, since longitude only depends on x, and latitude
+    // on y.
+    let tile_bbox = |x, y| Tile::new(zoom, x, y).unwrap().bbox();
+    let x_min = partition_point(&|x| tile_bbox(x, 0).right <= bbox.left);
+    let x_end = partition_point(&|x| tile_bbox(x, 0).left < bbox.right);
+    let y_min = partition_point(&|y| tile_bbox(0, y).bottom >= bbox.top);
+    let y_end = partition_point(&|y| tile_bbox(0, y).top > bbox.bottom);
+
+    if x_min >= x_end || y_min >= y_end {
+        None
+    } else {
+        TileRange::new(zoom, x_min, x_end - 1, y_min, y_end - 1)
     }
 }
 
@@ -1322,40 +2643,53 @@ impl<'a> Iterator for BBoxTilesIterator<'a> {
     type Item = Tile;
 
     fn next(&mut self) -> Option<Tile> {
-        if self.tile_index >= self.tiles.len() {
-            // We've sent off all the existing tiles, so start looking at the children
-            let mut new_tiles: Vec<Tile> = Vec::with_capacity(self.tiles.len() * 4);
-            for t in self.tiles.iter() {
-                match t.subtiles() {
-                    None => {}
-                    Some(sub) => {
-                        if self.bbox.overlaps_bbox(&sub[0].bbox()) {
-                            new_tiles.push(sub[0]);
-                        }
-                        if self.bbox.overlaps_bbox(&sub[1].bbox()) {
-                            new_tiles.push(sub[1]);
-                        }
-                        if self.bbox.overlaps_bbox(&sub[2].bbox()) {
-                            new_tiles.push(sub[2]);
-                        }
-                        if self.bbox.overlaps_bbox(&sub[3].bbox()) {
-                            new_tiles.push(sub[3]);
-                        }
-                    }
-                }
-            }
+        if self.zoom > self.maxzoom {
+            return None;
+        }
+        match self.traversal {
+            Traversal::BreadthFirst => self.next_breadth_first(),
+            Traversal::DepthFirst => self.next_depth_first(),
+        }
+    }
 
-            new_tiles.shrink_to_fit();
-            self.tiles = new_tiles;
-            self.tile_index = 0;
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = usize::try_from(self.remaining()).ok();
+        match (remaining, &self.prune) {
+            (Some(remaining), None) => (remaining, Some(remaining)),
+            (remaining, Some(_)) => (0, remaining),
+            (None, None) => (usize::MAX, None),
         }
+    }
+}
+
+/// Opens this file for reading. If the file is gzip compressed, and the `gzip` feature is
+/// enabled, it will be transparently decompressed.
+fn open_file<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead>> {
+    let mut rdr = BufReader::new(File::open(path)?);
+    let is_gzip = rdr.fill_buf()?.starts_with(&[0x1f, 0x8b]);
 
-        let tile = self.tiles[self.tile_index];
-        self.tile_index += 1;
-        Some(tile)
+    if is_gzip {
+        open_gzip(rdr)
+    } else {
+        Ok(Box::new(rdr))
     }
 }
 
+#[cfg(feature = "gzip")]
+fn open_gzip<R: Read + 'static>(rdr: R) -> io::Result<Box<dyn BufRead>> {
+    Ok(Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(
+        rdr,
+    ))))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn open_gzip<R: Read + 'static>(_rdr: R) -> io::Result<Box<dyn BufRead>> {
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "File is gzip compressed, but the gzip feature is not enabled",
+    ))
+}
+
 /// Convert x & y to a TileCache (tc) directory parts
 fn xy_to_tc(x: u32, y: u32) -> [String; 6] {
     [
@@ -1427,48 +2761,250 @@ fn xy_to_mt(x: u32, y: u32) -> [String; 5] {
 
 /// How many times are in this soom level? Returns None if there would be a usize overflow
 fn num_tiles_in_zoom(zoom: u8) -> Option<usize> {
-    // From experience it looks like you can't calc above zoom >= 6
-    if zoom == 0 {
-        // Special case of known value
-        Some(1)
-    } else if zoom <= 5 {
-        Some(2u64.pow(2u32.pow(zoom as u32)) as usize)
+    // 4^zoom, which is more than a usize can hold at high zooms
+    if 2 * (zoom as u32) < usize::BITS {
+        Some(1usize << (2 * zoom as u32))
     } else {
         None
     }
 }
 
+/// Convert x & y to a [z-order](https://en.wikipedia.org/wiki/Z-order_curve) (aka Morton code),
+/// with the bits of `x` in the even bits, and `y` in the odd bits.
+///
+/// If the crate is compiled for a target with BMI2 (e.g. `RUSTFLAGS="-C target-cpu=native"` on a
+/// modern x86_64), the `pdep` instruction is used, otherwise "magic number" bit shifting.
+///
+/// ```
+/// # use slippy_map_tiles::xy_to_zorder;
+/// assert_eq!(xy_to_zorder(0b11, 0b01), 0b0111);
+/// ```
+#[inline]
 pub fn xy_to_zorder(x: u32, y: u32) -> u64 {
-    let mut res: u64 = 0;
-    for i in 0..32 {
-        let x_set: bool = (x >> i) & 1 == 1;
-        let y_set: bool = (y >> i) & 1 == 1;
-        if x_set {
-            res |= 1 << (i * 2);
-        }
-        if y_set {
-            res |= 1 << (i * 2) + 1;
-        }
+    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    {
+        xy_to_zorder_bmi2(x, y)
+    }
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "bmi2")))]
+    {
+        xy_to_zorder_magic(x, y)
     }
-
-    res
 }
 
+/// Convert a z-order to x & y. The inverse of `xy_to_zorder`.
+#[inline]
 pub fn zorder_to_xy(zorder: u64) -> (u32, u32) {
-    let mut x: u32 = 0;
-    let mut y: u32 = 0;
+    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    {
+        zorder_to_xy_bmi2(zorder)
+    }
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "bmi2")))]
+    {
+        zorder_to_xy_magic(zorder)
+    }
+}
 
-    for i in 0..32 {
-        let x_bit_set = (zorder >> (i * 2)) & 1 == 1;
-        let y_bit_set = (zorder >> ((i * 2) + 1)) & 1 == 1;
+const EVEN_BITS: u64 = 0x5555_5555_5555_5555;
+
+/// Move the bits of `v` into the even bits of the result, i.e. bit `i` goes to bit `2i`
+#[inline]
+fn spread_bits(v: u32) -> u64 {
+    let mut v = v as u64;
+    v = (v | (v << 16)) & 0x0000_FFFF_0000_FFFF;
+    v = (v | (v << 8)) & 0x00FF_00FF_00FF_00FF;
+    v = (v | (v << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+    v = (v | (v << 1)) & EVEN_BITS;
+    v
+}
 
-        if x_bit_set {
-            x |= 1 << i;
+/// Take the even bits of `v`, and pack them together. The inverse of `spread_bits`
+#[inline]
+fn compact_bits(v: u64) -> u32 {
+    let mut v = v & EVEN_BITS;
+    v = (v | (v >> 1)) & 0x3333_3333_3333_3333;
+    v = (v | (v >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    v = (v | (v >> 4)) & 0x00FF_00FF_00FF_00FF;
+    v = (v | (v >> 8)) & 0x0000_FFFF_0000_FFFF;
+    v = (v | (v >> 16)) & 0x0000_0000_FFFF_FFFF;
+    v as u32
+}
+
+#[inline]
+fn xy_to_zorder_magic(x: u32, y: u32) -> u64 {
+    spread_bits(x) | (spread_bits(y) << 1)
+}
+
+#[inline]
+fn zorder_to_xy_magic(zorder: u64) -> (u32, u32) {
+    (compact_bits(zorder), compact_bits(zorder >> 1))
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+#[inline]
+fn xy_to_zorder_bmi2(x: u32, y: u32) -> u64 {
+    use std::arch::x86_64::_pdep_u64;
+    // Safe since this is only compiled when the target has BMI2
+    unsafe { _pdep_u64(x as u64, EVEN_BITS) | _pdep_u64(y as u64, EVEN_BITS << 1) }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+#[inline]
+fn zorder_to_xy_bmi2(zorder: u64) -> (u32, u32) {
+    use std::arch::x86_64::_pext_u64;
+    // Safe since this is only compiled when the target has BMI2
+    unsafe {
+        (
+            _pext_u64(zorder, EVEN_BITS) as u32,
+            _pext_u64(zorder, EVEN_BITS << 1) as u32,
+        )
+    }
+}
+
+/// The bits of a z-order which are for the same dimension (x or y) as `bit`, and lower than it.
+fn zorder_lower_dimension_bits(bit: u32) -> u64 {
+    let dimension = if bit & 1 == 0 {
+        EVEN_BITS
+    } else {
+        EVEN_BITS << 1
+    };
+    dimension & ((1u64 << bit) - 1)
+}
+
+/// Set `bit` to 1, and all lower bits of the same dimension to 0 (i.e. "1000...")
+fn zorder_load_1000(zorder: u64, bit: u32) -> u64 {
+    (zorder | (1 << bit)) & !zorder_lower_dimension_bits(bit)
+}
+
+/// Set `bit` to 0, and all lower bits of the same dimension to 1 (i.e. "0111...")
+fn zorder_load_0111(zorder: u64, bit: u32) -> u64 {
+    (zorder & !(1 << bit)) | zorder_lower_dimension_bits(bit)
+}
+
+/// BIGMIN: the smallest z-order, greater than `zorder`, which is inside the rectangle with
+/// corners `min_zorder` (i.e. `xy_to_zorder(x_min, y_min)`) and `max_zorder`.
+///
+/// `zorder` must be between `min_zorder` and `max_zorder`, but outside the rectangle. When
+/// scanning a z-order index for a rectangle, and you find a value outside it, you can jump to
+/// BIGMIN. See Tropf & Herzog, "Multidimensional Range Search in Dynamically Balanced Trees"
+/// (1981).
+///
+/// ```
+/// # use slippy_map_tiles::{xy_to_zorder, zorder_bigmin};
+/// // The rectangle x 1-2, y 1-2. (3, 0) is outside, and the next z-order inside is (2, 1)
+/// let (min, max) = (xy_to_zorder(1, 1), xy_to_zorder(2, 2));
+/// assert_eq!(zorder_bigmin(xy_to_zorder(3, 0), min, max), xy_to_zorder(2, 1));
+/// ```
+pub fn zorder_bigmin(zorder: u64, min_zorder: u64, max_zorder: u64) -> u64 {
+    let mut bigmin = 0;
+    let mut min = min_zorder;
+    let mut max = max_zorder;
+
+    for bit in (0..64).rev() {
+        let mask = 1u64 << bit;
+        match (zorder & mask != 0, min & mask != 0, max & mask != 0) {
+            (false, false, true) => {
+                bigmin = zorder_load_1000(min, bit);
+                max = zorder_load_0111(max, bit);
+            }
+            (false, true, true) => return min,
+            (true, false, false) => return bigmin,
+            (true, false, true) => {
+                min = zorder_load_1000(min, bit);
+            }
+            // (false, false, false) & (true, true, true): nothing to do
+            // (_, true, false): min > max, which can't happen for a valid rectangle
+            _ => {}
         }
-        if y_bit_set {
-            y |= 1 << i;
+    }
+
+    bigmin
+}
+
+/// LITMAX: the largest z-order, less than `zorder`, which is inside the rectangle with corners
+/// `min_zorder` and `max_zorder`. The counterpart to `zorder_bigmin`.
+pub fn zorder_litmax(zorder: u64, min_zorder: u64, max_zorder: u64) -> u64 {
+    let mut litmax = 0;
+    let mut min = min_zorder;
+    let mut max = max_zorder;
+
+    for bit in (0..64).rev() {
+        let mask = 1u64 << bit;
+        match (zorder & mask != 0, min & mask != 0, max & mask != 0) {
+            (false, false, true) => {
+                max = zorder_load_0111(max, bit);
+            }
+            (false, true, true) => return litmax,
+            (true, false, false) => return max,
+            (true, false, true) => {
+                litmax = zorder_load_0111(max, bit);
+                min = zorder_load_1000(min, bit);
+            }
+            _ => {}
         }
     }
 
-    (x, y)
+    litmax
+}
+
+/// Convert x & y, at this zoom, to the position along the [Hilbert
+/// curve](https://en.wikipedia.org/wiki/Hilbert_curve) which covers all the tiles at that zoom.
+/// Uses the same orientation as PMTiles. `zoom` must be 31 or less.
+///
+/// ```
+/// # use slippy_map_tiles::xy_to_hilbert;
+/// assert_eq!(xy_to_hilbert(0, 0, 1), 0);
+/// assert_eq!(xy_to_hilbert(0, 1, 1), 1);
+/// assert_eq!(xy_to_hilbert(1, 1, 1), 2);
+/// assert_eq!(xy_to_hilbert(1, 0, 1), 3);
+/// ```
+pub fn xy_to_hilbert(x: u32, y: u32, zoom: u8) -> u64 {
+    let n: u64 = 1 << zoom;
+    let mut x = x as u64;
+    let mut y = y as u64;
+    let mut index = 0;
+
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s != 0) as u64;
+        let ry = (y & s != 0) as u64;
+        index += s * s * ((3 * rx) ^ ry);
+        hilbert_rotate(n, &mut x, &mut y, rx, ry);
+        s /= 2;
+    }
+
+    index
+}
+
+/// Convert a position along the Hilbert curve at this zoom to x & y. The inverse of
+/// `xy_to_hilbert`.
+pub fn hilbert_to_xy(index: u64, zoom: u8) -> (u32, u32) {
+    let n: u64 = 1 << zoom;
+    let mut x = 0;
+    let mut y = 0;
+    let mut t = index;
+
+    let mut s = 1;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        hilbert_rotate(s, &mut x, &mut y, rx, ry);
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+
+    (x as u32, y as u32)
+}
+
+/// Rotate/flip a quadrant, as part of Hilbert curve calculations
+fn hilbert_rotate(n: u64, x: &mut u64, y: &mut u64, rx: u64, ry: u64) {
+    if ry == 0 {
+        if rx == 1 {
+            *x = n - 1 - *x;
+            *y = n - 1 - *y;
+        }
+        std::mem::swap(x, y);
+    }
 }
@@ -0,0 +1,317 @@
+//! Web Mercator ([EPSG:3857](https://epsg.io/3857)), the projection used by slippy map tiles, in
+//! metres. All calculations are done in `f64`.
+//!
+//! The world is a square from `-EXTENT` to `EXTENT` in both x & y. Unlike tile y, mercator y goes
+//! up, i.e. north is positive.
+//!
+//! ```
+//! # use slippy_map_tiles::Tile;
+//! # use slippy_map_tiles::mercator;
+//! let (x, y) = mercator::lat_lon_to_merc(53.35, -6.26);
+//! let (lat, lon) = mercator::merc_to_lat_lon(x, y);
+//! assert!((lat - 53.35).abs() < 1e-9 && (lon - -6.26).abs() < 1e-9);
+//!
+//! let (tile, (px, py)) = mercator::merc_to_tile(x, y, 10, 256).unwrap();
+//! assert_eq!(tile, Tile::new(10, 494, 331).unwrap());
+//! assert_eq!((px, py), (49, 232));
+//! ```
+
+use super::{BBox, Tile};
+
+/// The radius of the earth (the WGS84 semi-major axis) in metres, as used by Web Mercator
+pub const EARTH_RADIUS: f64 = 6_378_137.;
+
+/// Half the width of the world in metres. x & y go from `-EXTENT` to `EXTENT`.
+pub const EXTENT: f64 = std::f64::consts::PI * EARTH_RADIUS;
+
+/// The largest latitude in Web Mercator (~85.0511°), where y is `EXTENT`
+pub fn max_lat() -> f64 {
+    std::f64::consts::PI.sinh().atan().to_degrees()
+}
+
+/// Convert a lat/lon (in degrees) to a mercator x/y. The latitude is clamped to ±`max_lat()`, so
+/// y is always in the world.
+pub fn lat_lon_to_merc(lat: f64, lon: f64) -> (f64, f64) {
+    let lat = lat.max(-max_lat()).min(max_lat());
+    let x = lon.to_radians() * EARTH_RADIUS;
+    let y = (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.)
+        .tan()
+        .ln()
+        * EARTH_RADIUS;
+
+    (x, y)
+}
+
+/// Convert a mercator x/y to a lat/lon (in degrees), the opposite of `lat_lon_to_merc`.
+pub fn merc_to_lat_lon(x: f64, y: f64) -> (f64, f64) {
+    let lon = (x / EARTH_RADIUS).to_degrees();
+    let lat = (2. * (y / EARTH_RADIUS).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees();
+
+    (lat, lon)
+}
+
+/// A rectangle in Web Mercator metres, or in the units of another CRS (see `tilematrixset`)
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Extent {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl Extent {
+    /// Returns None if the minimum is more than the maximum
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Option<Extent> {
+        if min_x <= max_x && min_y <= max_y {
+            Some(Extent {
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The whole world
+    pub fn world() -> Extent {
+        Extent::new(-EXTENT, -EXTENT, EXTENT, EXTENT).unwrap()
+    }
+
+    pub fn min_x(&self) -> f64 {
+        self.min_x
+    }
+    pub fn min_y(&self) -> f64 {
+        self.min_y
+    }
+    pub fn max_x(&self) -> f64 {
+        self.max_x
+    }
+    pub fn max_y(&self) -> f64 {
+        self.max_y
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    /// This extent as a WKT polygon, with the same points as PostGIS's `ST_MakeEnvelope`.
+    ///
+    /// Numbers are written with the fewest digits which give the same `f64`, as PostGIS 3.1+
+    /// does.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::mercator::Extent;
+    /// let e = Extent::new(0., -1.5, 2., 3.).unwrap();
+    /// assert_eq!(e.to_wkt(), "POLYGON((0 -1.5,0 3,2 3,2 -1.5,0 -1.5))");
+    /// ```
+    pub fn to_wkt(&self) -> String {
+        format!(
+            "POLYGON(({x1} {y1},{x1} {y2},{x2} {y2},{x2} {y1},{x1} {y1}))",
+            x1 = self.min_x,
+            y1 = self.min_y,
+            x2 = self.max_x,
+            y2 = self.max_y
+        )
+    }
+
+    /// This extent as an EWKT polygon, i.e. WKT with a SRID, e.g. `SRID=3857;POLYGON((...))`.
+    pub fn to_ewkt(&self, srid: u32) -> String {
+        format!("SRID={};{}", srid, self.to_wkt())
+    }
+}
+
+/// The width (and height) of a tile at this zoom in metres
+pub fn tile_width(zoom: u8) -> f64 {
+    2. * EXTENT / 2f64.powi(zoom as i32)
+}
+
+/// The size of a pixel in metres (0.28mm) used for scale denominators by OGC standards, e.g. WMTS
+pub const STANDARD_PIXEL_SIZE: f64 = 0.00028;
+
+/// The size of a pixel in metres when printed at this many dots per inch
+pub fn pixel_size_for_dpi(dpi: f64) -> f64 {
+    0.0254 / dpi
+}
+
+/// The resolution (metres per pixel) at this zoom, when tiles are `tile_size` pixels wide. This is
+/// the size of a pixel on the ground at the equator, see `ground_resolution` for other latitudes.
+///
+/// ```
+/// # use slippy_map_tiles::mercator;
+/// assert!((mercator::resolution(0, 256) - 156_543.034).abs() < 0.001);
+/// assert!((mercator::resolution(18, 512) - 0.2986).abs() < 0.0001);
+/// ```
+pub fn resolution(zoom: u8, tile_size: u32) -> f64 {
+    tile_width(zoom) / tile_size as f64
+}
+
+/// The size of a pixel on the ground at this latitude (in degrees), in metres. Mercator stretches
+/// things away from the equator, so this is smaller than `resolution`.
+pub fn ground_resolution(lat: f64, zoom: u8, tile_size: u32) -> f64 {
+    resolution(zoom, tile_size) * lat.to_radians().cos()
+}
+
+/// The scale denominator of this resolution (metres per pixel), when each pixel is `pixel_size`
+/// metres, e.g. `STANDARD_PIXEL_SIZE`. A map at 1:25,000 has a scale denominator of 25,000.
+///
+/// ```
+/// # use slippy_map_tiles::mercator::{self, STANDARD_PIXEL_SIZE};
+/// assert_eq!(mercator::scale_denominator(7., STANDARD_PIXEL_SIZE).round(), 25_000.);
+/// // Printed at 300 DPI
+/// let pixel_size = mercator::pixel_size_for_dpi(300.);
+/// assert_eq!(mercator::scale_denominator(7., pixel_size).round(), 82_677.);
+/// ```
+pub fn scale_denominator(resolution: f64, pixel_size: f64) -> f64 {
+    resolution / pixel_size
+}
+
+/// The resolution (metres per pixel) of this scale denominator, the opposite of
+/// `scale_denominator`.
+pub fn resolution_for_scale_denominator(scale_denominator: f64, pixel_size: f64) -> f64 {
+    scale_denominator * pixel_size
+}
+
+/// The zoom (which can have a fraction) with this resolution, e.g. `2.5` is half way between
+/// zooms 2 & 3. It can be negative, or more than 31.
+pub fn fractional_zoom(resolution: f64, tile_size: u32) -> f64 {
+    (self::resolution(0, tile_size) / resolution).log2()
+}
+
+/// How to pick a zoom for a resolution which is between 2 zooms
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
+pub enum ZoomRounding {
+    /// The closest zoom (on a log scale)
+    #[default]
+    Nearest,
+    /// The zoom below, which is less detailed than asked for
+    Coarser,
+    /// The zoom above, which is at least as detailed as asked for
+    Finer,
+}
+
+/// The zoom (0 to 31) for this resolution (metres per pixel). Returns None if `resolution` isn't
+/// a positive number.
+///
+/// ```
+/// # use slippy_map_tiles::mercator::{self, ZoomRounding};
+/// assert_eq!(mercator::zoom_for_resolution(1., 256, ZoomRounding::Nearest), Some(17));
+/// assert_eq!(mercator::zoom_for_resolution(1., 256, ZoomRounding::Coarser), Some(17));
+/// assert_eq!(mercator::zoom_for_resolution(1., 256, ZoomRounding::Finer), Some(18));
+/// ```
+pub fn zoom_for_resolution(resolution: f64, tile_size: u32, rounding: ZoomRounding) -> Option<u8> {
+    if !(resolution > 0. && resolution.is_finite()) {
+        return None;
+    }
+    let zoom = fractional_zoom(resolution, tile_size);
+    // Resolutions calculated from a zoom might not give exactly that zoom back
+    let nearest = zoom.round();
+    let zoom = if (zoom - nearest).abs() < 1e-9 {
+        nearest
+    } else {
+        match rounding {
+            ZoomRounding::Nearest => nearest,
+            ZoomRounding::Coarser => zoom.floor(),
+            ZoomRounding::Finer => zoom.ceil(),
+        }
+    };
+
+    Some(zoom.clamp(0., 31.) as u8)
+}
+
+/// The zoom (0 to 31) for this scale denominator, when each pixel is `pixel_size` metres. Returns
+/// None if `scale_denominator` isn't a positive number.
+pub fn zoom_for_scale_denominator(
+    scale_denominator: f64,
+    pixel_size: f64,
+    tile_size: u32,
+    rounding: ZoomRounding,
+) -> Option<u8> {
+    zoom_for_resolution(
+        resolution_for_scale_denominator(scale_denominator, pixel_size),
+        tile_size,
+        rounding,
+    )
+}
+
+/// The extent of this tile in metres
+pub fn tile_extent(tile: &Tile) -> Extent {
+    let width = tile_width(tile.zoom());
+    let min_x = -EXTENT + tile.x() as f64 * width;
+    let max_y = EXTENT - tile.y() as f64 * width;
+
+    Extent::new(min_x, max_y - width, min_x + width, max_y).unwrap()
+}
+
+/// The envelope of this tile, calculated the same way as PostGIS's
+/// [`ST_TileEnvelope(zoom, x, y, bounds, margin)`](https://postgis.net/docs/ST_TileEnvelope.html).
+///
+/// The tiles split `bounds`. `margin` is the fraction of the tile size to add on each side, and it
+/// can be negative to shrink the tile. The y values are clipped to `bounds`, but x isn't, so an
+/// envelope can go over the antimeridian (as in PostGIS), unless the tile with its margins is
+/// wider than `bounds`, then it's the width of `bounds`.
+///
+/// Returns None, where PostGIS has an error, if `margin` is less than -0.5, or `bounds` has no
+/// width or height.
+pub fn tile_envelope(tile: &Tile, bounds: &Extent, margin: f64) -> Option<Extent> {
+    if margin < -0.5 || bounds.width() <= 0. || bounds.height() <= 0. {
+        return None;
+    }
+    let world_tile_size = (1u64 << tile.zoom()) as f64;
+    let tile_size_x = bounds.width() / world_tile_size;
+    let tile_size_y = bounds.height() / world_tile_size;
+    let (x, y) = (tile.x() as f64, tile.y() as f64);
+
+    let (x1, x2) = if 1. + margin * 2. > world_tile_size {
+        (bounds.min_x, bounds.max_x)
+    } else {
+        (
+            bounds.min_x + tile_size_x * (x - margin),
+            bounds.min_x + tile_size_x * (x + 1. + margin),
+        )
+    };
+
+    let y1 = bounds.max_y - tile_size_y * (y + 1. + margin);
+    let y2 = bounds.max_y - tile_size_y * (y - margin);
+    let y1 = if y1 < bounds.min_y { bounds.min_y } else { y1 };
+    let y2 = if y2 > bounds.max_y { bounds.max_y } else { y2 };
+
+    Extent::new(x1, y1, x2, y2)
+}
+
+/// The global pixel x/y (see `lat_lon_to_global_pixel`) of this mercator x/y
+pub(crate) fn merc_to_global_pixel(x: f64, y: f64, zoom: u8, tile_size: u32) -> (f64, f64) {
+    let pixel_width = tile_width(zoom) / tile_size as f64;
+    ((x + EXTENT) / pixel_width, (EXTENT - y) / pixel_width)
+}
+
+/// The tile which this mercator x/y is in at this zoom, and then the x/y of the pixel within that
+/// tile's image, which is `tile_size` pixels wide. `0,0` is the top left pixel of the image.
+/// Returns None if the point is outside the world.
+pub fn merc_to_tile(x: f64, y: f64, zoom: u8, tile_size: u32) -> Option<(Tile, (u32, u32))> {
+    let (px, py) = merc_to_global_pixel(x, y, zoom, tile_size);
+    let (tile, (px, py)) = super::global_pixel_to_tile(px, py, zoom, tile_size)?;
+
+    Some((tile, (px as u32, py as u32)))
+}
+
+/// The mercator extent of this bbox. None if the bbox goes over the antimeridian (i.e. left is
+/// more than right), or the top is below the bottom, since an `Extent` can't do that.
+pub fn bbox_to_extent(bbox: &BBox) -> Option<Extent> {
+    let (min_x, min_y) = lat_lon_to_merc(bbox.bottom() as f64, bbox.left() as f64);
+    let (max_x, max_y) = lat_lon_to_merc(bbox.top() as f64, bbox.right() as f64);
+
+    Extent::new(min_x, min_y, max_x, max_y)
+}
+
+/// The bbox of this mercator extent. None if it's outside the world.
+pub fn extent_to_bbox(extent: &Extent) -> Option<BBox> {
+    let (bottom, left) = merc_to_lat_lon(extent.min_x, extent.min_y);
+    let (top, right) = merc_to_lat_lon(extent.max_x, extent.max_y);
+
+    BBox::new(top as f32, left as f32, bottom as f32, right as f32)
+}
@@ -0,0 +1,212 @@
+//! Work out which tiles have been changed ("expired") by an OpenStreetMap
+//! [OsmChange](https://wiki.openstreetmap.org/wiki/OsmChange) (`.osc`) file, like the minutely
+//! replication diffs.
+//!
+//! Every created, modified or deleted node which has a location is used. Way node references
+//! (`<nd>`) are included when they have a location attached, as done by `osmium
+//! add-locations-to-ways`. Nodes without a location (e.g. most deletes) are skipped.
+//!
+//! ```
+//! # use slippy_map_tiles::osc;
+//! # use slippy_map_tiles::Tile;
+//! let osc = r#"<osmChange version="0.6">
+//!   <modify><node id="1" version="2" lat="53.3498" lon="-6.2603"/></modify>
+//! </osmChange>"#;
+//! let tiles = osc::expired_tiles(osc.as_bytes(), &[10, 12], 0).unwrap();
+//! assert_eq!(tiles.len(), 2);
+//! assert!(tiles.contains(&Tile::new(12, 1976, 1327).unwrap()));
+//! ```
+
+use std::collections::HashSet;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+use regex::Regex;
+
+use super::{lat_lon_to_tile, open_file, LatLon, Metatile, Tile};
+
+/// Read all the locations of changed nodes (and located way nodes) from this OsmChange file.
+///
+/// Returns an error if there is an I/O error, or if this doesn't look like an OsmChange file.
+pub fn changed_locations<R: BufRead>(mut rdr: R) -> io::Result<Vec<LatLon>> {
+    lazy_static! {
+        static ref NAME_RE: Regex =
+            Regex::new(r"^(?P<close>/?)(?P<name>[A-Za-z_:][-\w.:]*)").unwrap();
+        static ref ATTR_RE: Regex =
+            Regex::new(r#"(?P<key>[A-Za-z_:][-\w.:]*)\s*=\s*(?:"(?P<dq>[^"]*)"|'(?P<sq>[^']*)')"#)
+                .unwrap();
+    }
+
+    let mut locations = Vec::new();
+    let mut seen_root = false;
+    let mut action_depth = 0;
+    let mut buf = Vec::new();
+
+    while read_tag(&mut rdr, &mut buf)? {
+        let tag = String::from_utf8_lossy(&buf);
+        let caps = match NAME_RE.captures(&tag) {
+            None => {
+                // Comments, processing instructions etc.
+                continue;
+            }
+            Some(c) => c,
+        };
+        let closing = !caps["close"].is_empty();
+        let self_closing = tag.ends_with('/');
+
+        match &caps["name"] {
+            "osmChange" => {
+                seen_root = true;
+            }
+            "create" | "modify" | "delete" => {
+                if closing {
+                    action_depth -= 1;
+                } else if !self_closing {
+                    action_depth += 1;
+                }
+            }
+            "node" | "nd" if !closing && action_depth > 0 => {
+                let mut lat = None;
+                let mut lon = None;
+                for attr in ATTR_RE.captures_iter(&tag) {
+                    let value = attr.name("dq").or_else(|| attr.name("sq")).unwrap();
+                    match &attr["key"] {
+                        "lat" => lat = value.as_str().parse::<f32>().ok(),
+                        "lon" => lon = value.as_str().parse::<f32>().ok(),
+                        _ => {}
+                    }
+                }
+                if let (Some(lat), Some(lon)) = (lat, lon) {
+                    match LatLon::new(lat, lon) {
+                        None => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("Invalid location lat={} lon={}", lat, lon),
+                            ));
+                        }
+                        Some(loc) => locations.push(loc),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !seen_root {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No <osmChange> element found",
+        ));
+    }
+
+    Ok(locations)
+}
+
+/// Reads the next XML tag (i.e. everything between `<` and `>`) into `buf`. `>` inside quoted
+/// attribute values is handled. Returns false at the end of the file.
+fn read_tag<R: BufRead>(rdr: &mut R, buf: &mut Vec<u8>) -> io::Result<bool> {
+    buf.clear();
+    // Skip any text before the tag
+    if rdr.read_until(b'<', buf)? == 0 || buf.last() != Some(&b'<') {
+        return Ok(false);
+    }
+    buf.clear();
+
+    loop {
+        if rdr.read_until(b'>', buf)? == 0 || buf.last() != Some(&b'>') {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "File ended in the middle of an XML tag",
+            ));
+        }
+
+        let mut quote = None;
+        for &c in buf.iter() {
+            match quote {
+                None if c == b'"' || c == b'\'' => quote = Some(c),
+                Some(q) if c == q => quote = None,
+                _ => {}
+            }
+        }
+
+        if quote.is_none() {
+            // remove the '>'
+            buf.pop();
+            return Ok(true);
+        }
+    }
+}
+
+/// Opens an OsmChange file. If the file is gzip compressed (as replication diffs are), and the
+/// `gzip` feature is enabled, it will be transparently decompressed.
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead>> {
+    open_file(path)
+}
+
+/// The tiles at `zoom` which are within `buffer` pixels (of a 256×256 tile) of this point.
+///
+/// Buffers are only supported up to zoom 24, above that, only the tile the point is in is
+/// returned. There are no tiles above zoom 31, so nothing is returned.
+pub fn tiles_near_point(point: &LatLon, zoom: u8, buffer: u32) -> Vec<Tile> {
+    if zoom > 31 {
+        return Vec::new();
+    }
+    let max_tile = (2u64.pow(zoom as u32) - 1) as u32;
+
+    if buffer == 0 || zoom > 24 {
+        let (x, y) = lat_lon_to_tile(point.lat(), point.lon(), zoom);
+        return vec![Tile::new(zoom, x.min(max_tile), y.min(max_tile)).unwrap()];
+    }
+
+    // A tile is 256 = 2^8 pixels wide, so the "tile" at zoom+8 is the global pixel
+    let (px, py) = lat_lon_to_tile(point.lat(), point.lon(), zoom + 8);
+    let tile_range = |p: u32| {
+        (
+            (p.saturating_sub(buffer) / 256).min(max_tile),
+            (p.saturating_add(buffer) / 256).min(max_tile),
+        )
+    };
+    let (x1, x2) = tile_range(px);
+    let (y1, y2) = tile_range(py);
+
+    (x1..=x2)
+        .flat_map(|x| (y1..=y2).map(move |y| Tile::new(zoom, x, y).unwrap()))
+        .collect()
+}
+
+/// All the tiles, at these zoom levels, which have been changed by this OsmChange file, including
+/// any tiles within `buffer` pixels of a change.
+pub fn expired_tiles<R: BufRead>(rdr: R, zooms: &[u8], buffer: u32) -> io::Result<HashSet<Tile>> {
+    let locations = changed_locations(rdr)?;
+
+    let mut tiles = HashSet::new();
+    for loc in locations.iter() {
+        for &zoom in zooms {
+            tiles.extend(tiles_near_point(loc, zoom, buffer));
+        }
+    }
+
+    Ok(tiles)
+}
+
+/// All the metatiles, of this scale and at these zoom levels, which have been changed by this
+/// OsmChange file, including any metatiles within `buffer` pixels of a change.
+pub fn expired_metatiles<R: BufRead>(
+    rdr: R,
+    zooms: &[u8],
+    buffer: u32,
+    scale: u8,
+) -> io::Result<HashSet<Metatile>> {
+    if !scale.is_power_of_two() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Metatile scale must be a power of 2",
+        ));
+    }
+
+    Ok(expired_tiles(rdr, zooms, buffer)?
+        .into_iter()
+        .filter_map(|t| t.metatile(scale))
+        .collect())
+}
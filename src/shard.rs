@@ -0,0 +1,359 @@
+//! Split a job of tiles or metatiles into shards, e.g. to spread rendering over many machines.
+//!
+//! All the metatiles are put in order of zoom, then along a curve (`TileOrder`), and split into
+//! shards with (nearly) the same number of metatiles. With `TileOrder::Hilbert`, each shard is a
+//! compact area on the map, so workers share less data at the edges.
+//!
+//! A `Shard` can be converted to and from a short string, so it can be sent to another machine,
+//! which can then iterate over the metatiles in it without needing the other shards.
+//!
+//! ```
+//! # use slippy_map_tiles::{BBox, TileOrder};
+//! # use slippy_map_tiles::shard::{self, Shard};
+//! let bbox = BBox::new(55.5, -10.7, 51.4, -5.3).unwrap();
+//! let shards = shard::partition_bbox(8, Some(&bbox), 0, 14, TileOrder::Hilbert, 4);
+//! assert_eq!(shards.len(), 4);
+//!
+//! // Send this to a worker
+//! let descriptor = shards[1].to_string();
+//!
+//! let shard: Shard = descriptor.parse().unwrap();
+//! assert_eq!(shard.iter().count() as u64, shards[1].len());
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{metatile_range, BBox, Metatile, Tile, TileOrder, TileRange};
+
+/// The position of a metatile in the job: (zoom, position along the curve in the metatile grid).
+type Key = (u8, u64);
+
+/// The key for this metatile, without checking the scale.
+fn key(order: TileOrder, metatile: &Metatile) -> Key {
+    let shift = metatile.scale().trailing_zeros() as u8;
+    let zoom = metatile.zoom().saturating_sub(shift);
+    let index = order.index(metatile.x() >> shift, metatile.y() >> shift, zoom);
+    (metatile.zoom(), index)
+}
+
+/// One part of a job, which can be sent to a worker. See the module docs.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Shard {
+    bbox: Option<BBox>,
+    // For shards from `partition_tiles`, the smallest rectangle around it's tiles at each zoom
+    ranges: Vec<TileRange>,
+    scale: u8,
+    order: TileOrder,
+    first: Key,
+    last: Key,
+    len: u64,
+}
+
+/// Split the metatiles of `scale` in this bbox (or the whole world), from `min_zoom` to
+/// `max_zoom` (inclusive, up to 31), into `num_shards` shards. There will be fewer shards if
+/// there are fewer metatiles. Use a scale of 1 for tiles.
+///
+/// Panics if `scale` is not a power of 2, or `num_shards` is 0.
+pub fn partition_bbox(
+    scale: u8,
+    bbox: Option<&BBox>,
+    min_zoom: u8,
+    max_zoom: u8,
+    order: TileOrder,
+    num_shards: usize,
+) -> Vec<Shard> {
+    assert!(scale.is_power_of_two(), "scale must be a power of 2");
+    assert!(num_shards > 0, "num_shards must be more than 0");
+
+    let ranges: Vec<(u8, TileRange)> = (min_zoom..=max_zoom.min(31))
+        .map(|zoom| (zoom, metatile_range(bbox, zoom, scale)))
+        .collect();
+    let total: u64 = ranges.iter().map(|(_, r)| r.count()).sum();
+
+    // The key of the nth metatile in the whole job
+    let nth_key = |mut n: u64| -> Key {
+        for &(zoom, ref range) in ranges.iter() {
+            if n < range.count() {
+                return (zoom, range.nth_index(order, n).unwrap());
+            }
+            n -= range.count();
+        }
+        unreachable!()
+    };
+
+    split(total, num_shards)
+        .map(|(start, end)| Shard {
+            bbox: bbox.cloned(),
+            ranges: Vec::new(),
+            scale,
+            order,
+            first: nth_key(start),
+            last: nth_key(end - 1),
+            len: end - start,
+        })
+        .collect()
+}
+
+/// Split these tiles (in any order, duplicates are ignored) into `num_shards` shards. There
+/// will be fewer shards if there are fewer tiles.
+///
+/// The shards don't store the tiles, only where they start & end, and the smallest rectangle of
+/// tiles at each zoom. So `Shard::iter()` returns the tiles in those rectangles in that part of
+/// the curve, which can include tiles that weren't given. To only use the tiles given, each
+/// worker should go through the same list of tiles, and only use the ones for which
+/// `Shard::contains_tile` is true.
+///
+/// Panics if `num_shards` is 0.
+pub fn partition_tiles<I: IntoIterator<Item = Tile>>(
+    tiles: I,
+    order: TileOrder,
+    num_shards: usize,
+) -> Vec<Shard> {
+    assert!(num_shards > 0, "num_shards must be more than 0");
+
+    let mut keys: Vec<Key> = tiles
+        .into_iter()
+        .map(|t| (t.zoom(), order.index(t.x(), t.y(), t.zoom())))
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+
+    split(keys.len() as u64, num_shards)
+        .map(|(start, end)| {
+            let keys = &keys[start as usize..end as usize];
+            let mut ranges: Vec<TileRange> = Vec::new();
+            for &(zoom, index) in keys.iter() {
+                let (x, y) = order.xy(index, zoom);
+                let tile = TileRange::new(zoom, x, x, y, y).unwrap();
+                match ranges.last_mut() {
+                    Some(range) if range.zoom() == zoom => {
+                        *range = range.union_bounds(&tile).unwrap()
+                    }
+                    _ => ranges.push(tile),
+                }
+            }
+            Shard {
+                bbox: None,
+                ranges,
+                scale: 1,
+                order,
+                first: keys[0],
+                last: keys[keys.len() - 1],
+                len: end - start,
+            }
+        })
+        .collect()
+}
+
+/// Split `total` things into `num_shards` (start, end) ranges, skipping empty ones
+fn split(total: u64, num_shards: usize) -> impl Iterator<Item = (u64, u64)> {
+    let n = num_shards as u128;
+    let boundary = move |i: u128| (i * total as u128 / n) as u64;
+    (0..n)
+        .map(move |i| (boundary(i), boundary(i + 1)))
+        .filter(|&(start, end)| start < end)
+}
+
+impl Shard {
+    /// The bbox of the job, or None for the whole world (or from `partition_tiles`)
+    pub fn bbox(&self) -> Option<&BBox> {
+        self.bbox.as_ref()
+    }
+
+    /// For shards from `partition_tiles`, the smallest rectangle around the tiles in this shard,
+    /// for each zoom which has some. Empty for other shards.
+    pub fn tile_ranges(&self) -> &[TileRange] {
+        &self.ranges
+    }
+
+    /// The metatiles (in metatile coordinates) at this zoom which this shard can include. None
+    /// if there are none.
+    fn range_for_zoom(&self, zoom: u8) -> Option<TileRange> {
+        if self.ranges.is_empty() {
+            Some(metatile_range(self.bbox.as_ref(), zoom, self.scale))
+        } else {
+            self.ranges.iter().find(|r| r.zoom() == zoom).cloned()
+        }
+    }
+
+    /// The metatile scale, 1 for tiles.
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    pub fn order(&self) -> TileOrder {
+        self.order
+    }
+
+    /// The number of metatiles (or tiles) in this shard
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Always false, since empty shards aren't created
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The lowest & highest zoom of the metatiles in this shard
+    pub fn zooms(&self) -> (u8, u8) {
+        (self.first.0, self.last.0)
+    }
+
+    /// True iff this metatile is part of this shard
+    pub fn contains(&self, metatile: &Metatile) -> bool {
+        if metatile.scale() != self.scale {
+            return false;
+        }
+        let key = key(self.order, metatile);
+        if key < self.first || key > self.last {
+            return false;
+        }
+        match self.range_for_zoom(metatile.zoom()) {
+            None => false,
+            Some(range) => {
+                let shift = self.scale.trailing_zeros();
+                let (x, y) = (metatile.x() >> shift, metatile.y() >> shift);
+                x >= range.x_min() && x <= range.x_max() && y >= range.y_min() && y <= range.y_max()
+            }
+        }
+    }
+
+    /// True iff this tile is in one of the metatiles in this shard
+    pub fn contains_tile(&self, tile: &Tile) -> bool {
+        tile.metatile(self.scale)
+            .is_some_and(|mt| self.contains(&mt))
+    }
+
+    /// Iterate over the metatiles in this shard, in order.
+    pub fn iter(&self) -> ShardIter {
+        ShardIter {
+            shard: self.clone(),
+            curr_zoom: self.first.0,
+            next_index: self.first.1,
+            finished: false,
+        }
+    }
+
+    /// Iterate over all the tiles in the metatiles in this shard.
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> {
+        self.iter().flat_map(|mt| mt.tiles())
+    }
+}
+
+impl fmt::Display for Shard {
+    /// e.g. `hilbert scale=8 first=10/123 last=14/4567 len=1000 bbox=-10.7,51.4,-5.3,55.5`, or
+    /// `zorder scale=1 first=3/5 last=4/10 len=6 ranges=3/1-2/1-2,4/2-3/2-3` from
+    /// `partition_tiles`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} scale={} first={}/{} last={}/{} len={}",
+            self.order, self.scale, self.first.0, self.first.1, self.last.0, self.last.1, self.len
+        )?;
+        if let Some(ref bbox) = self.bbox {
+            write!(f, " bbox={}", bbox)?;
+        }
+        for (i, range) in self.ranges.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " ranges=" } else { "," }, range)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Shard {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse_key(s: &str) -> Result<Key, &'static str> {
+            let mut parts = s.splitn(2, '/');
+            let zoom = parts.next().and_then(|z| z.parse().ok());
+            let index = parts.next().and_then(|i| i.parse().ok());
+            match (zoom, index) {
+                (Some(zoom), Some(index)) if zoom < 32 => Ok((zoom, index)),
+                _ => Err("Invalid shard position"),
+            }
+        }
+
+        let mut parts = s.split_whitespace();
+        let order = parts.next().ok_or("Empty shard")?.parse()?;
+        let (mut scale, mut first, mut last, mut len, mut bbox) = (None, None, None, None, None);
+        let mut ranges = Vec::new();
+        for part in parts {
+            let mut kv = part.splitn(2, '=');
+            let (k, v) = (kv.next().unwrap(), kv.next().ok_or("Missing =")?);
+            match k {
+                "scale" => scale = Some(v.parse::<u8>().map_err(|_| "Invalid scale")?),
+                "first" => first = Some(parse_key(v)?),
+                "last" => last = Some(parse_key(v)?),
+                "len" => len = Some(v.parse::<u64>().map_err(|_| "Invalid len")?),
+                "bbox" => bbox = Some(v.parse::<BBox>()?),
+                "ranges" => {
+                    ranges = v
+                        .split(',')
+                        .map(|r| r.parse::<TileRange>())
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err("Unknown shard field"),
+            }
+        }
+
+        let scale = scale.ok_or("Missing scale")?;
+        if !scale.is_power_of_two() {
+            return Err("Invalid scale");
+        }
+        let (first, last) = (first.ok_or("Missing first")?, last.ok_or("Missing last")?);
+        if first > last {
+            return Err("first is after last");
+        }
+
+        Ok(Shard {
+            bbox,
+            ranges,
+            scale,
+            order,
+            first,
+            last,
+            len: len.ok_or("Missing len")?,
+        })
+    }
+}
+
+/// Iterates over the metatiles in a `Shard`
+pub struct ShardIter {
+    shard: Shard,
+    curr_zoom: u8,
+    next_index: u64,
+    finished: bool,
+}
+
+impl Iterator for ShardIter {
+    type Item = Metatile;
+
+    fn next(&mut self) -> Option<Metatile> {
+        let (scale, order, last) = (self.shard.scale, self.shard.order, self.shard.last);
+        while !self.finished && self.curr_zoom <= last.0 {
+            let index = self
+                .shard
+                .range_for_zoom(self.curr_zoom)
+                .and_then(|range| Some((range, range.next_index(order, self.next_index)?)));
+            match index {
+                Some((range, index)) if (self.curr_zoom, index) <= last => {
+                    self.next_index = index + 1;
+                    let (x, y) = order.xy(index, range.zoom());
+                    let s = scale as u32;
+                    return Metatile::new(scale, self.curr_zoom, x * s, y * s);
+                }
+                Some(_) => self.finished = true,
+                None => {
+                    self.curr_zoom += 1;
+                    self.next_index = 0;
+                }
+            }
+        }
+
+        self.finished = true;
+        None
+    }
+}
@@ -0,0 +1,267 @@
+//! Plan a static map image, made by stitching tiles together: which tiles are needed, which part
+//! of each tile to use, and where it goes in the image.
+//!
+//! ```
+//! # use slippy_map_tiles::BBox;
+//! # use slippy_map_tiles::staticmap::StaticMap;
+//! let ireland = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+//! let map = StaticMap::for_bbox(&ireland, 7, 256).unwrap();
+//! assert_eq!((map.width(), map.height()), (579, 702));
+//! for part in map.tiles() {
+//!     // Copy part.crop() of part.tile() to part.dest() in the image
+//! }
+//! ```
+
+use super::viewport::global_pixel_rect_bbox;
+use super::{lat_lon_to_tile_fraction, BBox, LatLon, Tile};
+
+#[cfg(feature = "world_file")]
+use super::mercator;
+#[cfg(feature = "world_file")]
+use world_image_file::WorldFile;
+
+/// A rectangle of pixels, `x`,`y` is the top left
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct PixelRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl PixelRect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> PixelRect {
+        PixelRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+/// A static map image, see the module docs
+#[derive(PartialEq, Debug, Clone)]
+pub struct StaticMap {
+    zoom: u8,
+    tile_size: u32,
+    /// The global pixel of the top left of the image. `left` can be outside the world, when the
+    /// image crosses the antimeridian, and `top` can be, when it's taller than the world.
+    left: i64,
+    top: i64,
+    width: u32,
+    height: u32,
+}
+
+/// Part of a tile, and where it goes in a `StaticMap` image
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct StaticMapTile {
+    tile: Tile,
+    crop: PixelRect,
+    dest: PixelRect,
+}
+
+impl StaticMapTile {
+    pub fn tile(&self) -> Tile {
+        self.tile
+    }
+    /// The part of the tile image to use
+    pub fn crop(&self) -> PixelRect {
+        self.crop
+    }
+    /// Where that part goes in the static map image. It's the same size as `crop()`.
+    pub fn dest(&self) -> PixelRect {
+        self.dest
+    }
+}
+
+impl StaticMap {
+    /// The smallest image which shows all of `bbox` at this zoom, using tiles `tile_size` pixels
+    /// wide. If `left` is more than `right`, the bbox crosses the antimeridian. Returns None if
+    /// `zoom` is more than 31, or the image would be too big.
+    pub fn for_bbox(bbox: &BBox, zoom: u8, tile_size: u32) -> Option<StaticMap> {
+        if zoom > 31 || tile_size == 0 {
+            return None;
+        }
+        let world_size = tile_size as f64 * 2f64.powi(zoom as i32);
+        let (left, top) = lat_lon_to_tile_fraction(bbox.top() as f64, bbox.left() as f64, 0);
+        let (right, bottom) =
+            lat_lon_to_tile_fraction(bbox.bottom() as f64, bbox.right() as f64, 0);
+        let right = if left > right { right + 1. } else { right };
+
+        // Allow a tenth of a pixel for rounding, so a bbox on the edge of a pixel (e.g. a tile's
+        // bbox) doesn't get one more
+        let pixel =
+            |x: f64, round: fn(f64) -> f64, nudge: f64| round(x * world_size + nudge) as i64;
+        let (left, right) = (pixel(left, f64::floor, 0.1), pixel(right, f64::ceil, -0.1));
+        let (top, bottom) = (pixel(top, f64::floor, 0.1), pixel(bottom, f64::ceil, -0.1));
+
+        StaticMap::new(
+            zoom,
+            tile_size,
+            left,
+            top,
+            (right - left).max(1),
+            (bottom - top).max(1),
+        )
+    }
+
+    /// A `width` x `height` image centred on `centre` at this zoom, using tiles `tile_size` pixels
+    /// wide. Returns None if `zoom` is more than 31, or the size is 0.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{LatLon, Tile};
+    /// # use slippy_map_tiles::staticmap::StaticMap;
+    /// let map = StaticMap::for_centre(&LatLon::new(0., 0.).unwrap(), 1, 300, 200, 256).unwrap();
+    /// let tiles = map.tiles();
+    /// assert_eq!(tiles.len(), 4);
+    /// assert_eq!(tiles[0].tile(), Tile::new(1, 0, 0).unwrap());
+    /// assert_eq!((tiles[0].crop().x(), tiles[0].crop().y()), (106, 156));
+    /// assert_eq!((tiles[0].dest().width(), tiles[0].dest().height()), (150, 100));
+    /// ```
+    pub fn for_centre(
+        centre: &LatLon,
+        zoom: u8,
+        width: u32,
+        height: u32,
+        tile_size: u32,
+    ) -> Option<StaticMap> {
+        if zoom > 31 || tile_size == 0 {
+            return None;
+        }
+        let world_size = tile_size as f64 * 2f64.powi(zoom as i32);
+        let (x, y) = lat_lon_to_tile_fraction(centre.lat() as f64, centre.lon() as f64, 0);
+        let left = (x * world_size - width as f64 / 2.).round() as i64;
+        let top = (y * world_size - height as f64 / 2.).round() as i64;
+
+        StaticMap::new(zoom, tile_size, left, top, width as i64, height as i64)
+    }
+
+    fn new(
+        zoom: u8,
+        tile_size: u32,
+        left: i64,
+        top: i64,
+        width: i64,
+        height: i64,
+    ) -> Option<StaticMap> {
+        if width <= 0 || height <= 0 || width > u32::MAX as i64 || height > u32::MAX as i64 {
+            return None;
+        }
+        Some(StaticMap {
+            zoom,
+            tile_size,
+            left,
+            top,
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+
+    pub fn zoom(&self) -> u8 {
+        self.zoom
+    }
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+    /// The width of the image in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    /// The height of the image in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn world_size(&self) -> i64 {
+        (self.tile_size as i64) << self.zoom
+    }
+
+    /// The area the image shows. Parts outside the world (north & south) are left out. If the
+    /// image crosses the antimeridian, `left` is more than `right`, and if it's wider than the
+    /// world, it's all longitudes.
+    pub fn bbox(&self) -> BBox {
+        global_pixel_rect_bbox(
+            (self.left as f64, self.top as f64),
+            (self.width as f64, self.height as f64),
+            self.world_size() as f64,
+        )
+    }
+
+    /// The tiles to stitch together, row by row. Tiles are repeated across the antimeridian.
+    /// Parts of the image north or south of the world have no tiles.
+    pub fn tiles(&self) -> Vec<StaticMapTile> {
+        let tile_size = self.tile_size as i64;
+        let num_tiles = 1i64 << self.zoom;
+        let right = self.left + self.width as i64;
+        let bottom = self.top + self.height as i64;
+
+        let first_row = self.top.div_euclid(tile_size).max(0);
+        let last_row = ((bottom - 1).div_euclid(tile_size)).min(num_tiles - 1);
+        let first_col = self.left.div_euclid(tile_size);
+        let last_col = (right - 1).div_euclid(tile_size);
+
+        let mut tiles = Vec::new();
+        for row in first_row..=last_row {
+            let tile_top = row * tile_size;
+            let y0 = tile_top.max(self.top);
+            let y1 = (tile_top + tile_size).min(bottom);
+            for col in first_col..=last_col {
+                let tile_left = col * tile_size;
+                let x0 = tile_left.max(self.left);
+                let x1 = (tile_left + tile_size).min(right);
+                let (width, height) = ((x1 - x0) as u32, (y1 - y0) as u32);
+
+                tiles.push(StaticMapTile {
+                    tile: Tile::new(self.zoom, col.rem_euclid(num_tiles) as u32, row as u32)
+                        .unwrap(),
+                    crop: PixelRect::new(
+                        (x0 - tile_left) as u32,
+                        (y0 - tile_top) as u32,
+                        width,
+                        height,
+                    ),
+                    dest: PixelRect::new(
+                        (x0 - self.left) as u32,
+                        (y0 - self.top) as u32,
+                        width,
+                        height,
+                    ),
+                });
+            }
+        }
+
+        tiles
+    }
+
+    #[cfg(feature = "world_file")]
+    /// The World File (in EPSG:3857 / Web Mercator SRID) for the image. As with
+    /// `Tile::world_file`, the coordinates are of the centre of the top left pixel.
+    pub fn world_file(&self) -> WorldFile {
+        let scale = mercator::resolution(self.zoom, self.tile_size);
+
+        WorldFile {
+            x_scale: scale,
+            y_scale: -scale,
+
+            x_skew: 0.,
+            y_skew: 0.,
+
+            x_coord: (self.left as f64 + 0.5) * scale - mercator::EXTENT,
+            y_coord: -(self.top as f64 + 0.5) * scale + mercator::EXTENT,
+        }
+    }
+}
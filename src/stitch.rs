@@ -0,0 +1,310 @@
+//! Stitch tile images from a directory into one image, and split a metatile image into tiles.
+//! Needs the `image` feature.
+//!
+//! Only PNG images are read & written. Turn on more of the `image` crate's features in your own
+//! `Cargo.toml` to use other formats.
+//!
+//! ```no_run
+//! # use slippy_map_tiles::BBox;
+//! # use slippy_map_tiles::stitch::{TileDir, TileLayout};
+//! let tiles = TileDir::new("/srv/tiles", TileLayout::Zxy, "png");
+//! let ireland = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+//! let (map, image) = tiles.compose_bbox(&ireland, 7).unwrap();
+//! image.save("ireland.png").unwrap();
+//! # #[cfg(feature = "world_file")]
+//! map.world_file().write_to_path("ireland.pgw");
+//! ```
+
+use image::{imageops, GenericImageView, ImageError, ImageReader, RgbaImage};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use super::staticmap::StaticMap;
+use super::{BBox, Metatile, Tile};
+
+/// The most pixels in an image made by `TileDir::compose`, 2^28 (1 GiB as RGBA)
+pub const MAX_PIXELS: u64 = 1 << 28;
+
+/// How tiles are stored in a directory, one of the `Tile::*_path` layouts
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum TileLayout {
+    /// `Tile::zxy_path`, e.g. `6/33/21.png`
+    Zxy,
+    /// `Tile::tc_path` (TileCache)
+    Tc,
+    /// `Tile::mp_path` (MapProxy)
+    Mp,
+    /// `Tile::ts_path` (TileStash safe)
+    Ts,
+}
+
+impl TileLayout {
+    /// The path of this tile, relative to the directory
+    pub fn path(&self, tile: &Tile, ext: &str) -> String {
+        match *self {
+            TileLayout::Zxy => tile.zxy_path(ext),
+            TileLayout::Tc => tile.tc_path(ext),
+            TileLayout::Mp => tile.mp_path(ext),
+            TileLayout::Ts => tile.ts_path(ext),
+        }
+    }
+}
+
+/// An error from reading, writing, stitching or splitting images
+#[derive(Debug)]
+pub enum StitchError {
+    /// An I/O error happened with this file
+    Io { path: PathBuf, error: io::Error },
+
+    /// This file couldn't be decoded or encoded
+    Image { path: PathBuf, error: ImageError },
+
+    /// An image (a tile, or the metatile for `path` None) is the wrong size
+    WrongSize {
+        path: Option<PathBuf>,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+
+    /// The image can't be made, e.g. the zoom is more than 31
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StitchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StitchError::Io {
+                ref path,
+                ref error,
+            } => write!(f, "I/O error with {}: {}", path.display(), error),
+            StitchError::Image {
+                ref path,
+                ref error,
+            } => write!(f, "Image error with {}: {}", path.display(), error),
+            StitchError::WrongSize {
+                ref path,
+                expected,
+                found,
+            } => {
+                match *path {
+                    Some(ref path) => write!(f, "{} is ", path.display())?,
+                    None => write!(f, "Metatile image is ")?,
+                }
+                write!(
+                    f,
+                    "{}x{}, not {}x{}",
+                    found.0, found.1, expected.0, expected.1
+                )
+            }
+            StitchError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Error for StitchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            StitchError::Io { ref error, .. } => Some(error),
+            StitchError::Image { ref error, .. } => Some(error),
+            StitchError::WrongSize { .. } | StitchError::Invalid(_) => None,
+        }
+    }
+}
+
+/// A directory of tile images
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TileDir {
+    root: PathBuf,
+    layout: TileLayout,
+    ext: String,
+    tile_size: u32,
+}
+
+impl TileDir {
+    /// Tiles in `root`, stored in `layout`, with this file extension (e.g. `"png"`). Tiles are
+    /// 256 pixels.
+    pub fn new<P: AsRef<Path>>(root: P, layout: TileLayout, ext: &str) -> TileDir {
+        TileDir {
+            root: root.as_ref().to_path_buf(),
+            layout,
+            ext: ext.to_string(),
+            tile_size: 256,
+        }
+    }
+
+    /// The tiles are this many pixels wide, e.g. 512. Returns None if `tile_size` is 0.
+    pub fn with_tile_size(mut self, tile_size: u32) -> Option<Self> {
+        if tile_size == 0 {
+            return None;
+        }
+        self.tile_size = tile_size;
+        Some(self)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    pub fn layout(&self) -> TileLayout {
+        self.layout
+    }
+    pub fn ext(&self) -> &str {
+        &self.ext
+    }
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// The file for this tile
+    pub fn tile_path(&self, tile: &Tile) -> PathBuf {
+        self.root.join(self.layout.path(tile, &self.ext))
+    }
+
+    /// Read this tile's image. Returns `Ok(None)` if there is no file for it.
+    pub fn read_tile(&self, tile: &Tile) -> Result<Option<RgbaImage>, StitchError> {
+        let path = self.tile_path(tile);
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(StitchError::Io { path, error }),
+        };
+        let image = ImageReader::new(BufReader::new(file))
+            .with_guessed_format()
+            .map_err(|error| StitchError::Io {
+                path: path.clone(),
+                error,
+            })?
+            .decode()
+            .map_err(|error| StitchError::Image {
+                path: path.clone(),
+                error,
+            })?;
+
+        if image.dimensions() != (self.tile_size, self.tile_size) {
+            return Err(StitchError::WrongSize {
+                path: Some(path),
+                expected: (self.tile_size, self.tile_size),
+                found: image.dimensions(),
+            });
+        }
+
+        Ok(Some(image.to_rgba8()))
+    }
+
+    /// Save this tile's image, making any directories needed. The format is from the extension.
+    pub fn write_tile(&self, tile: &Tile, image: &RgbaImage) -> Result<PathBuf, StitchError> {
+        let path = self.tile_path(tile);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|error| StitchError::Io {
+                path: parent.to_path_buf(),
+                error,
+            })?;
+        }
+        image.save(&path).map_err(|error| StitchError::Image {
+            path: path.clone(),
+            error,
+        })?;
+
+        Ok(path)
+    }
+
+    /// Stitch the tiles of `map` into one image. Missing tiles are left transparent. `map` must
+    /// use this tile size, and have at most `MAX_PIXELS` pixels.
+    pub fn compose(&self, map: &StaticMap) -> Result<RgbaImage, StitchError> {
+        if map.tile_size() != self.tile_size {
+            return Err(StitchError::Invalid("static map has a different tile size"));
+        }
+        if map.width() as u64 * map.height() as u64 > MAX_PIXELS {
+            return Err(StitchError::Invalid("static map has too many pixels"));
+        }
+        let mut image = RgbaImage::new(map.width(), map.height());
+        for part in map.tiles() {
+            let tile = match self.read_tile(&part.tile())? {
+                Some(tile) => tile,
+                None => continue,
+            };
+            let (crop, dest) = (part.crop(), part.dest());
+            let crop = imageops::crop_imm(&tile, crop.x(), crop.y(), crop.width(), crop.height());
+            imageops::replace(&mut image, &*crop, dest.x() as i64, dest.y() as i64);
+        }
+
+        Ok(image)
+    }
+
+    /// Stitch an image of `bbox` at this zoom (see `StaticMap::for_bbox`). The `StaticMap` has
+    /// the `world_file()` for the image.
+    pub fn compose_bbox(
+        &self,
+        bbox: &BBox,
+        zoom: u8,
+    ) -> Result<(StaticMap, RgbaImage), StitchError> {
+        let map = StaticMap::for_bbox(bbox, zoom, self.tile_size).ok_or(StitchError::Invalid(
+            "invalid zoom, or the image is too big",
+        ))?;
+        let image = self.compose(&map)?;
+
+        Ok((map, image))
+    }
+
+    /// Split a rendered metatile image into tiles, and save them. Returns the files, in
+    /// `Metatile::tiles()` order.
+    pub fn write_metatile(
+        &self,
+        metatile: &Metatile,
+        image: &RgbaImage,
+    ) -> Result<Vec<PathBuf>, StitchError> {
+        split_metatile(metatile, image, self.tile_size)?
+            .iter()
+            .map(|(tile, image)| self.write_tile(tile, image))
+            .collect()
+    }
+}
+
+/// Split a rendered metatile image into its tiles, which are `tile_size` pixels wide, in
+/// `Metatile::tiles()` order. The image must be exactly the size of the metatile.
+///
+/// ```
+/// # extern crate image;
+/// # extern crate slippy_map_tiles;
+/// # use slippy_map_tiles::{Metatile, Tile};
+/// # use slippy_map_tiles::stitch::split_metatile;
+/// # fn main() {
+/// let metatile = Metatile::new(8, 10, 0, 0).unwrap();
+/// let image = image::RgbaImage::new(2048, 2048);
+/// let tiles = split_metatile(&metatile, &image, 256).unwrap();
+/// assert_eq!(tiles.len(), 64);
+/// assert_eq!(tiles[1].0, Tile::new(10, 0, 1).unwrap());
+/// # }
+/// ```
+pub fn split_metatile(
+    metatile: &Metatile,
+    image: &RgbaImage,
+    tile_size: u32,
+) -> Result<Vec<(Tile, RgbaImage)>, StitchError> {
+    let size = (metatile.size() as u32)
+        .checked_mul(tile_size)
+        .ok_or(StitchError::Invalid("tile size is too big"))?;
+    if image.dimensions() != (size, size) {
+        return Err(StitchError::WrongSize {
+            path: None,
+            expected: (size, size),
+            found: image.dimensions(),
+        });
+    }
+
+    Ok(metatile
+        .tiles()
+        .into_iter()
+        .map(|tile| {
+            let x = (tile.x() - metatile.x()) * tile_size;
+            let y = (tile.y() - metatile.y()) * tile_size;
+            (
+                tile,
+                imageops::crop_imm(image, x, y, tile_size, tile_size).to_image(),
+            )
+        })
+        .collect())
+}
@@ -154,7 +154,7 @@ fn latlon_create() {
     assert_eq!(p1.lat(), 54.9);
     assert_eq!(p1.lon(), 5.5);
 
-    assert_eq!(p1.to_3857(), (612257.20, 7342480.5));
+    assert_eq!(p1.to_3857(), (612257.2, 7342482.5));
 }
 
 #[test]
@@ -270,16 +270,16 @@ fn test_num_tiles_in_zoom() {
     assert_eq!(num_tiles_in_zoom(0), Some(1));
     assert_eq!(num_tiles_in_zoom(1), Some(4));
     assert_eq!(num_tiles_in_zoom(2), Some(16));
-    assert_eq!(num_tiles_in_zoom(3), Some(256));
-    assert_eq!(num_tiles_in_zoom(4), Some(65_536));
-    assert_eq!(num_tiles_in_zoom(5), Some(4_294_967_296));
-
-    assert_eq!(num_tiles_in_zoom(6), None);
-
-    // Can't do these because the integers overflow
-    //assert_eq!(num_tiles_in_zoom(17), 17_179_869_184);
-    //assert_eq!(num_tiles_in_zoom(18), 68_719_476_736);
-    //assert_eq!(num_tiles_in_zoom(19), 274_877_906_944);
+    assert_eq!(num_tiles_in_zoom(3), Some(64));
+    assert_eq!(num_tiles_in_zoom(4), Some(256));
+    assert_eq!(num_tiles_in_zoom(5), Some(1_024));
+    assert_eq!(num_tiles_in_zoom(6), Some(4_096));
+
+    assert_eq!(num_tiles_in_zoom(17), Some(17_179_869_184));
+    assert_eq!(num_tiles_in_zoom(18), Some(68_719_476_736));
+    assert_eq!(num_tiles_in_zoom(19), Some(274_877_906_944));
+    assert_eq!(num_tiles_in_zoom(31), Some(1 << 62));
+    assert_eq!(num_tiles_in_zoom(32), None);
 }
 
 #[test]
@@ -331,55 +331,44 @@ fn all_tiles_to_zoom_iter() {
 
     assert_eq!(Tile::all_to_zoom(2).size_hint(), (21, Some(21)));
 
-    assert_eq!(Tile::all_to_zoom(3).size_hint(), (277, Some(277)));
-    assert_eq!(Tile::all_to_zoom(4).size_hint(), (65_813, Some(65_813)));
-    assert_eq!(
-        Tile::all_to_zoom(5).size_hint(),
-        (4_295_033_109, Some(4_295_033_109))
-    );
-    assert_eq!(
-        Tile::all_to_zoom(6).size_hint(),
-        (18_446_744_073_709_551_615, None)
-    );
-    assert_eq!(
-        Tile::all_to_zoom(7).size_hint(),
-        (18_446_744_073_709_551_615, None)
-    );
-    assert_eq!(
-        Tile::all_to_zoom(8).size_hint(),
-        (18_446_744_073_709_551_615, None)
-    );
+    assert_eq!(Tile::all_to_zoom(3).size_hint(), (85, Some(85)));
+    assert_eq!(Tile::all_to_zoom(4).size_hint(), (341, Some(341)));
+    assert_eq!(Tile::all_to_zoom(5).size_hint(), (1_365, Some(1_365)));
+    assert_eq!(Tile::all_to_zoom(6).size_hint(), (5_461, Some(5_461)));
+    assert_eq!(Tile::all_to_zoom(7).size_hint(), (21_845, Some(21_845)));
+    assert_eq!(Tile::all_to_zoom(8).size_hint(), (87_381, Some(87_381)));
     assert_eq!(
-        Tile::all_to_zoom(9).size_hint(),
-        (18_446_744_073_709_551_615, None)
+        Tile::all_to_zoom(20).size_hint(),
+        (1_466_015_503_701, Some(1_466_015_503_701))
     );
+    assert_eq!(Tile::all_to_zoom(9).size_hint(), (349_525, Some(349_525)));
     assert_eq!(
         Tile::all_to_zoom(10).size_hint(),
-        (18_446_744_073_709_551_615, None)
+        (1_398_101, Some(1_398_101))
     );
     assert_eq!(
         Tile::all_to_zoom(11).size_hint(),
-        (18_446_744_073_709_551_615, None)
+        (5_592_405, Some(5_592_405))
     );
     assert_eq!(
         Tile::all_to_zoom(12).size_hint(),
-        (18_446_744_073_709_551_615, None)
+        (22_369_621, Some(22_369_621))
     );
     assert_eq!(
         Tile::all_to_zoom(13).size_hint(),
-        (18_446_744_073_709_551_615, None)
+        (89_478_485, Some(89_478_485))
     );
     assert_eq!(
         Tile::all_to_zoom(14).size_hint(),
-        (18_446_744_073_709_551_615, None)
+        (357_913_941, Some(357_913_941))
     );
     assert_eq!(
         Tile::all_to_zoom(15).size_hint(),
-        (18_446_744_073_709_551_615, None)
+        (1_431_655_765, Some(1_431_655_765))
     );
     assert_eq!(
         Tile::all_to_zoom(16).size_hint(),
-        (18_446_744_073_709_551_615, None)
+        (5_726_623_061, Some(5_726_623_061))
     );
 }
 
@@ -740,7 +729,7 @@ fn world_file() {
     let wf = t.world_file();
     assert_eq!(
         format!("{}", wf),
-        "2445.98490512564\n0\n0\n-2445.98490512564\n626172.1357121654\n6887893.4928338025\n"
+        "2445.98490512564\n0\n0\n-2445.98490512564\n627395.1281647272\n6886670.500381241\n"
     );
 }
 
@@ -787,3 +776,2960 @@ mod metatiles {
         }
     }
 }
+
+mod osc {
+    use super::*;
+    use osc::*;
+
+    const OSC: &str = r#"<?xml version='1.0' encoding='UTF-8'?>
+<osmChange version="0.6" generator="osmium/1.14.0">
+  <create>
+    <node id="1" version="1" user="a>b" lat="53.3498" lon="-6.2603"/>
+  </create>
+  <modify>
+    <way id="10" version="3">
+      <nd ref="2" lat="51.8985" lon="-8.4756"/>
+      <nd ref="3"/>
+      <tag k="highway" v="primary"/>
+    </way>
+  </modify>
+  <delete>
+    <node id="4" version="5"/>
+  </delete>
+</osmChange>
+"#;
+
+    #[test]
+    fn locations() {
+        let locs = changed_locations(OSC.as_bytes()).unwrap();
+        assert_eq!(
+            locs,
+            vec![
+                LatLon::new(53.3498, -6.2603).unwrap(),
+                LatLon::new(51.8985, -8.4756).unwrap()
+            ]
+        );
+
+        assert!(changed_locations("<osm></osm>".as_bytes()).is_err());
+        assert!(changed_locations("<osmChange><create><node lat=\"1\"".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn tiles() {
+        let tiles = expired_tiles(OSC.as_bytes(), &[0, 10], 0).unwrap();
+        assert_eq!(tiles.len(), 3);
+        assert!(tiles.contains(&Tile::new(0, 0, 0).unwrap()));
+        assert!(tiles.contains(&Tile::new(10, 494, 331).unwrap()));
+
+        let metatiles = expired_metatiles(OSC.as_bytes(), &[10], 0, 8).unwrap();
+        assert_eq!(metatiles.len(), 2);
+        assert!(metatiles.contains(&Metatile::new(8, 10, 488, 328).unwrap()));
+        assert!(metatiles.contains(&Metatile::new(8, 10, 480, 336).unwrap()));
+    }
+
+    #[test]
+    fn buffer() {
+        let point = LatLon::new(0., 0.).unwrap();
+        assert_eq!(
+            tiles_near_point(&point, 1, 0),
+            vec![Tile::new(1, 1, 1).unwrap()]
+        );
+        assert_eq!(
+            tiles_near_point(&point, 1, 1),
+            vec![
+                Tile::new(1, 0, 0).unwrap(),
+                Tile::new(1, 0, 1).unwrap(),
+                Tile::new(1, 1, 0).unwrap(),
+                Tile::new(1, 1, 1).unwrap()
+            ]
+        );
+
+        let corner = LatLon::new(90., 180.).unwrap();
+        assert_eq!(
+            tiles_near_point(&corner, 2, 0),
+            vec![Tile::new(2, 3, 0).unwrap()]
+        );
+        assert_eq!(tiles_near_point(&corner, 2, 300).len(), 4);
+
+        // No tiles above zoom 31
+        assert_eq!(tiles_near_point(&point, 31, 10).len(), 1);
+        assert_eq!(tiles_near_point(&point, 32, 10), vec![]);
+        assert_eq!(tiles_near_point(&point, 64, 0), vec![]);
+    }
+}
+
+#[test]
+fn quadkey() {
+    assert_eq!(Tile::new(0, 0, 0).unwrap().quadkey(), "");
+    assert_eq!(Tile::new(1, 1, 0).unwrap().quadkey(), "1");
+    assert_eq!(Tile::new(3, 3, 5).unwrap().quadkey(), "213");
+    assert_eq!(Tile::new(10, 511, 340).unwrap().quadkey(), "0313131311");
+
+    assert_eq!(Tile::from_quadkey(""), Tile::new(0, 0, 0));
+    assert_eq!(Tile::from_quadkey("0313131311"), Tile::new(10, 511, 340));
+    assert_eq!(Tile::from_quadkey("4"), None);
+    assert_eq!(Tile::from_quadkey("1/1"), None);
+
+    for t in Tile::all_to_zoom(4) {
+        assert_eq!(Tile::from_quadkey(&t.quadkey()), Some(t));
+    }
+}
+
+mod tilelist {
+    use super::*;
+    use tilelist::*;
+
+    #[test]
+    fn read() {
+        let list = "10/511/340\n  # a comment\n\n8 10/504/336 # expired\n0313131311\r\n10/5000/1\n8 0/0/0\n";
+        let mut rdr = TileListReader::new(list.as_bytes());
+
+        let tile = TileListEntry::Tile(Tile::new(10, 511, 340).unwrap());
+        assert_eq!(rdr.next().unwrap().unwrap(), tile);
+        assert_eq!(
+            rdr.next().unwrap().unwrap(),
+            TileListEntry::Metatile(Metatile::new(8, 10, 504, 336).unwrap())
+        );
+        assert_eq!(rdr.line_no(), 4);
+        assert_eq!(rdr.next().unwrap().unwrap(), tile);
+
+        let err = rdr.next().unwrap().unwrap_err();
+        assert_eq!(err.line(), 6);
+        assert_eq!(
+            err.to_string(),
+            "Invalid X or Y for this zoom at line 6: \"10/5000/1\""
+        );
+
+        assert_eq!(
+            rdr.next().unwrap().unwrap(),
+            TileListEntry::Metatile(Metatile::new(8, 0, 0, 0).unwrap())
+        );
+        assert!(rdr.next().is_none());
+        assert!(rdr.next().is_none());
+    }
+
+    #[test]
+    fn read_numbers_out_of_range() {
+        let list = "300 1/0/0
+1/9999999999/0
+40/0/0
+8 40/0/0
+8 1/0/9999999999
+2/1/1
+";
+        let results: Vec<_> = TileListReader::new(list.as_bytes()).collect();
+        assert_eq!(results.len(), 6);
+        for (i, result) in results[..5].iter().enumerate() {
+            assert_eq!(result.as_ref().unwrap_err().line(), i + 1);
+        }
+        assert_eq!(
+            results[5].as_ref().unwrap(),
+            &TileListEntry::Tile(Tile::new(2, 1, 1).unwrap())
+        );
+
+        assert!(parse_line("40/0/0").is_err());
+        assert!(parse_line("32/0/0").is_err());
+        assert!(parse_line("31/0/0").is_ok());
+        assert!(Tile::new(32, 0, 0).is_none());
+        assert!(Tile::new(99, 0, 0).is_none());
+        assert!(Metatile::new(8, 40, 0, 0).is_none());
+    }
+
+    #[test]
+    fn read_tab_separated_metatile() {
+        assert_eq!(
+            parse_line("8\t10/504/336\n").unwrap(),
+            Some(TileListEntry::Metatile(
+                Metatile::new(8, 10, 504, 336).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_line("  8 \t 10/504/336 # expired").unwrap(),
+            Some(TileListEntry::Metatile(
+                Metatile::new(8, 10, 504, 336).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let entries: Vec<TileListEntry> = vec![
+            Tile::new(0, 0, 0).unwrap().into(),
+            Tile::new(12, 2000, 1000).unwrap().into(),
+            Metatile::new(8, 12, 2000, 1000).unwrap().into(),
+        ];
+
+        let mut wtr = TileListWriter::new(Vec::new());
+        wtr.write_comment("expired\ntiles").unwrap();
+        for e in entries.iter() {
+            wtr.write_entry(e).unwrap();
+        }
+        let output = wtr.into_inner();
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "# expired tiles\n0/0/0\n12/2000/1000\n8 12/2000/1000\n"
+        );
+        let read: Vec<_> = TileListReader::new(&output[..]).ok().collect();
+        assert_eq!(read, entries);
+
+        let mut wtr = TileListWriter::new_quadkeys(Vec::new());
+        for e in entries.iter() {
+            wtr.write_entry(e).unwrap();
+        }
+        let output = wtr.into_inner();
+        let read: Vec<_> = TileListReader::new(&output[..]).ok().collect();
+        assert_eq!(read, entries);
+    }
+}
+
+mod binary {
+    use super::*;
+    use binary::*;
+
+    fn round_trip(tiles: Vec<Tile>) -> Vec<Tile> {
+        let bytes = write_tiles(Vec::new(), tiles).unwrap();
+        TileSetDecoder::new(&bytes[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn empty() {
+        let bytes = write_tiles(Vec::new(), vec![]).unwrap();
+        assert_eq!(bytes, b"SMTS\x01\x00\x00\x00");
+        assert_eq!(round_trip(vec![]), vec![]);
+    }
+
+    #[test]
+    fn simple() {
+        let tiles: Vec<Tile> = Tile::all_to_zoom(3).collect();
+        let bytes = write_tiles(Vec::new(), tiles.clone()).unwrap();
+        // header + 1 byte per tile + 2 bytes per zoom change
+        assert_eq!(bytes.len(), 8 + tiles.len() + 3 * 2);
+
+        let decoder = TileSetDecoder::new(&bytes[..]).unwrap();
+        assert_eq!(decoder.min_zoom(), 0);
+        assert_eq!(decoder.max_zoom(), 3);
+        assert_eq!(decoder.total(), 85);
+
+        let mut read: Vec<Tile> = decoder.collect::<Result<_, _>>().unwrap();
+        let mut expected = tiles;
+        read.sort_by_key(|t| (t.zoom(), t.x(), t.y()));
+        expected.sort_by_key(|t| (t.zoom(), t.x(), t.y()));
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn sparse() {
+        let tiles = vec![
+            Tile::new(18, 130_000, 87_000).unwrap(),
+            Tile::new(5, 31, 31).unwrap(),
+            Tile::new(18, 130_001, 87_000).unwrap(),
+            Tile::new(5, 31, 31).unwrap(),
+            Tile::new(31, 0, 2_147_483_647).unwrap(),
+        ];
+        assert_eq!(
+            round_trip(tiles),
+            vec![
+                Tile::new(5, 31, 31).unwrap(),
+                Tile::new(18, 130_000, 87_000).unwrap(),
+                Tile::new(18, 130_001, 87_000).unwrap(),
+                Tile::new(31, 0, 2_147_483_647).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn encoder_errors() {
+        let mut enc = TileSetEncoder::new(Vec::new(), 2, 3, 2).unwrap();
+        assert!(enc.push(&Tile::new(1, 0, 0).unwrap()).is_err());
+        enc.push(&Tile::new(3, 1, 1).unwrap()).unwrap();
+        assert!(enc.push(&Tile::new(3, 1, 1).unwrap()).is_err());
+        assert!(enc.push(&Tile::new(2, 1, 1).unwrap()).is_err());
+        assert!(enc.finish().is_err());
+    }
+
+    #[test]
+    fn decoder_errors() {
+        assert!(TileSetDecoder::new(&b"SMTX\x01\x00\x00\x00"[..]).is_err());
+        assert!(TileSetDecoder::new(&b"SMTS\x02\x00\x00\x00"[..]).is_err());
+
+        // Says there are 2 tiles, but only has one
+        let mut decoder = TileSetDecoder::new(&b"SMTS\x01\x01\x01\x02\x01"[..]).unwrap();
+        assert_eq!(
+            decoder.next().unwrap().unwrap(),
+            Tile::new(1, 0, 0).unwrap()
+        );
+        assert!(decoder.next().unwrap().is_err());
+        assert!(decoder.next().is_none());
+
+        // Tile outside the zoom
+        let mut decoder = TileSetDecoder::new(&b"SMTS\x01\x01\x01\x01\x05"[..]).unwrap();
+        assert!(decoder.next().unwrap().is_err());
+    }
+}
+
+mod tileset {
+    use super::*;
+    use std::collections::HashSet;
+    use tileset::*;
+
+    #[test]
+    fn insert_contains() {
+        let mut set = TileSet::new();
+        assert!(set.is_empty());
+        let t = Tile::new(5, 10, 12).unwrap();
+        assert!(set.insert(&t));
+        assert!(!set.insert(&t));
+        assert!(!set.insert(&Tile::new(8, 80, 96).unwrap()));
+
+        assert!(set.contains(&t));
+        assert!(set.contains(&Tile::new(8, 80, 96).unwrap()));
+        assert!(!set.contains(&t.parent().unwrap()));
+        assert!(set.intersects(&t.parent().unwrap()));
+        assert!(!set.intersects(&Tile::new(5, 11, 12).unwrap()));
+        assert_eq!(set.len(), 1);
+
+        for sibling in t.parent().unwrap().subtiles().unwrap().iter() {
+            set.insert(sibling);
+        }
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![t.parent().unwrap()]);
+
+        set.remove(&Tile::new(6, 20, 24).unwrap());
+        assert_eq!(set.len(), 6);
+        assert_eq!(set.count_at_zoom(6), 15);
+        assert!(!set.contains(&t));
+        assert!(set.intersects(&t));
+    }
+
+    #[test]
+    fn iter_zoom() {
+        let set: TileSet = vec![Tile::new(1, 1, 1).unwrap(), Tile::new(3, 0, 0).unwrap()]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            vec![Tile::new(3, 0, 0).unwrap(), Tile::new(1, 1, 1).unwrap()]
+        );
+
+        assert_eq!(
+            set.iter_zoom(2).collect::<Vec<_>>(),
+            vec![
+                Tile::new(2, 2, 2).unwrap(),
+                Tile::new(2, 3, 2).unwrap(),
+                Tile::new(2, 2, 3).unwrap(),
+                Tile::new(2, 3, 3).unwrap(),
+            ]
+        );
+        assert_eq!(
+            set.iter_zoom_intersecting(2).collect::<Vec<_>>(),
+            vec![
+                Tile::new(2, 0, 0).unwrap(),
+                Tile::new(2, 2, 2).unwrap(),
+                Tile::new(2, 3, 2).unwrap(),
+                Tile::new(2, 2, 3).unwrap(),
+                Tile::new(2, 3, 3).unwrap(),
+            ]
+        );
+        assert_eq!(set.iter_zoom(0).count(), 0);
+        assert_eq!(set.iter_zoom_intersecting(0).count(), 1);
+
+        assert_eq!(set.iter_zoom(8).count() as u64, set.count_at_zoom(8));
+        assert_eq!(set.count_at_zoom(8), 16384 + 1024);
+        assert_eq!(TileSet::full().count_at_zoom(31), 1 << 62);
+        assert_eq!(TileSet::full().count_at_zoom(32), 0);
+        assert_eq!(TileSet::full().count_at_zoom(255), 0);
+    }
+
+    #[test]
+    fn set_operations() {
+        let a: TileSet = Tile::all_to_zoom(3)
+            .filter(|t| t.zoom() == 3 && t.x() < 5)
+            .collect();
+        let b: TileSet = Tile::all_to_zoom(3)
+            .filter(|t| t.zoom() == 3 && t.y() >= 3)
+            .collect();
+
+        let to_hashset =
+            |s: &TileSet| -> HashSet<Tile> { s.iter_zoom(3).collect::<HashSet<Tile>>() };
+        let ha = to_hashset(&a);
+        let hb = to_hashset(&b);
+        assert_eq!(ha.len(), 40);
+        assert_eq!(hb.len(), 40);
+
+        assert_eq!(to_hashset(&a.union(&b)), &ha | &hb);
+        assert_eq!(to_hashset(&a.intersection(&b)), &ha & &hb);
+        assert_eq!(to_hashset(&a.difference(&b)), &ha - &hb);
+        assert_eq!(to_hashset(&b.difference(&a)), &hb - &ha);
+
+        assert_eq!(a.union(&b).count_at_zoom(3), 55);
+        assert_eq!(a.difference(&a), TileSet::new());
+        assert_eq!(a.union(&TileSet::full()), TileSet::full());
+        assert_eq!(a.intersection(&TileSet::full()), a);
+    }
+}
+
+mod tilerange {
+    use super::*;
+
+    #[test]
+    fn create() {
+        assert!(TileRange::new(2, 0, 3, 1, 2).is_some());
+        assert!(TileRange::new(2, 0, 4, 1, 2).is_none());
+        assert!(TileRange::new(2, 2, 1, 1, 2).is_none());
+        assert!(TileRange::new(32, 0, 0, 0, 0).is_none());
+
+        let world = TileRange::whole_world(31).unwrap();
+        assert_eq!(world.count(), 1 << 62);
+        assert_eq!(
+            TileRange::from_tile(&Tile::new(4, 3, 2).unwrap()).to_string(),
+            "4/3-3/2-2"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let r: TileRange = "10/500-510/300-301".parse().unwrap();
+        assert_eq!(r, TileRange::new(10, 500, 510, 300, 301).unwrap());
+        assert_eq!(r.to_string(), "10/500-510/300-301");
+        assert_eq!(r.width(), 11);
+        assert_eq!(r.height(), 2);
+
+        assert_eq!(
+            "3/2/1-4".parse(),
+            Ok(TileRange::new(3, 2, 2, 1, 4).unwrap())
+        );
+        assert!("3/2-1/1-4".parse::<TileRange>().is_err());
+        assert!("3/2-8/1-4".parse::<TileRange>().is_err());
+        assert!("3/2-x/1-4".parse::<TileRange>().is_err());
+        assert!("3/1-9999999999/1-4".parse::<TileRange>().is_err());
+    }
+
+    #[test]
+    fn bbox() {
+        let ie_bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+        let r = TileRange::from_bbox(&ie_bbox, 6);
+        assert_eq!(r, TileRange::new(6, 29, 31, 20, 21).unwrap());
+        assert_eq!(ie_bbox.tile_range(6), r);
+        let tiles: Vec<Tile> = ie_bbox.tiles_for_zoom(6).collect();
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles, r.iter().collect::<Vec<_>>());
+
+        let back = r.to_bbox();
+        assert_eq!(back.nw_corner(), Tile::new(6, 29, 20).unwrap().nw_corner());
+        assert_eq!(back.se_corner(), Tile::new(6, 31, 21).unwrap().se_corner());
+
+        let world = BBox::new(90., -180., -90., 180.).unwrap();
+        assert_eq!(
+            TileRange::from_bbox(&world, 3),
+            TileRange::whole_world(3).unwrap()
+        );
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = TileRange::new(5, 0, 10, 0, 10).unwrap();
+        let b = TileRange::new(5, 5, 20, 8, 9).unwrap();
+        assert_eq!(a.intersection(&b), TileRange::new(5, 5, 10, 8, 9));
+        assert_eq!(a.union_bounds(&b), TileRange::new(5, 0, 20, 0, 10));
+        assert!(a.contains(&Tile::new(5, 10, 10).unwrap()));
+        assert!(!a.contains(&Tile::new(5, 11, 10).unwrap()));
+        assert!(!a.contains(&Tile::new(6, 10, 10).unwrap()));
+        assert!(a.contains_range(&a.intersection(&b).unwrap()));
+        assert!(!a.contains_range(&b));
+
+        let c = TileRange::new(5, 11, 20, 0, 10).unwrap();
+        assert_eq!(a.intersection(&c), None);
+        assert_eq!(a.intersection(&a.child_range().unwrap()), None);
+    }
+
+    #[test]
+    fn zooms() {
+        let r = TileRange::new(3, 1, 2, 5, 5).unwrap();
+        assert_eq!(r.child_range(), TileRange::new(4, 2, 5, 10, 11));
+        assert_eq!(r.parent_range(), TileRange::new(2, 0, 1, 2, 2));
+        assert_eq!(r.at_zoom(0), TileRange::whole_world(0));
+        assert_eq!(r.at_zoom(6), TileRange::new(6, 8, 23, 40, 47));
+        assert_eq!(r.at_zoom(32), None);
+        assert_eq!(TileRange::whole_world(0).unwrap().parent_range(), None);
+        assert_eq!(TileRange::whole_world(31).unwrap().child_range(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let r = TileRange::new(3, 1, 2, 5, 6).unwrap();
+        let mut it = r.iter();
+        assert_eq!(it.len(), 4);
+        assert_eq!(it.next(), Tile::new(3, 1, 5));
+        assert_eq!(it.next(), Tile::new(3, 1, 6));
+        assert_eq!(it.len(), 2);
+        assert_eq!(it.next(), Tile::new(3, 2, 5));
+        assert_eq!(it.next(), Tile::new(3, 2, 6));
+        assert_eq!(it.len(), 0);
+        assert_eq!(it.next(), None);
+    }
+}
+
+mod hilbert {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn round_trip() {
+        for zoom in 0..6 {
+            let n = 1u64 << (2 * zoom);
+            let mut prev: Option<(u32, u32)> = None;
+            for i in 0..n {
+                let (x, y) = hilbert_to_xy(i, zoom);
+                assert_eq!(xy_to_hilbert(x, y, zoom), i);
+                // Each tile is beside the previous one
+                if let Some((px, py)) = prev {
+                    assert_eq!(
+                        (px as i64 - x as i64).abs() + (py as i64 - y as i64).abs(),
+                        1
+                    );
+                }
+                prev = Some((x, y));
+            }
+        }
+
+        let max = (1u32 << 31) - 1;
+        assert_eq!(
+            hilbert_to_xy(xy_to_hilbert(max, 12345, 31), 31),
+            (max, 12345)
+        );
+    }
+
+    #[test]
+    fn tile_id() {
+        // Same values as the PMTiles spec tests
+        let known = [
+            ((0, 0, 0), 0),
+            ((1, 0, 0), 1),
+            ((1, 0, 1), 2),
+            ((1, 1, 1), 3),
+            ((1, 1, 0), 4),
+            ((2, 0, 0), 5),
+            ((3, 7, 7), 63),
+            ((3, 7, 0), 84),
+            ((12, 3423, 1763), 19078479),
+        ];
+        for &((z, x, y), id) in known.iter() {
+            let t = Tile::new(z, x, y).unwrap();
+            assert_eq!(t.tile_id(), id);
+            assert_eq!(Tile::from_tile_id(id), Some(t));
+        }
+
+        for (i, t) in Tile::all_to_zoom(4).enumerate() {
+            assert_eq!(Tile::from_tile_id(t.tile_id()), Some(t));
+            assert!(t.tile_id() < 341, "{} {:?}", i, t);
+        }
+
+        let last = Tile::new(31, (1 << 31) - 1, 0).unwrap();
+        assert_eq!(Tile::from_tile_id(last.tile_id()), Some(last));
+        assert_eq!(Tile::from_tile_id(last.tile_id() + 1), None);
+    }
+
+    #[test]
+    fn all_tiles() {
+        let tiles: Vec<Tile> = all_tiles_hilbert().take(9).collect();
+        assert_eq!(
+            tiles,
+            vec![
+                Tile::new(0, 0, 0).unwrap(),
+                Tile::new(1, 0, 0).unwrap(),
+                Tile::new(1, 0, 1).unwrap(),
+                Tile::new(1, 1, 1).unwrap(),
+                Tile::new(1, 1, 0).unwrap(),
+                Tile::new(2, 0, 0).unwrap(),
+                Tile::new(2, 1, 0).unwrap(),
+                Tile::new(2, 1, 1).unwrap(),
+                Tile::new(2, 0, 1).unwrap(),
+            ]
+        );
+        let ids: Vec<u64> = all_tiles_hilbert().take(400).map(|t| t.tile_id()).collect();
+        assert_eq!(ids, (0..400).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn tile_range() {
+        let r = TileRange::new(4, 3, 9, 2, 2).unwrap();
+        let tiles: Vec<Tile> = r.iter_hilbert().collect();
+        assert_eq!(tiles.len(), 7);
+        assert_eq!(
+            tiles.iter().cloned().collect::<HashSet<_>>(),
+            r.iter().collect::<HashSet<_>>()
+        );
+        assert!(tiles
+            .windows(2)
+            .all(|w| w[0].hilbert_index() < w[1].hilbert_index()));
+
+        assert_eq!(r.next_hilbert_index(0), Some(xy_to_hilbert(3, 2, 4)));
+        assert_eq!(r.next_hilbert_index(256), None);
+
+        let world = TileRange::whole_world(3).unwrap();
+        let mut it = world.iter_hilbert();
+        assert_eq!(it.len(), 64);
+        assert_eq!(it.next(), Tile::new(3, 0, 0));
+        assert_eq!(it.len(), 63);
+        assert_eq!(it.last(), Tile::new(3, 7, 0));
+    }
+
+    #[test]
+    fn metatiles() {
+        let ie_bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+        let zorder: HashSet<Metatile> =
+            MetatilesIterator::new_for_bbox_zoom(8, &Some(ie_bbox.clone()), 0, 12)
+                .filter(|mt| {
+                    ie_bbox.overlaps_bbox(&BBox::new_from_points(&mt.nw_corner(), &mt.se_corner()))
+                })
+                .collect();
+        let hilbert: Vec<Metatile> =
+            MetatilesIterator::new_for_bbox_zoom(8, &Some(ie_bbox.clone()), 0, 12)
+                .with_order(TileOrder::Hilbert)
+                .collect();
+        assert_eq!(hilbert.iter().cloned().collect::<HashSet<_>>(), zorder);
+        assert_eq!(hilbert.len(), zorder.len());
+
+        let all: Vec<Metatile> = Metatile::all(8)
+            .with_order(TileOrder::Hilbert)
+            .take_while(|mt| mt.zoom() <= 4)
+            .collect();
+        assert_eq!(all.len(), 4 + 4);
+        assert_eq!(all[4], Metatile::new(8, 4, 0, 0).unwrap());
+        assert_eq!(all[5], Metatile::new(8, 4, 0, 8).unwrap());
+        assert_eq!(all[6], Metatile::new(8, 4, 8, 8).unwrap());
+        assert_eq!(all[7], Metatile::new(8, 4, 8, 0).unwrap());
+    }
+}
+
+mod zorder {
+    use super::*;
+
+    /// The original, bit-by-bit, implementation, to compare against
+    fn reference_xy_to_zorder(x: u32, y: u32) -> u64 {
+        let mut res: u64 = 0;
+        for i in 0..32 {
+            if (x >> i) & 1 == 1 {
+                res |= 1 << (i * 2);
+            }
+            if (y >> i) & 1 == 1 {
+                res |= 1 << ((i * 2) + 1);
+            }
+        }
+        res
+    }
+
+    fn reference_zorder_to_xy(zorder: u64) -> (u32, u32) {
+        let mut x: u32 = 0;
+        let mut y: u32 = 0;
+        for i in 0..32 {
+            if (zorder >> (i * 2)) & 1 == 1 {
+                x |= 1 << i;
+            }
+            if (zorder >> ((i * 2) + 1)) & 1 == 1 {
+                y |= 1 << i;
+            }
+        }
+        (x, y)
+    }
+
+    /// Interesting bit patterns, every single bit, and all bits up to a point
+    fn patterns() -> Vec<u32> {
+        let mut values = vec![
+            0,
+            u32::MAX,
+            0x5555_5555,
+            0xAAAA_AAAA,
+            0x0F0F_0F0F,
+            0xF0F0_F0F0,
+        ];
+        for i in 0..32 {
+            values.push(1 << i);
+            values.push(u32::MAX >> i);
+            values.push(!(1 << i));
+        }
+        values
+    }
+
+    fn check_xy(x: u32, y: u32) {
+        let expected = reference_xy_to_zorder(x, y);
+        assert_eq!(xy_to_zorder(x, y), expected);
+        assert_eq!(xy_to_zorder_magic(x, y), expected);
+        #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+        assert_eq!(xy_to_zorder_bmi2(x, y), expected);
+    }
+
+    fn check_zorder(zorder: u64) {
+        let expected = reference_zorder_to_xy(zorder);
+        assert_eq!(zorder_to_xy(zorder), expected);
+        assert_eq!(zorder_to_xy_magic(zorder), expected);
+        #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+        assert_eq!(zorder_to_xy_bmi2(zorder), expected);
+    }
+
+    #[test]
+    fn exhaustive_low_zooms() {
+        // Every tile up to zoom 10
+        for x in 0..1024 {
+            for y in 0..1024 {
+                check_xy(x, y);
+            }
+        }
+        for zorder in 0..(1 << 20) {
+            check_zorder(zorder);
+        }
+    }
+
+    #[test]
+    fn bit_patterns() {
+        let patterns = patterns();
+        for &x in patterns.iter() {
+            for &y in patterns.iter() {
+                check_xy(x, y);
+                check_zorder(((x as u64) << 32) | y as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn pseudo_random() {
+        // xorshift, so this is repeatable without a dependency
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        for _ in 0..1_000_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            check_xy(state as u32, (state >> 32) as u32);
+            check_zorder(state);
+        }
+    }
+}
+
+mod zorder_ranges {
+    use super::*;
+
+    /// All the z-orders in this range, worked out the slow way
+    fn zorders(range: &TileRange) -> Vec<u64> {
+        let mut zorders: Vec<u64> = range.iter().map(|t| xy_to_zorder(t.x(), t.y())).collect();
+        zorders.sort_unstable();
+        zorders
+    }
+
+    fn expand(ranges: &[(u64, u64)]) -> Vec<u64> {
+        ranges.iter().flat_map(|&(a, b)| a..=b).collect()
+    }
+
+    fn all_ranges(zoom: u8) -> Vec<TileRange> {
+        let max = (1u32 << zoom) - 1;
+        let mut result = Vec::new();
+        for x1 in 0..=max {
+            for x2 in x1..=max {
+                for y1 in 0..=max {
+                    for y2 in y1..=max {
+                        result.push(TileRange::new(zoom, x1, x2, y1, y2).unwrap());
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn exact() {
+        for zoom in 0..4 {
+            for range in all_ranges(zoom) {
+                let ranges = range.zorder_ranges();
+                assert_eq!(expand(&ranges), zorders(&range), "{}", range);
+                // Sorted, and adjacent intervals are merged, so it's minimal
+                for w in ranges.windows(2) {
+                    assert!(w[0].1 + 1 < w[1].0, "{} {:?}", range, ranges);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn whole_world() {
+        assert_eq!(
+            TileRange::whole_world(0).unwrap().zorder_ranges(),
+            vec![(0, 0)]
+        );
+        assert_eq!(
+            TileRange::whole_world(31).unwrap().zorder_ranges(),
+            vec![(0, (1u64 << 62) - 1)]
+        );
+        let range = TileRange::new(20, 0, (1 << 19) - 1, 0, (1 << 20) - 1).unwrap();
+        // The left half of the world is 2 intervals, top left & bottom left quadrants
+        assert_eq!(
+            range.zorder_ranges(),
+            vec![(0, (1 << 38) - 1), (2 << 38, (3 << 38) - 1)]
+        );
+    }
+
+    #[test]
+    fn bounded() {
+        for range in all_ranges(3) {
+            let exact = range.zorder_ranges();
+            for max in 1..6 {
+                let bounded = range.zorder_ranges_bounded(max);
+                assert!(bounded.len() <= max);
+                if exact.len() <= max {
+                    assert_eq!(bounded, exact);
+                }
+                assert_eq!(bounded.first().unwrap().0, exact.first().unwrap().0);
+                assert_eq!(bounded.last().unwrap().1, exact.last().unwrap().1);
+                // Every z-order in the range is in one of the intervals
+                for z in expand(&exact) {
+                    assert!(bounded.iter().any(|&(a, b)| a <= z && z <= b));
+                }
+            }
+        }
+
+        let range = TileRange::new(3, 1, 6, 1, 6).unwrap();
+        assert_eq!(range.zorder_ranges_bounded(1), vec![(3, 60)]);
+
+        // A one tile wide strip at zoom 31 would need about 2^31 intervals
+        let range = TileRange::new(31, 1000, 1000, 0, (1 << 31) - 1).unwrap();
+        let bounded = range.zorder_ranges_bounded(10);
+        assert!(!bounded.is_empty() && bounded.len() <= 10);
+        assert_eq!(bounded.first().unwrap().0, range.min_zorder());
+        assert_eq!(bounded.last().unwrap().1, range.max_zorder());
+        assert!(bounded.windows(2).all(|w| w[0].1 < w[1].0));
+        for y in [0, 1, 12345, (1 << 31) - 1] {
+            let z = xy_to_zorder(1000, y);
+            assert!(bounded.iter().any(|&(a, b)| a <= z && z <= b));
+        }
+    }
+
+    #[test]
+    fn bigmin_litmax() {
+        for range in all_ranges(3) {
+            let inside = zorders(&range);
+            let (min, max) = (range.min_zorder(), range.max_zorder());
+            for z in min..=max {
+                if inside.contains(&z) {
+                    assert!(range.contains_zorder(z));
+                    continue;
+                }
+                assert!(!range.contains_zorder(z));
+                let next = inside.iter().cloned().find(|&i| i > z).unwrap();
+                let prev = inside.iter().cloned().rev().find(|&i| i < z).unwrap();
+                assert_eq!(zorder_bigmin(z, min, max), next, "{} {}", range, z);
+                assert_eq!(zorder_litmax(z, min, max), prev, "{} {}", range, z);
+            }
+        }
+    }
+
+    #[test]
+    fn next_zorder_index() {
+        let range = TileRange::new(2, 1, 2, 0, 1).unwrap();
+        let next: Vec<_> = (0..17).map(|z| range.next_zorder_index(z)).collect();
+        assert_eq!(
+            next,
+            vec![
+                Some(1),
+                Some(1),
+                Some(3),
+                Some(3),
+                Some(4),
+                Some(6),
+                Some(6),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+    }
+}
+
+mod packed_id {
+    use super::*;
+
+    /// All the tiles up to `max_zoom`, depth first
+    fn depth_first(tile: Tile, max_zoom: u8, tiles: &mut Vec<Tile>) {
+        tiles.push(tile);
+        if tile.zoom() < max_zoom {
+            for t in tile.subtiles().unwrap().iter() {
+                depth_first(*t, max_zoom, tiles);
+            }
+        }
+    }
+
+    #[test]
+    fn order() {
+        let mut tiles = Vec::new();
+        depth_first(Tile::new(0, 0, 0).unwrap(), 5, &mut tiles);
+        for w in tiles.windows(2) {
+            assert!(w[0].packed_id() < w[1].packed_id(), "{:?}", w);
+            assert!(w[0] < w[1]);
+        }
+        for t in tiles.iter() {
+            assert_eq!(Tile::from_packed_id(t.packed_id()), Some(*t));
+        }
+
+        let mut sorted = tiles.clone();
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(sorted, tiles);
+    }
+
+    #[test]
+    fn edges() {
+        let max = (1u32 << 31) - 1;
+        // There are (4^32 - 1)/3 tiles from zoom 0 to 31
+        let last_id = u64::MAX / 3 - 1;
+        let last = Tile::new(31, max, max).unwrap();
+        assert_eq!(last.packed_id(), last_id);
+        assert_eq!(Tile::from_packed_id(last_id), Some(last));
+        assert_eq!(Tile::from_packed_id(last_id + 1), None);
+        assert_eq!(Tile::from_packed_id(u64::MAX), None);
+
+        assert_eq!(Tile::new(31, 0, 0).unwrap().packed_id(), 31);
+        assert_eq!(Tile::from_packed_id(32), Tile::new(31, 1, 0));
+        for &(x, y) in [(max, 0), (0, max), (12345, 67890), (max - 1, max)].iter() {
+            let t = Tile::new(31, x, y).unwrap();
+            assert_eq!(Tile::from_packed_id(t.packed_id()), Some(t));
+        }
+    }
+
+    #[test]
+    fn descendant_id_range() {
+        let mut tiles = Vec::new();
+        depth_first(Tile::new(0, 0, 0).unwrap(), 4, &mut tiles);
+        for t in tiles.iter() {
+            for max_zoom in 0..6 {
+                let range = t.descendant_id_range(max_zoom);
+                for other in tiles.iter() {
+                    let is_descendant = other.zoom() >= t.zoom()
+                        && (other.x() >> (other.zoom() - t.zoom())) == t.x()
+                        && (other.y() >> (other.zoom() - t.zoom())) == t.y();
+                    let in_range = range.contains(&other.packed_id());
+                    if other.zoom() <= max_zoom.max(t.zoom()) {
+                        assert_eq!(in_range, is_descendant, "{:?} {} {:?}", t, max_zoom, other);
+                    } else {
+                        // Deeper tiles in the range are still descendants
+                        assert!(!in_range || is_descendant, "{:?} {:?}", t, other);
+                    }
+                }
+            }
+        }
+
+        let root = Tile::new(0, 0, 0).unwrap();
+        assert_eq!(root.descendant_id_range(0), 0..=0);
+        assert_eq!(
+            root.descendant_id_range(1),
+            0..=Tile::new(1, 1, 1).unwrap().packed_id()
+        );
+        assert_eq!(root.descendant_id_range(40), root.descendant_id_range(31));
+    }
+}
+
+mod tilemap {
+    use super::*;
+    use tilemap::*;
+
+    fn t(s: &str) -> Tile {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn basic() {
+        let mut map = TileMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert(t("3/1/2"), 1), None);
+        assert_eq!(map.insert(t("3/1/2"), 2), Some(1));
+        map.insert(t("0/0/0"), 0);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&t("3/1/2")), Some(&2));
+        *map.get_mut(&t("0/0/0")).unwrap() += 10;
+        assert_eq!(map.get(&t("0/0/0")), Some(&10));
+        assert!(!map.contains_key(&t("3/2/1")));
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(t("0/0/0"), &10), (t("3/1/2"), &2)]
+        );
+        assert_eq!(map.remove(&t("0/0/0")), Some(10));
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![(t("3/1/2"), 2)]);
+    }
+
+    #[test]
+    fn nearest_ancestor() {
+        let map: TileMap<u8> = vec![(t("2/1/1"), 2), (t("5/8/8"), 5)].into_iter().collect();
+        assert_eq!(map.nearest_ancestor(&t("2/1/1")), Some((t("2/1/1"), &2)));
+        assert_eq!(map.nearest_ancestor(&t("4/4/4")), Some((t("2/1/1"), &2)));
+        assert_eq!(
+            map.nearest_ancestor(&t("18/65536/65600")),
+            Some((t("5/8/8"), &5))
+        );
+        assert_eq!(map.nearest_ancestor(&t("1/0/0")), None);
+        assert_eq!(map.nearest_ancestor(&t("3/0/0")), None);
+    }
+
+    #[test]
+    fn descendants() {
+        let tiles = vec![
+            t("0/0/0"),
+            t("1/0/0"),
+            t("1/1/1"),
+            t("2/0/0"),
+            t("2/1/1"),
+            t("2/2/0"),
+            t("31/0/0"),
+            t("20/524287/524287"),
+            t("20/524288/0"),
+        ];
+        let map: TileMap<()> = tiles.iter().map(|&t| (t, ())).collect();
+
+        let desc = |s: &str| map.descendants(&t(s)).map(|(t, _)| t).collect::<Vec<_>>();
+        assert_eq!(desc("0/0/0").len(), tiles.len());
+        assert_eq!(
+            desc("1/0/0"),
+            vec![
+                t("1/0/0"),
+                t("2/0/0"),
+                t("31/0/0"),
+                t("2/1/1"),
+                t("20/524287/524287")
+            ]
+        );
+        assert_eq!(desc("1/1/0"), vec![t("2/2/0"), t("20/524288/0")]);
+        assert_eq!(desc("2/3/3"), vec![]);
+    }
+
+    #[test]
+    fn in_bbox() {
+        let mut map = TileMap::new();
+        for tile in ::all_tiles().take_while(|t| t.zoom() <= 5) {
+            map.insert(tile, tile.zoom());
+        }
+
+        let bbox = BBox::new(55.5, -10.7, 51.4, -5.3).unwrap();
+        for zoom in 0..7 {
+            let mut expected: Vec<Tile> = bbox.tiles_for_zoom(zoom).collect();
+            if zoom > 5 {
+                expected.clear();
+            }
+            expected.sort();
+            let found: Vec<Tile> = map.in_bbox(&bbox, zoom).map(|(t, _)| t).collect();
+            assert_eq!(found, expected);
+        }
+
+        let range = TileRange::new(3, 2, 5, 1, 6).unwrap();
+        assert_eq!(map.in_tile_range(&range).count(), 24);
+        assert!(map
+            .in_tile_range(&range)
+            .all(|(t, &z)| z == 3 && range.contains(&t)));
+    }
+
+    #[test]
+    fn in_tile_range_skips_descendants() {
+        let mut map = TileMap::new();
+        let tile = Tile::new(2, 1, 1).unwrap();
+        map.insert(tile, 0);
+        map.insert(Tile::new(1, 1, 0).unwrap(), 0);
+        for x in 0..64 {
+            map.insert(Tile::new(12, 1024 + x, 1024 + x).unwrap(), 1);
+            map.insert(Tile::new(12, 2048 + x, 1024).unwrap(), 1);
+        }
+        map.insert(Tile::new(2, 2, 0).unwrap(), 0);
+
+        let range = TileRange::new(2, 1, 2, 0, 1).unwrap();
+        let found: Vec<Tile> = map.in_tile_range(&range).map(|(t, _)| t).collect();
+        assert_eq!(found, vec![tile, Tile::new(2, 2, 0).unwrap()]);
+
+        let range = TileRange::new(12, 1024, 2111, 1024, 1024).unwrap();
+        assert_eq!(map.in_tile_range(&range).count(), 65);
+    }
+}
+
+mod pyramid_index {
+    use super::*;
+
+    #[test]
+    fn matches_all_tiles() {
+        for (i, tile) in ::all_tiles().take(100_000).enumerate() {
+            assert_eq!(tile.pyramid_index(), i as u64);
+            assert_eq!(Tile::from_pyramid_index(i as u64), Some(tile));
+        }
+    }
+
+    #[test]
+    fn edges() {
+        let max = (1u32 << 31) - 1;
+        let last = Tile::new(31, max, max).unwrap();
+        assert_eq!(last.pyramid_index(), u64::MAX / 3 - 1);
+        assert_eq!(Tile::from_pyramid_index(u64::MAX / 3 - 1), Some(last));
+        assert_eq!(Tile::from_pyramid_index(u64::MAX / 3), None);
+        assert_eq!(Tile::from_pyramid_index(u64::MAX), None);
+
+        // First & last tile of each zoom
+        for zoom in 0..32u8 {
+            let max = ((1u64 << zoom) - 1) as u32;
+            for &(x, y) in [(0, 0), (max, max), (max, 0), (0, max)].iter() {
+                let t = Tile::new(zoom, x, y).unwrap();
+                assert_eq!(Tile::from_pyramid_index(t.pyramid_index()), Some(t));
+            }
+        }
+    }
+
+    #[test]
+    fn all_tiles_nth() {
+        let mut it = ::all_tiles();
+        assert_eq!(it.nth(5), Tile::new(2, 0, 0));
+        assert_eq!(it.next_index(), 6);
+        assert_eq!(it.next(), Tile::new(2, 1, 0));
+        assert_eq!(it.next(), Tile::new(2, 0, 1));
+
+        let t = Tile::new(18, 131_000, 87_000).unwrap();
+        let mut it = ::all_tiles().skip(t.pyramid_index() as usize);
+        assert_eq!(it.next(), Some(t));
+
+        let mut it = ::all_tiles();
+        assert_eq!(it.len() as u64, u64::MAX / 3);
+        assert_eq!(
+            it.nth((u64::MAX / 3 - 2) as usize),
+            Tile::new(31, (1 << 31) - 2, (1 << 31) - 1)
+        );
+        assert_eq!(it.len(), 1);
+        assert_eq!(it.next(), Tile::new(31, (1 << 31) - 1, (1 << 31) - 1));
+        assert_eq!(it.len(), 0);
+        assert_eq!(it.next(), None);
+
+        let mut it = ::all_tiles();
+        assert_eq!(it.nth(usize::MAX), None);
+        assert_eq!(it.next(), None);
+
+        // The Hilbert version is in tile_id order
+        let mut it = all_tiles_hilbert();
+        let t = Tile::new(7, 100, 3).unwrap();
+        assert_eq!(it.nth(t.tile_id() as usize), Some(t));
+    }
+
+    #[test]
+    fn all_to_zoom_nth() {
+        let tiles: Vec<Tile> = Tile::all_to_zoom(4).collect();
+        for n in 0..tiles.len() + 2 {
+            let mut it = Tile::all_to_zoom(4);
+            assert_eq!(it.nth(n), tiles.get(n).cloned());
+            assert_eq!(it.len(), tiles.len().saturating_sub(n + 1));
+            assert_eq!(
+                it.collect::<Vec<_>>(),
+                tiles.get(n + 1..).unwrap_or(&[]).to_vec()
+            );
+        }
+
+        let mut it = Tile::all_to_zoom(4);
+        it.next();
+        it.next();
+        assert_eq!(it.nth(10), tiles.get(12).cloned());
+        assert_eq!(
+            Tile::all_to_zoom(4).skip(100).collect::<Vec<_>>(),
+            tiles[100..].to_vec()
+        );
+
+        let mut it = Tile::all_to_zoom(4);
+        it.next();
+        it.next();
+        assert_eq!(it.nth(usize::MAX), None);
+        assert_eq!(it.next(), None);
+        assert_eq!(it.len(), 0);
+    }
+
+    #[test]
+    fn matches_all_to_zoom() {
+        for (i, tile) in Tile::all_to_zoom(6).enumerate() {
+            assert_eq!(tile.column_pyramid_index(), i as u64);
+            assert_eq!(Tile::from_column_pyramid_index(i as u64), Some(tile));
+        }
+
+        let tile = Tile::new(2, 1, 2).unwrap();
+        assert_eq!(tile.pyramid_index(), 14);
+        assert_eq!(tile.column_pyramid_index(), 11);
+        assert_eq!(::all_tiles().nth(14), Some(tile));
+        assert_eq!(Tile::all_to_zoom(3).nth(11), Some(tile));
+
+        let max = (1u32 << 31) - 1;
+        let last = Tile::new(31, max, max).unwrap();
+        assert_eq!(last.column_pyramid_index(), u64::MAX / 3 - 1);
+        assert_eq!(
+            Tile::from_column_pyramid_index(u64::MAX / 3 - 1),
+            Some(last)
+        );
+        assert_eq!(Tile::from_column_pyramid_index(u64::MAX / 3), None);
+    }
+
+    #[test]
+    fn all_to_zoom_past_zoom_31() {
+        let mut it = Tile::all_to_zoom(40);
+        assert_eq!(it.len() as u64, u64::MAX / 3);
+        assert_eq!(it.total(), u64::MAX / 3);
+        let max = (1u32 << 31) - 1;
+        assert_eq!(it.nth((u64::MAX / 3 - 1) as usize), Tile::new(31, max, max));
+        assert_eq!(it.len(), 0);
+        assert_eq!(it.next(), None);
+    }
+}
+
+mod shard {
+    use super::*;
+    use shard::*;
+    use std::collections::HashSet;
+
+    fn check_partition(shards: &[Shard], expected: &[Metatile], num_shards: usize) {
+        assert_eq!(shards.len(), num_shards.min(expected.len()));
+        let all: Vec<Metatile> = shards.iter().flat_map(|s| s.iter()).collect();
+        assert_eq!(all.len(), expected.len());
+        let all_set: HashSet<Metatile> = all.iter().cloned().collect();
+        let expected_set: HashSet<Metatile> = expected.iter().cloned().collect();
+        assert_eq!(all_set, expected_set);
+
+        let (min, max) = (
+            expected.len() / num_shards,
+            expected.len().div_ceil(num_shards),
+        );
+        for s in shards.iter() {
+            let mts: Vec<Metatile> = s.iter().collect();
+            assert_eq!(mts.len() as u64, s.len());
+            assert!(mts.len() >= min && mts.len() <= max, "{}", mts.len());
+            for mt in expected.iter() {
+                assert_eq!(s.contains(mt), mts.contains(mt));
+            }
+
+            let s2: Shard = s.to_string().parse().unwrap();
+            assert_eq!(s2.iter().collect::<Vec<_>>(), mts);
+        }
+    }
+
+    #[test]
+    fn bbox() {
+        let bbox = BBox::new(55.5, -10.7, 51.4, -5.3).unwrap();
+        for &order in [TileOrder::ZOrder, TileOrder::Hilbert].iter() {
+            for &scale in [1, 8].iter() {
+                let expected: Vec<Metatile> = (0..=10)
+                    .flat_map(|z| {
+                        let range = bbox.tile_range(z);
+                        range
+                            .iter()
+                            .map(|t| t.metatile(scale).unwrap())
+                            .collect::<HashSet<_>>()
+                    })
+                    .collect();
+                for &n in [1, 3, 7, 100, 100_000].iter() {
+                    let shards = partition_bbox(scale, Some(&bbox), 0, 10, order, n);
+                    check_partition(&shards, &expected, n);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn whole_world() {
+        let shards = partition_bbox(1, None, 2, 4, TileOrder::Hilbert, 5);
+        let expected: Vec<Metatile> = ::all_tiles()
+            .skip_while(|t| t.zoom() < 2)
+            .take_while(|t| t.zoom() <= 4)
+            .map(|t| t.metatile(1).unwrap())
+            .collect();
+        check_partition(&shards, &expected, 5);
+        assert_eq!(shards[0].zooms(), (2, 3));
+        assert_eq!(shards[4].zooms(), (4, 4));
+        // Hilbert shards at one zoom are a compact area
+        let tiles: Vec<Tile> = shards[4].tiles().collect();
+        let width = tiles.iter().map(|t| t.x()).max().unwrap()
+            - tiles.iter().map(|t| t.x()).min().unwrap()
+            + 1;
+        let height = tiles.iter().map(|t| t.y()).max().unwrap()
+            - tiles.iter().map(|t| t.y()).min().unwrap()
+            + 1;
+        assert!((width * height) as usize <= 2 * tiles.len());
+    }
+
+    #[test]
+    fn tiles() {
+        let tiles: Vec<Tile> = (0..200)
+            .map(|i| Tile::new(8, (i * 37) % 256, (i * 101) % 256).unwrap())
+            .chain(Some(Tile::new(3, 1, 1).unwrap()))
+            .collect();
+        let shards = partition_tiles(tiles.clone(), TileOrder::Hilbert, 6);
+        assert_eq!(shards.len(), 6);
+        for t in tiles.iter() {
+            assert_eq!(shards.iter().filter(|s| s.contains_tile(t)).count(), 1);
+        }
+        for s in shards.iter() {
+            let count = tiles.iter().filter(|t| s.contains_tile(t)).count();
+            assert!(count == 33 || count == 34, "{}", count);
+        }
+
+        // A worker can iterate over a shard, and get the tiles it was given, and not all the tiles
+        // in the world on that part of the curve
+        for s in shards.iter() {
+            let s: Shard = s.to_string().parse().unwrap();
+            let iterated: HashSet<Tile> = s.tiles().collect();
+            assert!(iterated
+                .iter()
+                .all(|t| s.tile_ranges().iter().any(|r| r.contains(t))));
+            for t in tiles.iter().filter(|t| s.contains_tile(t)) {
+                assert!(iterated.contains(t));
+            }
+            assert!(iterated.iter().all(|t| s.contains_tile(t)));
+        }
+        let s = &partition_tiles(vec![Tile::new(10, 5, 5).unwrap()], TileOrder::ZOrder, 1)[0];
+        assert_eq!(
+            s.to_string(),
+            "zorder scale=1 first=10/51 last=10/51 len=1 ranges=10/5-5/5-5"
+        );
+        assert_eq!(
+            s.tiles().collect::<Vec<_>>(),
+            vec![Tile::new(10, 5, 5).unwrap()]
+        );
+
+        assert!(partition_tiles(vec![], TileOrder::ZOrder, 3).is_empty());
+    }
+
+    #[test]
+    fn parse() {
+        let s: Shard = "zorder scale=8 first=3/0 last=5/10 len=20 bbox=-10,50,-5,55"
+            .parse()
+            .unwrap();
+        assert_eq!(s.scale(), 8);
+        assert_eq!(s.order(), TileOrder::ZOrder);
+        assert_eq!(s.zooms(), (3, 5));
+        assert_eq!(
+            s.to_string(),
+            "zorder scale=8 first=3/0 last=5/10 len=20 bbox=-10,50,-5,55"
+        );
+        assert!("zorder scale=3 first=3/0 last=5/10 len=20"
+            .parse::<Shard>()
+            .is_err());
+        assert!("zorder scale=1 first=6/0 last=5/10 len=20"
+            .parse::<Shard>()
+            .is_err());
+        assert!("zorder scale=1 first=6/0 len=20".parse::<Shard>().is_err());
+        assert!("peano scale=1 first=1/0 last=5/10 len=20"
+            .parse::<Shard>()
+            .is_err());
+    }
+}
+
+mod metatiles_checkpoint {
+    use super::*;
+
+    fn check(make: &dyn Fn() -> MetatilesIterator) {
+        let all: Vec<Metatile> = make().collect();
+        assert!(!all.is_empty());
+        for n in (0..=all.len()).step_by(7).chain(Some(all.len())) {
+            let mut it = make();
+            for _ in 0..n {
+                it.next();
+            }
+            let token = it.checkpoint().unwrap().to_string();
+            let checkpoint: MetatilesCheckpoint = token.parse().unwrap();
+            assert_eq!(checkpoint.to_string(), token);
+            let resumed = MetatilesIterator::from_checkpoint(&checkpoint);
+            assert_eq!(resumed.remaining(), it.remaining());
+            assert_eq!(resumed.checkpoint(), it.checkpoint());
+            let resumed: Vec<Metatile> = resumed.collect();
+            assert_eq!(resumed, all[n..].to_vec(), "{}", token);
+        }
+    }
+
+    #[test]
+    fn resume() {
+        let bbox = BBox::new(55.5, -10.7, 51.4, -5.3).unwrap();
+        for &order in [TileOrder::ZOrder, TileOrder::Hilbert].iter() {
+            for &scale in [1, 8].iter() {
+                check(&|| {
+                    MetatilesIterator::new_for_bbox_zoom(scale, &Some(bbox.clone()), 2, 9)
+                        .with_order(order)
+                });
+                check(&|| {
+                    MetatilesIterator::new_for_bbox_zoom(scale, &None, 0, 5).with_order(order)
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn token() {
+        let bbox = BBox::new(51.512345, -0.1234567, 51.4, 0.0001).unwrap();
+        let it = MetatilesIterator::new_for_bbox_zoom(8, &Some(bbox.clone()), 12, 18);
+        let checkpoint = it.checkpoint().unwrap();
+        assert_eq!(checkpoint.zoom(), 12);
+        assert_eq!(checkpoint.scale(), 8);
+        let token = checkpoint.to_string();
+        assert_eq!(
+            token,
+            "zorder scale=8 minzoom=12 zoom=12 maxzoom=18 position=0 bbox=-0.1234567,51.4,0.0001,51.512344"
+        );
+        let parsed: MetatilesCheckpoint = token.parse().unwrap();
+        assert_eq!(parsed.bbox(), Some(&bbox));
+        // The bbox is in the same format as a shard's
+        assert_eq!(bbox.to_string().parse::<BBox>(), Ok(bbox));
+
+        let it = MetatilesIterator::all(4).with_order(TileOrder::Hilbert);
+        assert_eq!(
+            it.checkpoint().unwrap().to_string(),
+            "hilbert scale=4 minzoom=0 zoom=0 maxzoom=32 position=0"
+        );
+
+        let parse = |s: &str| s.parse::<MetatilesCheckpoint>();
+        assert!(parse("zorder scale=3 minzoom=0 zoom=0 maxzoom=32 position=0").is_err());
+        assert!(parse("zorder scale=8 minzoom=0 zoom=0 maxzoom=32").is_err());
+        assert!(parse("zorder scale=8 minzoom=0 zoom=0 maxzoom=32 position=0 bbox=1,2,3").is_err());
+        assert!(
+            parse("zorder scale=8 minzoom=0 zoom=0 maxzoom=32 position=0 bbox=1,2,3,x").is_err()
+        );
+        assert!(parse("zorder scale=8 minzoom=0 zoom=0 maxzoom=32 position=0 foo=1").is_err());
+        assert!(parse("8 zorder 0 0 32 0").is_err());
+    }
+}
+
+mod totals {
+    use super::*;
+
+    #[test]
+    fn all_tiles() {
+        let mut it = ::all_tiles();
+        assert_eq!(it.total(), u64::MAX / 3);
+        assert_eq!(it.remaining(), u64::MAX / 3);
+        it.nth(99);
+        assert_eq!(it.remaining(), u64::MAX / 3 - 100);
+    }
+
+    #[test]
+    fn all_to_zoom() {
+        for max_zoom in 0..6 {
+            let mut it = Tile::all_to_zoom(max_zoom);
+            let total = it.total();
+            assert_eq!(total, Tile::all_to_zoom(max_zoom).count() as u64);
+            let mut returned = 0;
+            loop {
+                assert_eq!(it.remaining(), total - returned);
+                if it.next().is_none() {
+                    break;
+                }
+                returned += 1;
+            }
+        }
+        assert_eq!(Tile::all_to_zoom(31).total(), u64::MAX / 3);
+        assert_eq!(Tile::all_to_zoom(200).total(), u64::MAX / 3);
+    }
+
+    #[test]
+    fn subtiles() {
+        let mut it = Tile::new(28, 5, 6).unwrap().all_subtiles_iter();
+        assert_eq!(it.total(), 4 + 16 + 64);
+        for i in 0..84 {
+            assert_eq!(it.remaining(), 84 - i);
+            assert_eq!(it.size_hint(), (84 - i as usize, Some(84 - i as usize)));
+            let t = it.next().unwrap();
+            assert!(t.zoom() <= 31);
+        }
+        assert_eq!(it.next(), None);
+        assert_eq!(it.remaining(), 0);
+
+        assert_eq!(Tile::new(31, 0, 0).unwrap().all_subtiles_iter().count(), 0);
+        assert_eq!(
+            Tile::new(0, 0, 0).unwrap().all_subtiles_iter().total(),
+            u64::MAX / 3 - 1
+        );
+    }
+
+    /// The tiles of a bbox, by going down the quadtree, as `BBox::tiles()` used to.
+    fn bbox_tiles_quadtree(bbox: &BBox, max_zoom: u8) -> Vec<Tile> {
+        let mut result = Vec::new();
+        let mut tiles = vec![Tile::new(0, 0, 0).unwrap()];
+        for _ in 0..=max_zoom {
+            result.extend(tiles.iter().cloned());
+            tiles = tiles
+                .iter()
+                .flat_map(|t| t.subtiles().unwrap().to_vec())
+                .filter(|t| bbox.overlaps_bbox(&t.bbox()))
+                .collect();
+        }
+        result
+    }
+
+    #[test]
+    fn bbox_tiles() {
+        let bboxes = [
+            BBox::new(53.61, -6.66, 53.08, -5.98).unwrap(),
+            BBox::new(55.7, -11.32, 51.11, -4.97).unwrap(),
+            BBox::new(10., -180., -10., -170.).unwrap(),
+            BBox::new(85., 170., 80., 180.).unwrap(),
+        ];
+        for bbox in bboxes.iter() {
+            let expected = bbox_tiles_quadtree(bbox, 10);
+            let mut it = bbox.tiles();
+            let total = it.total();
+            for (i, t) in expected.iter().enumerate() {
+                assert_eq!(it.remaining(), total - i as u64);
+                assert_eq!(it.next().as_ref(), Some(t));
+            }
+            let zoom_11 = it.by_ref().take_while(|t| t.zoom() == 11).count() as u64;
+            assert_eq!(zoom_11, overlapping_tile_range(bbox, 11).unwrap().count());
+        }
+    }
+
+    fn check_metatiles(make: &dyn Fn() -> MetatilesIterator) {
+        let mut it = make();
+        let total = it.total_metatiles();
+        assert_eq!(it.total(), None);
+        assert_eq!(total, make().count() as u64);
+        let mut returned = 0;
+        loop {
+            assert_eq!(it.remaining(), total - returned);
+            assert_eq!(it.size_hint().1, Some((total - returned) as usize));
+            if it.next().is_none() {
+                break;
+            }
+            returned += 1;
+        }
+    }
+
+    #[test]
+    fn metatiles() {
+        let bboxes = [
+            BBox::new(55.7, -11.32, 51.11, -4.97).unwrap(),
+            BBox::new(53.61, -6.66, 53.08, -5.98).unwrap(),
+            // Wide, and on the right edge of the world
+            BBox::new(10., 100., 5., 180.).unwrap(),
+        ];
+        for &order in [TileOrder::ZOrder, TileOrder::Hilbert].iter() {
+            for &scale in [1, 4, 8].iter() {
+                for bbox in bboxes.iter() {
+                    check_metatiles(&|| {
+                        MetatilesIterator::new_for_bbox_zoom(scale, &Some(bbox.clone()), 3, 11)
+                            .with_order(order)
+                    });
+
+                    // Only the metatiles which cover the bbox
+                    for mt in
+                        MetatilesIterator::new_for_bbox_zoom(scale, &Some(bbox.clone()), 0, 11)
+                            .with_order(order)
+                    {
+                        let range = bbox.tile_range(mt.zoom());
+                        assert!(mt.tiles().iter().any(|t| range.contains(t)), "{:?}", mt);
+                    }
+                }
+                check_metatiles(&|| {
+                    MetatilesIterator::new_for_bbox_zoom(scale, &None, 0, 6).with_order(order)
+                });
+            }
+        }
+
+        assert_eq!(MetatilesIterator::all(1).total_metatiles(), u64::MAX / 3);
+        assert_eq!(
+            MetatilesIterator::all(8).remaining(),
+            MetatilesIterator::all(8).total_metatiles()
+        );
+
+        // Resuming keeps the total
+        let bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+        let mut it = MetatilesIterator::new_for_bbox_zoom(8, &Some(bbox), 2, 12);
+        it.nth(20);
+        let resumed = MetatilesIterator::from_checkpoint(&it.checkpoint().unwrap());
+        assert_eq!(resumed.total_metatiles(), it.total_metatiles());
+        assert_eq!(resumed.remaining(), it.remaining());
+    }
+}
+
+mod bbox_tiles_traversal {
+    use super::*;
+
+    fn bboxes() -> Vec<BBox> {
+        vec![
+            BBox::new(53.61, -6.66, 53.08, -5.98).unwrap(),
+            BBox::new(55.7, -11.32, 51.11, -4.97).unwrap(),
+            BBox::new(10., -180., -10., -170.).unwrap(),
+            BBox::new(85., 170., 80., 180.).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn zoom_bounds() {
+        for bbox in bboxes().iter() {
+            let all: Vec<Tile> = bbox.tiles().take_while(|t| t.zoom() <= 9).collect();
+            let tiles: Vec<Tile> = bbox.tiles().with_min_zoom(3).with_max_zoom(9).collect();
+            let expected: Vec<Tile> = all.iter().cloned().filter(|t| t.zoom() >= 3).collect();
+            assert_eq!(tiles, expected);
+            assert_eq!(
+                bbox.tiles().with_min_zoom(3).with_max_zoom(9).total(),
+                expected.len() as u64
+            );
+        }
+
+        let bbox = BBox::new(53.61, -6.66, 53.08, -5.98).unwrap();
+        assert_eq!(
+            bbox.tiles().with_max_zoom(0).collect::<Vec<_>>(),
+            vec![Tile::new(0, 0, 0).unwrap()]
+        );
+        assert_eq!(bbox.tiles().with_min_zoom(6).with_max_zoom(5).count(), 0);
+        assert_eq!(bbox.tiles().with_min_zoom(6).with_max_zoom(5).total(), 0);
+        assert_eq!(
+            bbox.tiles().with_max_zoom(200).total(),
+            bbox.tiles().total()
+        );
+    }
+
+    #[test]
+    fn depth_first() {
+        for bbox in bboxes().iter() {
+            let breadth_first: Vec<Tile> = bbox.tiles().with_min_zoom(2).with_max_zoom(9).collect();
+            let mut it = bbox
+                .tiles()
+                .with_min_zoom(2)
+                .with_max_zoom(9)
+                .with_traversal(Traversal::DepthFirst);
+            let total = it.total();
+            assert_eq!(total, breadth_first.len() as u64);
+
+            let mut depth_first = Vec::new();
+            loop {
+                assert_eq!(it.remaining(), total - depth_first.len() as u64);
+                assert_eq!(
+                    it.size_hint(),
+                    (it.remaining() as usize, Some(it.remaining() as usize))
+                );
+                match it.next() {
+                    None => break,
+                    Some(t) => depth_first.push(t),
+                }
+            }
+
+            // Each tile comes after its parent, and all tiles are there once
+            for (i, t) in depth_first.iter().enumerate() {
+                if t.zoom() > 2 {
+                    let parent = t.parent().unwrap();
+                    assert!(depth_first[..i].contains(&parent));
+                }
+            }
+            let mut sorted = depth_first.clone();
+            sorted.sort_by_key(|t| (t.zoom(), xy_to_zorder(t.x(), t.y())));
+            assert_eq!(sorted, breadth_first);
+
+            // Depth first is packed_id order
+            let mut by_id = depth_first.clone();
+            by_id.sort();
+            assert_eq!(by_id, depth_first);
+        }
+    }
+
+    #[test]
+    fn prune() {
+        for bbox in bboxes().iter() {
+            for &traversal in [Traversal::BreadthFirst, Traversal::DepthFirst].iter() {
+                // Only go into tiles with an even x
+                let tiles: Vec<Tile> = bbox
+                    .tiles()
+                    .with_max_zoom(8)
+                    .with_traversal(traversal)
+                    .with_prune(|t| t.x() % 2 == 1)
+                    .collect();
+                let expected: Vec<Tile> = bbox
+                    .tiles()
+                    .with_max_zoom(8)
+                    .with_traversal(traversal)
+                    .filter(|t| {
+                        let mut t = *t;
+                        while let Some(parent) = t.parent() {
+                            if parent.x() % 2 == 1 {
+                                return false;
+                            }
+                            t = parent;
+                        }
+                        true
+                    })
+                    .collect();
+                assert_eq!(tiles, expected);
+
+                let it = bbox.tiles().with_traversal(traversal).with_prune(|_| true);
+                assert_eq!(it.size_hint(), (0, Some(it.remaining() as usize)));
+                assert_eq!(it.count(), 1);
+            }
+        }
+
+        // The callback isn't called for tiles at the max zoom
+        let bbox = BBox::new(53.61, -6.66, 53.08, -5.98).unwrap();
+        let mut seen = Vec::new();
+        bbox.tiles()
+            .with_min_zoom(4)
+            .with_max_zoom(5)
+            .with_prune(|t| {
+                seen.push(*t);
+                false
+            })
+            .for_each(drop);
+        assert_eq!(seen, vec![Tile::new(4, 7, 5).unwrap()]);
+    }
+
+    #[test]
+    fn prune_memory() {
+        // Breadth first only keeps the pruned areas, which are joined up
+        let world = BBox::new(85., -180., -85., 180.).unwrap();
+        let mut it = world
+            .tiles()
+            .with_max_zoom(10)
+            .with_prune(|t| t.zoom() == 1 && t.x() == 1);
+        let mut count = 0;
+        while let Some(tile) = it.next() {
+            assert!(it.pruned.len() <= 2, "{:?} {:?}", tile, it.pruned);
+            assert!(it.next_pruned.len() <= 2, "{:?} {:?}", tile, it.next_pruned);
+            count += 1;
+        }
+        let expected = world
+            .tiles()
+            .with_max_zoom(10)
+            .filter(|t| t.zoom() < 2 || t.x() >> (t.zoom() - 1) == 0)
+            .count();
+        assert_eq!(count, expected);
+    }
+}
+
+mod pixels {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let points = [(53.35, -6.26), (0., 0.), (-33.86, 151.21), (85., -179.9)];
+        for &(lat, lon) in points.iter() {
+            let point = LatLon::new(lat, lon).unwrap();
+            for &tile_size in [256, 512].iter() {
+                for &zoom in [0, 5, 12, 18].iter() {
+                    let tile = point.tile(zoom);
+                    let (px, py) = tile.latlon_to_pixel(&point, tile_size);
+                    assert!(px >= 0. && px < tile_size as f64, "{} {}", px, tile_size);
+                    assert!(py >= 0. && py < tile_size as f64, "{} {}", py, tile_size);
+
+                    let back = tile.pixel_to_latlon(px, py, tile_size).unwrap();
+                    assert!((back.lat() - lat).abs() < 1e-4, "{:?}", back);
+                    assert!((back.lon() - lon).abs() < 1e-4, "{:?}", back);
+
+                    // Global pixels agree with the tile pixels
+                    let (gx, gy) = lat_lon_to_global_pixel(lat, lon, zoom, tile_size);
+                    let (origin_x, origin_y) = tile.global_pixel(tile_size);
+                    assert!((gx - origin_x as f64 - px).abs() < 1e-6);
+                    assert!((gy - origin_y as f64 - py).abs() < 1e-6);
+                    let (t, (tx, ty)) = global_pixel_to_tile(gx, gy, zoom, tile_size).unwrap();
+                    assert_eq!(t, tile);
+                    assert!((tx - px).abs() < 1e-6 && (ty - py).abs() < 1e-6);
+                    let back = global_pixel_to_lat_lon(gx, gy, zoom, tile_size).unwrap();
+                    assert!((back.lat() - lat).abs() < 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn retina() {
+        // A @2x tile has the same points at twice the pixels
+        let tile = Tile::new(14, 7896, 5303).unwrap();
+        for &(px, py) in [(0., 0.), (10., 200.5), (255., 255.)].iter() {
+            assert_eq!(
+                tile.pixel_to_latlon(px, py, 256),
+                tile.pixel_to_latlon(px * 2., py * 2., 512)
+            );
+        }
+    }
+
+    #[test]
+    fn corners() {
+        let tile = Tile::new(3, 2, 5).unwrap();
+        assert_eq!(tile.pixel_to_latlon(0., 0., 256), Some(tile.nw_corner()));
+        let se = tile.pixel_to_latlon(256., 256., 256).unwrap();
+        assert!((se.lat() - tile.se_corner().lat()).abs() < 1e-4);
+        assert!((se.lon() - tile.se_corner().lon()).abs() < 1e-4);
+
+        // Outside the world
+        assert_eq!(tile.pixel_to_latlon(-600., 0., 256), None);
+        assert_eq!(global_pixel_to_lat_lon(-1., 0., 0, 256), None);
+        assert_eq!(global_pixel_to_tile(256., 0., 0, 256), None);
+        assert_eq!(global_pixel_to_tile(0., -0.5, 0, 256), None);
+    }
+}
+
+mod web_mercator {
+    use super::*;
+    use mercator::{self, Extent, EXTENT};
+
+    #[test]
+    fn round_trip() {
+        let points = [(53.35, -6.26), (0., 0.), (-33.86, 151.21), (85., -179.9)];
+        for &(lat, lon) in points.iter() {
+            let (x, y) = mercator::lat_lon_to_merc(lat, lon);
+            let (lat2, lon2) = mercator::merc_to_lat_lon(x, y);
+            assert!((lat2 - lat).abs() < 1e-9 && (lon2 - lon).abs() < 1e-9);
+        }
+
+        assert_eq!(mercator::lat_lon_to_merc(0., 180.).0, EXTENT);
+        let (_, top) = mercator::lat_lon_to_merc(90., 0.);
+        assert!((top - EXTENT).abs() < 1e-6);
+        assert!((mercator::max_lat() - 85.0511287798).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tile_extent() {
+        assert_eq!(Tile::new(0, 0, 0).unwrap().merc_bbox(), Extent::world());
+
+        let tile = Tile::new(10, 494, 331).unwrap();
+        let extent = tile.merc_bbox();
+        assert!((extent.width() - mercator::tile_width(10)).abs() < 1e-6);
+        assert!((extent.height() - mercator::tile_width(10)).abs() < 1e-6);
+
+        let nw = tile.nw_corner();
+        let (x, y) = mercator::lat_lon_to_merc(nw.lat() as f64, nw.lon() as f64);
+        assert!((x - extent.min_x()).abs() < 1. && (y - extent.max_y()).abs() < 1.);
+
+        // The subtiles split the extent
+        let subtiles = tile.subtiles().unwrap();
+        assert_eq!(subtiles[0].merc_bbox().min_x(), extent.min_x());
+        assert_eq!(subtiles[0].merc_bbox().max_y(), extent.max_y());
+        assert!((subtiles[3].merc_bbox().max_x() - extent.max_x()).abs() < 1e-6);
+        assert!((subtiles[3].merc_bbox().min_y() - extent.min_y()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn merc_to_tile() {
+        let points = [(53.35, -6.26), (1., 1.), (-33.86, 151.21), (85., -179.9)];
+        for &(lat, lon) in points.iter() {
+            let point = LatLon::new(lat, lon).unwrap();
+            let (x, y) = mercator::lat_lon_to_merc(lat as f64, lon as f64);
+            for &zoom in [0, 4, 12, 18].iter() {
+                let (tile, (px, py)) = mercator::merc_to_tile(x, y, zoom, 512).unwrap();
+                assert_eq!(tile, point.tile(zoom));
+                let (px2, py2) = tile.latlon_to_pixel(&point, 512);
+                assert_eq!((px, py), (px2 as u32, py2 as u32));
+
+                let (tile_xy, pixel) = merc_location_to_tile_coords(x, y, zoom);
+                assert_eq!(tile_xy, (tile.x(), tile.y()));
+                assert_eq!(pixel, (px / 2, py / 2));
+            }
+        }
+
+        assert_eq!(mercator::merc_to_tile(EXTENT + 1., 0., 3, 256), None);
+
+        // Points at & outside the edges are moved to the nearest tile
+        assert_eq!(
+            merc_location_to_tile_coords(-EXTENT, EXTENT, 3),
+            ((0, 0), (0, 0))
+        );
+        assert_eq!(
+            merc_location_to_tile_coords(EXTENT, -EXTENT, 3),
+            ((7, 7), (255, 255))
+        );
+        assert_eq!(
+            merc_location_to_tile_coords(-3. * EXTENT, 0., 1),
+            ((0, 1), (0, 0))
+        );
+    }
+
+    #[test]
+    fn bbox() {
+        let bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+        let extent = bbox.to_3857().unwrap();
+        assert!(extent.min_x() < extent.max_x() && extent.min_y() < extent.max_y());
+        let back = BBox::from_3857(&extent).unwrap();
+        assert!((back.top() - bbox.top()).abs() < 1e-4);
+        assert!((back.left() - bbox.left()).abs() < 1e-4);
+        assert!((back.bottom() - bbox.bottom()).abs() < 1e-4);
+        assert!((back.right() - bbox.right()).abs() < 1e-4);
+
+        let world = BBox::from_3857(&Extent::world()).unwrap();
+        assert_eq!((world.left(), world.right()), (-180., 180.));
+        assert_eq!(
+            BBox::from_3857(&Extent::new(0., 0., EXTENT * 2., 1.).unwrap()),
+            None
+        );
+
+        // An extent can't go over the antimeridian, or be upside down
+        assert_eq!(BBox::new(10., 170., -10., -170.).unwrap().to_3857(), None);
+        assert_eq!(BBox::new(-10., 0., 10., 10.).unwrap().to_3857(), None);
+
+        assert_eq!(Extent::new(1., 0., 0., 1.), None);
+        assert_eq!(LatLon::from_3857(3. * EXTENT, 0.), None);
+    }
+
+    #[test]
+    fn envelope() {
+        let world = Extent::world();
+        let tile = Tile::new(10, 494, 331).unwrap();
+        let envelope = tile.envelope(0.).unwrap();
+        let extent = tile.merc_bbox();
+        assert_eq!(envelope.min_x(), extent.min_x());
+        assert_eq!(envelope.max_y(), extent.max_y());
+        assert!((envelope.min_y() - extent.min_y()).abs() < 1e-6);
+        assert!((envelope.max_x() - extent.max_x()).abs() < 1e-6);
+
+        // Examples from the PostGIS docs
+        assert_eq!(
+            Tile::new(2, 1, 1)
+                .unwrap()
+                .envelope(0.)
+                .unwrap()
+                .to_ewkt(3857),
+            format!(
+                "SRID=3857;POLYGON(({x} 0,{x} {y},0 {y},0 0,{x} 0))",
+                x = -EXTENT / 2.,
+                y = EXTENT / 2.
+            )
+        );
+        let bounds = Extent::new(-180., -90., 180., 90.).unwrap();
+        assert_eq!(
+            mercator::tile_envelope(&Tile::new(3, 1, 1).unwrap(), &bounds, 0.)
+                .unwrap()
+                .to_ewkt(4326),
+            "SRID=4326;POLYGON((-135 45,-135 67.5,-90 67.5,-90 45,-135 45))"
+        );
+
+        // y is clipped to the bounds, x isn't
+        let envelope = Tile::new(1, 0, 0).unwrap().envelope(0.5).unwrap();
+        assert_eq!(envelope.min_x(), -1.5 * EXTENT);
+        assert!((envelope.max_x() - EXTENT / 2.).abs() < 1e-6);
+        assert_eq!(envelope.max_y(), EXTENT);
+        assert!((envelope.min_y() - -EXTENT / 2.).abs() < 1e-6);
+
+        // Wider than the bounds
+        assert_eq!(Tile::new(0, 0, 0).unwrap().envelope(0.1), Some(world));
+        assert_eq!(
+            Tile::new(1, 1, 1).unwrap().envelope(0.6).unwrap().min_x(),
+            -EXTENT
+        );
+
+        // Shrinking
+        let envelope = Tile::new(1, 1, 1).unwrap().envelope(-0.25).unwrap();
+        assert!((envelope.min_x() - EXTENT / 4.).abs() < 1e-6);
+        assert!((envelope.max_x() - EXTENT * 3. / 4.).abs() < 1e-6);
+        assert_eq!(
+            Tile::new(1, 1, 1).unwrap().envelope(-0.5).unwrap().width(),
+            0.
+        );
+        assert_eq!(Tile::new(1, 1, 1).unwrap().envelope(-0.51), None);
+
+        let flat = Extent::new(0., 0., 10., 0.).unwrap();
+        assert_eq!(mercator::tile_envelope(&tile, &flat, 0.), None);
+    }
+}
+
+mod tile_matrix_set {
+    use super::*;
+    use mercator::Extent;
+    use tilematrixset::{CornerOfOrigin, Crs, TileMatrix, TileMatrixSet, VariableMatrixWidth};
+
+    #[test]
+    fn web_mercator_quad() {
+        let tms = TileMatrixSet::web_mercator_quad();
+        assert_eq!(tms.id(), "WebMercatorQuad");
+        assert_eq!(tms.max_zoom(), 24);
+        let z0 = tms.tile_matrix(0).unwrap();
+        assert!((z0.cell_size() - 156543.033928041).abs() < 1e-6);
+        assert!((z0.scale_denominator() - 559082264.028717).abs() < 1e-5);
+        assert_eq!(tms.tile_matrix(10).unwrap().matrix_size(), (1024, 1024));
+
+        // The same as the rest of this crate
+        let tile = Tile::new(10, 494, 331).unwrap();
+        let extent = tms.tile_extent(10, 494, 331).unwrap();
+        let merc = tile.merc_bbox();
+        assert!((extent.min_x() - merc.min_x()).abs() < 1e-6);
+        assert!((extent.max_y() - merc.max_y()).abs() < 1e-6);
+        let bbox = tms.tile_bbox(10, 494, 331).unwrap();
+        assert!((bbox.top() - tile.top()).abs() < 1e-4);
+        assert!((bbox.left() - tile.left()).abs() < 1e-4);
+
+        for &(lat, lon) in [(53.35, -6.26), (-33.86, 151.21), (1., 1.)].iter() {
+            for &zoom in [0, 5, 18].iter() {
+                assert_eq!(
+                    tms.lat_lon_to_tile(lat as f64, lon as f64, zoom),
+                    Some(lat_lon_to_tile(lat, lon, zoom))
+                );
+            }
+        }
+
+        let bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+        let range = bbox.tile_range(8);
+        assert_eq!(
+            tms.tile_ranges_for_bbox(&bbox, 8),
+            Some((range.x_min()..=range.x_max(), range.y_min()..=range.y_max()))
+        );
+        assert_eq!(tms.tile_ranges_for_bbox(&bbox, 25), None);
+    }
+
+    #[test]
+    fn tiles() {
+        // With `WebMercatorQuad`, the same as the Web Mercator methods
+        let tms = TileMatrixSet::default();
+        assert_eq!(tms, TileMatrixSet::web_mercator_quad());
+        let bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+        for zoom in 0..=10 {
+            let tiles: Vec<Tile> = bbox.tiles_for_zoom_in(&tms, zoom).unwrap().collect();
+            let mut expected: Vec<Tile> = bbox.tiles_for_zoom(zoom).collect();
+            expected.sort_by_key(|t| (t.y(), t.x()));
+            assert_eq!(tiles, expected);
+
+            for tile in tiles.iter() {
+                let (extent, merc) = (tile.extent_in(&tms).unwrap(), tile.merc_bbox());
+                assert!((extent.min_x() - merc.min_x()).abs() < 1e-6);
+                assert!((extent.max_y() - merc.max_y()).abs() < 1e-6);
+                let (in_tms, bbox) = (tile.bbox_in(&tms).unwrap(), tile.bbox());
+                assert!((in_tms.top() - bbox.top()).abs() < 1e-4);
+                assert!((in_tms.right() - bbox.right()).abs() < 1e-4);
+            }
+        }
+        let dublin = LatLon::new(53.35, -6.26).unwrap();
+        assert_eq!(dublin.tile_in(&tms, 12), Some(dublin.tile(12)));
+        assert_eq!(bbox.tiles_for_zoom_in(&tms, 25).map(|t| t.count()), None);
+        assert_eq!(Tile::new(25, 0, 0).unwrap().extent_in(&tms), None);
+
+        // Ellipsoidal mercator has the same columns, but the rows are further south
+        let tms = TileMatrixSet::world_mercator_wgs84_quad();
+        let tile = dublin.tile_in(&tms, 12).unwrap();
+        assert_eq!(tile.x(), dublin.tile(12).x());
+        assert!(tile.y() > dublin.tile(12).y());
+        assert!(tile.bbox_in(&tms).unwrap().contains_point(&dublin));
+        let tiles: Vec<Tile> = bbox.tiles_for_zoom_in(&tms, 8).unwrap().collect();
+        assert!(tiles.contains(&Tile::new(8, tile.x() >> 4, tile.y() >> 4).unwrap()));
+
+        // `WorldCRS84Quad` is twice as wide, so can't be a `Tile`
+        let tms = TileMatrixSet::world_crs84_quad();
+        assert_eq!(bbox.tiles_for_zoom_in(&tms, 3).map(|t| t.count()), None);
+        let tile = dublin.tile_in(&tms, 0).unwrap();
+        assert_eq!(tile.bbox_in(&tms).unwrap().right(), 0.);
+        let sydney = LatLon::new(-33.86, 151.21).unwrap();
+        assert_eq!(sydney.tile_in(&tms, 0), None);
+    }
+
+    #[test]
+    fn world_crs84_quad() {
+        let tms = TileMatrixSet::world_crs84_quad();
+        let z0 = tms.tile_matrix(0).unwrap();
+        assert_eq!(z0.matrix_size(), (2, 1));
+        assert!((z0.scale_denominator() - 279541132.014358).abs() < 1e-5);
+        assert_eq!(z0.extent(), Extent::new(-180., -90., 180., 90.).unwrap());
+
+        assert_eq!(tms.lat_lon_to_tile(-90., -180., 0), Some((0, 0)));
+        assert_eq!(tms.lat_lon_to_tile(90., 180., 0), Some((1, 0)));
+        assert_eq!(tms.lat_lon_to_tile(-30., 100., 2), Some((6, 2)));
+        assert_eq!(tms.tile_extent(0, 2, 0), None);
+
+        let bbox = tms.tile_bbox(2, 6, 2).unwrap();
+        assert_eq!(
+            (bbox.top(), bbox.left(), bbox.bottom(), bbox.right()),
+            (0., 90., -45., 135.)
+        );
+
+        let world = BBox::new(90., -180., -90., 180.).unwrap();
+        assert_eq!(tms.tile_ranges_for_bbox(&world, 3), Some((0..=15, 0..=7)));
+
+        // Over the antimeridian
+        let bbox = BBox::new(10., 170., -10., -170.).unwrap();
+        assert_eq!(tms.bbox_extent(&bbox), None);
+        assert_eq!(tms.tiles_for_bbox(&bbox, 3).count(), 0);
+    }
+
+    #[test]
+    fn world_mercator_wgs84_quad() {
+        let crs = Crs::WorldMercator;
+        let (x, y) = crs.project(45., 10.).unwrap();
+        assert!((x - 1113194.9079327357).abs() < 1e-6);
+        assert!((y - 5591295.9185533915).abs() < 1e-3, "{}", y);
+        for &(lat, lon) in [(53.35, -6.26), (-33.86, 151.21), (0., 0.), (85., 179.)].iter() {
+            let (x, y) = crs.project(lat, lon).unwrap();
+            let (lat2, lon2) = crs.unproject(x, y).unwrap();
+            assert!((lat2 - lat).abs() < 1e-9 && (lon2 - lon).abs() < 1e-9);
+        }
+
+        // Ellipsoidal Mercator is a little further south than spherical
+        let tms = TileMatrixSet::world_mercator_wgs84_quad();
+        let merc = TileMatrixSet::web_mercator_quad();
+        let (_, row) = tms.lat_lon_to_tile(60., 10., 12).unwrap();
+        let (_, merc_row) = merc.lat_lon_to_tile(60., 10., 12).unwrap();
+        assert!(row > merc_row);
+
+        let top = tms.tile_bbox(0, 0, 0).unwrap().top();
+        assert!((top - 85.08406).abs() < 1e-4, "{}", top);
+    }
+
+    #[test]
+    fn local_grid() {
+        // A made up grid, with the origin at the bottom left
+        let matrices = (0..3)
+            .map(|zoom| {
+                TileMatrix::new(
+                    &format!("L{}", zoom),
+                    0.,
+                    100. / (1 << zoom) as f64,
+                    (400_000., 500_000.),
+                    (256, 256),
+                    (3 << zoom, 2 << zoom),
+                )
+                .unwrap()
+                .with_corner_of_origin(CornerOfOrigin::BottomLeft)
+            })
+            .collect();
+        let crs = Crs::from_uri("http://www.opengis.net/def/crs/EPSG/0/2157");
+        assert_eq!(crs.uri(), "http://www.opengis.net/def/crs/EPSG/0/2157");
+        let tms = TileMatrixSet::new("Local", crs, matrices).unwrap();
+
+        assert_eq!(
+            tms.tile_extent(0, 2, 1),
+            Extent::new(451_200., 525_600., 476_800., 551_200.)
+        );
+        let z1 = tms.tile_matrix(1).unwrap();
+        assert_eq!(z1.tile_at(400_001., 500_001.), Some((0, 0)));
+        assert_eq!(z1.tile_at(413_000., 513_000.), Some((1, 1)));
+        assert_eq!(z1.tile_at(399_999., 500_001.), None);
+        let extent = Extent::new(390_000., 512_000., 415_000., 520_000.).unwrap();
+        assert_eq!(z1.tile_ranges(&extent), Some((0..=1, 0..=1)));
+        let outside = Extent::new(0., 0., 1., 1.).unwrap();
+        assert_eq!(z1.tile_ranges(&outside), None);
+
+        // Can't convert lat/lons
+        assert_eq!(tms.lat_lon_to_tile(53., -7., 1), None);
+        assert_eq!(tms.tile_bbox(0, 0, 0), None);
+    }
+
+    #[test]
+    fn crs() {
+        for crs in [Crs::WebMercator, Crs::Crs84, Crs::WorldMercator].iter() {
+            assert_eq!(&Crs::from_uri(crs.uri()), crs);
+        }
+        assert_eq!(Crs::from_uri("EPSG:3857"), Crs::WebMercator);
+        assert_eq!(Crs::from_uri("OGC:CRS84"), Crs::Crs84);
+
+        assert_eq!(
+            TileMatrix::new("0", 1., 0., (0., 0.), (256, 256), (1, 1)),
+            None
+        );
+        assert_eq!(TileMatrixSet::new("empty", Crs::Crs84, vec![]), None);
+    }
+
+    #[test]
+    fn variable_matrix_widths() {
+        // 8 x 4 tiles, with 4 tiles merged in the top & bottom rows, and 2 in the next rows
+        let matrix = TileMatrix::new("1", 0., 180. / 4. / 256., (-180., 90.), (256, 256), (8, 4))
+            .unwrap()
+            .with_variable_matrix_widths(vec![
+                VariableMatrixWidth::new(4, 0, 0).unwrap(),
+                VariableMatrixWidth::new(2, 1, 1).unwrap(),
+                VariableMatrixWidth::new(4, 3, 3).unwrap(),
+            ]);
+
+        assert_eq!(matrix.tile_extent(4, 0), Extent::new(0., 45., 180., 90.));
+        assert_eq!(matrix.tile_extent(5, 0), None);
+        assert_eq!(matrix.tile_extent(6, 1), Extent::new(90., 0., 180., 45.));
+        assert_eq!(matrix.tile_extent(5, 2), Extent::new(45., -45., 90., 0.));
+        assert_eq!(matrix.tile_at(100., 80.), Some((4, 0)));
+        assert_eq!(matrix.tile_at(100., 40.), Some((6, 1)));
+        assert_eq!(matrix.tile_at(100., -40.), Some((6, 2)));
+
+        let extent = Extent::new(10., -80., 100., 80.).unwrap();
+        assert_eq!(
+            matrix.tiles(&extent).collect::<Vec<_>>(),
+            vec![(4, 0), (4, 1), (6, 1), (4, 2), (5, 2), (6, 2), (4, 3)]
+        );
+        assert_eq!(
+            matrix.extent(),
+            Extent::new(-180., -90., 180., 90.).unwrap()
+        );
+
+        assert_eq!(VariableMatrixWidth::new(0, 0, 0), None);
+        assert_eq!(VariableMatrixWidth::new(2, 3, 1), None);
+    }
+
+    #[cfg(feature = "json")]
+    mod json {
+        use super::*;
+        use tilematrixset::JsonError;
+
+        const LAT_LON_GRID: &str = r#"{
+            "id": "LatLonGrid",
+            "title": "A lat/lon grid",
+            "crs": {"uri": "http://www.opengis.net/def/crs/EPSG/0/4326"},
+            "orderedAxes": ["Lat", "Lon"],
+            "tileMatrices": [
+                {
+                    "id": "0",
+                    "scaleDenominator": 279541132.0143589,
+                    "cellSize": 0.703125,
+                    "pointOfOrigin": [90, -180],
+                    "tileWidth": 256,
+                    "tileHeight": 256,
+                    "matrixWidth": 2,
+                    "matrixHeight": 1
+                },
+                {
+                    "id": "1",
+                    "scaleDenominator": 139770566.0071794,
+                    "cellSize": 0.3515625,
+                    "cornerOfOrigin": "topLeft",
+                    "pointOfOrigin": [90, -180],
+                    "tileWidth": 256,
+                    "tileHeight": 256,
+                    "matrixWidth": 4,
+                    "matrixHeight": 2,
+                    "variableMatrixWidths": [
+                        {"coalesce": 2, "minTileRow": 0, "maxTileRow": 0}
+                    ]
+                }
+            ]
+        }"#;
+
+        #[test]
+        fn read() {
+            let tms = TileMatrixSet::from_json(LAT_LON_GRID).unwrap();
+            assert_eq!(tms.id(), "LatLonGrid");
+            assert_eq!(tms.title(), Some("A lat/lon grid"));
+            assert_eq!(
+                tms.crs(),
+                &Crs::Other("http://www.opengis.net/def/crs/EPSG/0/4326".to_string())
+            );
+            assert_eq!(tms.ordered_axes(), Some(("Lat", "Lon")));
+            assert_eq!(tms.max_zoom(), 1);
+
+            // The point of origin is x/y
+            let z1 = tms.tile_matrix(1).unwrap();
+            assert_eq!(z1.point_of_origin(), (-180., 90.));
+            assert_eq!(tms.tile_extent(1, 2, 0), Extent::new(0., 0., 180., 90.));
+            assert_eq!(tms.tile_extent(1, 1, 0), None);
+            assert_eq!(tms.tile_extent(1, 1, 1), Extent::new(-90., -90., 0., 0.));
+        }
+
+        #[test]
+        fn round_trip() {
+            let custom = TileMatrixSet::from_json(LAT_LON_GRID).unwrap();
+            let local = TileMatrixSet::new(
+                "Local",
+                Crs::from_uri("http://www.opengis.net/def/crs/EPSG/0/2157"),
+                vec![TileMatrix::new("a", 1., 2., (3., 4.), (512, 256), (5, 6))
+                    .unwrap()
+                    .with_corner_of_origin(CornerOfOrigin::BottomLeft)],
+            )
+            .unwrap();
+            for tms in [
+                TileMatrixSet::web_mercator_quad(),
+                TileMatrixSet::world_crs84_quad(),
+                TileMatrixSet::world_mercator_wgs84_quad(),
+                custom,
+                local,
+            ]
+            .iter()
+            {
+                let json = tms.to_json();
+                assert_eq!(&TileMatrixSet::from_json(&json).unwrap(), tms, "{}", json);
+            }
+
+            let json = TileMatrixSet::web_mercator_quad().to_json();
+            assert!(json.contains(r#""crs": "http://www.opengis.net/def/crs/EPSG/0/3857""#));
+            assert!(json.contains(r#""cornerOfOrigin": "topLeft""#));
+            let json = TileMatrixSet::from_json(LAT_LON_GRID).unwrap().to_json();
+            assert!(json.contains(r#""coalesce": 2"#));
+        }
+
+        #[test]
+        fn cell_size_from_scale_denominator() {
+            let web_mercator = TileMatrixSet::web_mercator_quad();
+            let json = web_mercator.to_json().replace("\"cellSize\"", "\"unused\"");
+            let tms = TileMatrixSet::from_json(&json).unwrap();
+            for (a, b) in tms.tile_matrices().iter().zip(web_mercator.tile_matrices()) {
+                assert!((a.cell_size() - b.cell_size()).abs() / b.cell_size() < 1e-12);
+            }
+
+            // Unknown CRS, so it can't be calculated
+            let json = LAT_LON_GRID.replace("\"cellSize\"", "\"unused\"");
+            assert!(TileMatrixSet::from_json(&json).is_err());
+        }
+
+        #[test]
+        fn errors() {
+            match TileMatrixSet::from_json("{") {
+                Err(JsonError::Json(_)) => {}
+                other => panic!("{:?}", other),
+            }
+            let bad = [
+                ("\"id\": \"LatLonGrid\",", ""),
+                ("\"tileMatrices\"", "\"matrices\""),
+                (
+                    "\"cornerOfOrigin\": \"topLeft\"",
+                    "\"cornerOfOrigin\": \"middle\"",
+                ),
+                ("[90, -180]", "[90]"),
+                ("\"matrixWidth\": 2", "\"matrixWidth\": 0"),
+                ("\"matrixWidth\": 2", "\"matrixWidth\": -2"),
+                ("\"coalesce\": 2", "\"coalesce\": 0"),
+                ("[\"Lat\", \"Lon\"]", "[\"Lat\"]"),
+            ];
+            for &(from, to) in bad.iter() {
+                let json = LAT_LON_GRID.replacen(from, to, 1);
+                assert_ne!(json, LAT_LON_GRID);
+                match TileMatrixSet::from_json(&json) {
+                    Err(JsonError::Invalid(msg)) => assert!(!msg.is_empty()),
+                    other => panic!("{} {:?}", from, other),
+                }
+            }
+        }
+    }
+}
+
+mod resolution {
+    use super::*;
+    use mercator::{ZoomRounding, STANDARD_PIXEL_SIZE};
+    use tilematrixset::TileMatrixSet;
+
+    #[test]
+    fn resolution() {
+        let tms = TileMatrixSet::web_mercator_quad();
+        for zoom in 0..=24 {
+            let matrix = tms.tile_matrix(zoom).unwrap();
+            let tile = Tile::new(zoom, 0, 0).unwrap();
+            assert!((tile.resolution(256) - matrix.cell_size()).abs() < 1e-9);
+            assert!((tile.scale_denominator(256) - matrix.scale_denominator()).abs() < 1e-6);
+            assert_eq!(tile.resolution(512) * 2., tile.resolution(256));
+        }
+        assert!(
+            (Tile::new(0, 0, 0).unwrap().scale_denominator(256) - 559_082_264.028).abs() < 1e-3
+        );
+
+        // 60° is half the size
+        let res = mercator::ground_resolution(60., 10, 256);
+        assert!((res - mercator::resolution(10, 256) / 2.).abs() < 1e-9);
+        let tile = LatLon::new(60., 10.).unwrap().tile(16);
+        assert!((tile.ground_resolution(256) - tile.resolution(256) / 2.).abs() < 1e-3);
+
+        let res = mercator::resolution_for_scale_denominator(25_000., STANDARD_PIXEL_SIZE);
+        assert!((res - 7.).abs() < 1e-9);
+        assert!((mercator::pixel_size_for_dpi(254.) - 0.0001).abs() < 1e-12);
+    }
+
+    #[test]
+    fn zoom_for_resolution() {
+        let roundings = [
+            ZoomRounding::Nearest,
+            ZoomRounding::Coarser,
+            ZoomRounding::Finer,
+        ];
+        for zoom in 0..=31 {
+            for &tile_size in [256, 512].iter() {
+                for &rounding in roundings.iter() {
+                    let res = mercator::resolution(zoom, tile_size);
+                    assert_eq!(
+                        mercator::zoom_for_resolution(res, tile_size, rounding),
+                        Some(zoom)
+                    );
+                }
+            }
+        }
+
+        // A bit more detailed than zoom 10
+        let res = mercator::resolution(10, 256) * 0.9;
+        assert_eq!(
+            mercator::zoom_for_resolution(res, 256, ZoomRounding::Nearest),
+            Some(10)
+        );
+        assert_eq!(
+            mercator::zoom_for_resolution(res, 256, ZoomRounding::Coarser),
+            Some(10)
+        );
+        assert_eq!(
+            mercator::zoom_for_resolution(res, 256, ZoomRounding::Finer),
+            Some(11)
+        );
+        let res = mercator::resolution(10, 256) * 0.6;
+        assert_eq!(
+            mercator::zoom_for_resolution(res, 256, ZoomRounding::Nearest),
+            Some(11)
+        );
+        assert!((mercator::fractional_zoom(res, 256) - 10.737).abs() < 1e-3);
+
+        // 1:25,000
+        let zoom = |rounding| {
+            mercator::zoom_for_scale_denominator(25_000., STANDARD_PIXEL_SIZE, 256, rounding)
+        };
+        assert_eq!(zoom(ZoomRounding::Nearest), Some(14));
+        assert_eq!(zoom(ZoomRounding::Coarser), Some(14));
+        assert_eq!(zoom(ZoomRounding::Finer), Some(15));
+
+        // Clamped
+        assert_eq!(
+            mercator::zoom_for_resolution(1e9, 256, ZoomRounding::Finer),
+            Some(0)
+        );
+        assert_eq!(
+            mercator::zoom_for_resolution(1e-9, 256, ZoomRounding::Coarser),
+            Some(31)
+        );
+        assert_eq!(
+            mercator::zoom_for_resolution(0., 256, ZoomRounding::Nearest),
+            None
+        );
+        assert_eq!(
+            mercator::zoom_for_resolution(-1., 256, ZoomRounding::Nearest),
+            None
+        );
+        assert_eq!(
+            mercator::zoom_for_resolution(f64::NAN, 256, ZoomRounding::Nearest),
+            None
+        );
+    }
+}
+
+mod viewport {
+    use super::*;
+    use mercator::ZoomRounding;
+    use viewport::Viewport;
+
+    /// Check the tiles cover the viewport, and are in the right place
+    fn check_tiles(viewport: &Viewport) {
+        let tiles = viewport.tiles();
+        assert!(!tiles.is_empty());
+        let (width, height) = (viewport.width() as f64, viewport.height() as f64);
+        for t in tiles.iter() {
+            assert_eq!(t.tile().zoom(), viewport.tile_zoom());
+            assert!(t.x() < width && t.x() + t.size() > 0.);
+            assert!(t.y() < height && t.y() + t.size() > 0.);
+        }
+        let min_x = tiles.iter().map(|t| t.x()).fold(f64::MAX, f64::min);
+        let max_x = tiles
+            .iter()
+            .map(|t| t.x() + t.size())
+            .fold(f64::MIN, f64::max);
+        assert!(min_x <= 0. && max_x >= width);
+
+        // The centre is where it should be
+        let centre = viewport.centre();
+        let tile = centre.tile(viewport.tile_zoom());
+        let placed = tiles.iter().find(|t| t.tile() == tile).unwrap();
+        let (px, py) = tile.latlon_to_pixel(centre, viewport.tile_size());
+        let scale = placed.size() / viewport.tile_size() as f64;
+        let x = placed.x() + px * scale;
+        let y = placed.y() + py * scale;
+        let diff = (x - width / 2.).rem_euclid(placed.size() * (1u64 << tile.zoom()) as f64);
+        assert!(diff.min(placed.size() * (1u64 << tile.zoom()) as f64 - diff) < 1e-3);
+        assert!((y - height / 2.).abs() < 1e-3, "{} {}", y, height);
+    }
+
+    #[test]
+    fn tiles() {
+        let dublin = LatLon::new(53.35, -6.26).unwrap();
+        for &zoom in [0., 1., 3.2, 12., 12.5, 17.9, 31.].iter() {
+            for &tile_size in [256, 512].iter() {
+                for &rounding in [
+                    ZoomRounding::Nearest,
+                    ZoomRounding::Coarser,
+                    ZoomRounding::Finer,
+                ]
+                .iter()
+                {
+                    let viewport = Viewport::new(dublin.clone(), zoom, 800, 600)
+                        .unwrap()
+                        .with_tile_size(tile_size)
+                        .unwrap()
+                        .with_zoom_rounding(rounding);
+                    check_tiles(&viewport);
+                }
+            }
+        }
+
+        let viewport = Viewport::new(dublin.clone(), 12.5, 800, 600).unwrap();
+        assert!(viewport.clone().with_tile_size(0).is_none());
+        assert_eq!(viewport.tile_zoom(), 13);
+        assert!((viewport.tiles()[0].size() - 256. / 2f64.sqrt()).abs() < 1e-9);
+        let viewport = viewport.with_zoom_rounding(ZoomRounding::Coarser);
+        assert_eq!(viewport.tile_zoom(), 12);
+        assert!((viewport.tiles()[0].size() - 256. * 2f64.sqrt()).abs() < 1e-9);
+
+        // Exactly 4 tiles
+        let viewport = Viewport::new(LatLon::new(0., 0.).unwrap(), 1., 512, 512).unwrap();
+        let tiles = viewport.tiles();
+        assert_eq!(tiles.len(), 4);
+        assert_eq!(
+            (tiles[3].tile(), tiles[3].x(), tiles[3].y()),
+            (Tile::new(1, 1, 1).unwrap(), 256., 256.)
+        );
+
+        assert_eq!(Viewport::new(dublin.clone(), 31.5, 800, 600), None);
+        assert_eq!(Viewport::new(dublin.clone(), -1., 800, 600), None);
+        assert_eq!(Viewport::new(dublin, 3., 0, 600), None);
+    }
+
+    #[test]
+    fn bbox() {
+        let viewport = Viewport::new(LatLon::new(0., 0.).unwrap(), 2., 512, 256).unwrap();
+        let bbox = viewport.bbox();
+        assert_eq!((bbox.left(), bbox.right()), (-90., 90.));
+        assert!((bbox.top() - 40.9799).abs() < 1e-4);
+        assert!((bbox.bottom() + 40.9799).abs() < 1e-4);
+        assert!(!viewport.crosses_antimeridian());
+
+        // Wider & taller than the world
+        let viewport = Viewport::new(LatLon::new(0., 0.).unwrap(), 0., 1000, 1000).unwrap();
+        let bbox = viewport.bbox();
+        assert_eq!((bbox.left(), bbox.right()), (-180., 180.));
+        assert!((bbox.top() - 85.0511).abs() < 1e-4);
+        assert!(viewport.crosses_antimeridian());
+        check_tiles(&viewport);
+        let world_tiles = viewport.tiles();
+        assert_eq!(world_tiles.len(), 5);
+        assert!(world_tiles
+            .iter()
+            .all(|t| t.tile() == Tile::new(0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn antimeridian() {
+        let viewport = Viewport::new(LatLon::new(-17., 179.).unwrap(), 6., 800, 600).unwrap();
+        assert!(viewport.crosses_antimeridian());
+        let bbox = viewport.bbox();
+        assert!(bbox.left() > 170. && bbox.left() < 179.);
+        assert!(bbox.right() < -170. && bbox.right() > -179.);
+        check_tiles(&viewport);
+
+        let xs: Vec<u32> = viewport.tiles().iter().map(|t| t.tile().x()).collect();
+        assert!(xs.contains(&63) && xs.contains(&0));
+        // Tiles on the left are before those on the right
+        let row: Vec<_> = viewport
+            .tiles()
+            .into_iter()
+            .filter(|t| t.y() == viewport.tiles()[0].y())
+            .collect();
+        assert!(row.windows(2).all(|w| w[1].x() == w[0].x() + w[0].size()));
+    }
+
+    #[test]
+    fn fit_bbox() {
+        let bboxes = [
+            BBox::new(55.7, -11.32, 51.11, -4.97).unwrap(),
+            BBox::new(53.61, -6.66, 53.08, -5.98).unwrap(),
+            BBox::new(10., 170., -10., -170.).unwrap(),
+        ];
+        for bbox in bboxes.iter() {
+            for &padding in [0, 20].iter() {
+                let viewport = Viewport::new(LatLon::new(0., 0.).unwrap(), 0., 800, 600).unwrap();
+                let (centre, zoom) = viewport.fit_bbox(bbox, padding).unwrap();
+                let fitted = Viewport::new(centre, zoom, 800, 600).unwrap();
+
+                // The corners of the bbox are inside, and one side just fits
+                let tile_zoom = 20;
+                let pixel = |p: &LatLon| {
+                    let (x, y) = lat_lon_to_global_pixel(p.lat(), p.lon(), tile_zoom, 256);
+                    let scale = 2f64.powf(zoom - tile_zoom as f64);
+                    (x * scale, y * scale)
+                };
+                let world = 256. * 2f64.powf(zoom);
+                let (cx, cy) = pixel(fitted.centre());
+                let (left, top) = pixel(&LatLon::new(bbox.top(), bbox.left()).unwrap());
+                let (right, bottom) = pixel(&LatLon::new(bbox.bottom(), bbox.right()).unwrap());
+                let right = if right < left { right + world } else { right };
+                let cx = if cx < left { cx + world } else { cx };
+                let p = padding as f64;
+                assert!(left - (cx - 400.) >= p - 1e-3 && (cx + 400.) - right >= p - 1e-3);
+                assert!(top - (cy - 300.) >= p - 1e-3 && (cy + 300.) - bottom >= p - 1e-3);
+                let fits_x = ((right - left) - (800. - 2. * p)).abs() < 1e-3;
+                let fits_y = ((bottom - top) - (600. - 2. * p)).abs() < 1e-3;
+                assert!(fits_x || fits_y);
+            }
+        }
+
+        let viewport = Viewport::new(LatLon::new(0., 0.).unwrap(), 0., 100, 100).unwrap();
+        assert_eq!(viewport.fit_bbox(&bboxes[0], 50), None);
+        // A point is zoomed in as far as possible
+        let point = BBox::new(1., 1., 1., 1.).unwrap();
+        assert_eq!(viewport.fit_bbox(&point, 0).unwrap().1, 31.);
+    }
+}
+
+mod camera {
+    use super::*;
+    use std::collections::HashSet;
+    use viewport::{Camera, Viewport};
+
+    /// Check no tile is inside another, and every visible point is in exactly one tile
+    fn check_covering(camera: &Camera) -> Vec<Tile> {
+        let tiles = camera.covering_tiles();
+        let set: HashSet<Tile> = tiles.iter().cloned().collect();
+        assert_eq!(set.len(), tiles.len());
+        for tile in tiles.iter() {
+            let mut parent = tile.parent();
+            while let Some(p) = parent {
+                assert!(!set.contains(&p), "{:?} is in {:?}", tile, p);
+                parent = p.parent();
+            }
+        }
+
+        let zooms: HashSet<u8> = tiles.iter().map(|t| t.zoom()).collect();
+        for i in 0..20 {
+            for j in 0..20 {
+                let x = (i as f64 + 0.37) / 20. * camera.width() as f64;
+                let y = (j as f64 + 0.61) / 20. * camera.height() as f64;
+                if let Some(point) = camera.unproject(x, y) {
+                    let containing = zooms
+                        .iter()
+                        .filter(|&&z| set.contains(&point.tile(z)))
+                        .count();
+                    assert_eq!(containing, 1, "{:?} at {},{}", point, x, y);
+                }
+            }
+        }
+
+        tiles
+    }
+
+    #[test]
+    fn no_pitch() {
+        let dublin = LatLon::new(53.35, -6.26).unwrap();
+        for &zoom in [0., 2.3, 12., 17.6].iter() {
+            let camera = Camera::new(dublin.clone(), zoom, 0., 0., 800, 600).unwrap();
+            assert!(camera.clone().with_tile_size(0).is_none());
+            let tiles = check_covering(&camera);
+            let viewport = Viewport::new(dublin.clone(), zoom, 800, 600).unwrap();
+            let mut expected: Vec<Tile> = viewport.tiles().iter().map(|t| t.tile()).collect();
+            expected.sort();
+            expected.dedup();
+            assert_eq!(tiles, expected);
+        }
+    }
+
+    #[test]
+    fn pitch_and_bearing() {
+        let dublin = LatLon::new(53.35, -6.26).unwrap();
+        for &bearing in [0., 30., 90., 200., -45.].iter() {
+            for &pitch in [0., 20., 45., 60.].iter() {
+                for &zoom in [1., 8.5, 15.].iter() {
+                    let camera = Camera::new(dublin.clone(), zoom, bearing, pitch, 1024, 768)
+                        .unwrap()
+                        .with_tile_size(512)
+                        .unwrap();
+                    let tiles = check_covering(&camera);
+                    assert_eq!(
+                        tiles.iter().map(|t| t.zoom()).max().unwrap(),
+                        camera.tile_zoom()
+                    );
+                    if pitch == 60. && zoom > 8. {
+                        assert!(tiles.iter().any(|t| t.zoom() < camera.tile_zoom()));
+                    }
+                }
+            }
+        }
+
+        let camera = Camera::new(dublin.clone(), 10., -45., 30., 800, 600).unwrap();
+        assert_eq!(camera.bearing(), 315.);
+        let centre = camera.unproject(400., 300.).unwrap();
+        assert!((centre.lat() - 53.35).abs() < 1e-4 && (centre.lon() + 6.26).abs() < 1e-4);
+
+        // Looking east, so the top of the screen is east of the centre
+        let camera = Camera::new(dublin.clone(), 10., 90., 45., 800, 600).unwrap();
+        let top = camera.unproject(400., 0.).unwrap();
+        assert!(top.lon() > -6.26 && (top.lat() - 53.35).abs() < 1e-3);
+
+        assert_eq!(Camera::new(dublin.clone(), 10., 0., 61., 800, 600), None);
+        assert_eq!(Camera::new(dublin.clone(), 10., 0., -1., 800, 600), None);
+        assert_eq!(Camera::new(dublin.clone(), 32., 0., 0., 800, 600), None);
+        assert_eq!(Camera::new(dublin, 10., 0., 0., 800, 0), None);
+    }
+
+    #[test]
+    fn antimeridian() {
+        let fiji = LatLon::new(-17.7, 179.9).unwrap();
+        let camera = Camera::new(fiji, 6., 80., 60., 800, 600).unwrap();
+        let tiles = check_covering(&camera);
+        assert!(tiles.iter().any(|t| t.x() == (1 << t.zoom()) - 1));
+        assert!(tiles.iter().any(|t| t.x() == 0));
+    }
+}
+
+mod static_map {
+    use super::*;
+    use staticmap::StaticMap;
+
+    /// Check the tiles fill the image, without overlapping, from the right part of each tile
+    fn check_tiles(map: &StaticMap, rows: u32) {
+        let tiles = map.tiles();
+        let mut area = 0;
+        for part in tiles.iter() {
+            let (crop, dest) = (part.crop(), part.dest());
+            assert_eq!((crop.width(), crop.height()), (dest.width(), dest.height()));
+            assert!(crop.width() > 0 && crop.height() > 0);
+            assert!(crop.x() + crop.width() <= map.tile_size());
+            assert!(crop.y() + crop.height() <= map.tile_size());
+            assert!(dest.x() + dest.width() <= map.width());
+            assert!(dest.y() + dest.height() <= map.height());
+            // Neighbours in the image are neighbours in the world
+            if crop.x() > 0 {
+                assert_eq!(dest.x(), 0);
+            }
+            if crop.y() > 0 {
+                assert_eq!(dest.y(), 0);
+            }
+            area += dest.width() as u64 * dest.height() as u64;
+        }
+        assert_eq!(area, map.width() as u64 * rows as u64);
+    }
+
+    #[test]
+    fn for_bbox() {
+        let world = BBox::new(85.0511, -180., -85.0511, 180.).unwrap();
+        let map = StaticMap::for_bbox(&world, 1, 256).unwrap();
+        assert_eq!((map.width(), map.height()), (512, 512));
+        let tiles: Vec<_> = map
+            .tiles()
+            .iter()
+            .map(|t| (t.tile(), t.dest().x(), t.dest().y(), t.crop().width()))
+            .collect();
+        assert_eq!(
+            tiles,
+            vec![
+                (Tile::new(1, 0, 0).unwrap(), 0, 0, 256),
+                (Tile::new(1, 1, 0).unwrap(), 256, 0, 256),
+                (Tile::new(1, 0, 1).unwrap(), 0, 256, 256),
+                (Tile::new(1, 1, 1).unwrap(), 256, 256, 256),
+            ]
+        );
+
+        // One tile is just that tile
+        let tile = Tile::new(10, 494, 331).unwrap();
+        let map = StaticMap::for_bbox(&tile.bbox(), 10, 512).unwrap();
+        assert_eq!((map.width(), map.height()), (512, 512));
+        assert_eq!(map.tiles().len(), 1);
+        assert_eq!(map.tiles()[0].tile(), tile);
+
+        let ireland = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+        for zoom in 0..12 {
+            for &tile_size in [256, 512].iter() {
+                let map = StaticMap::for_bbox(&ireland, zoom, tile_size).unwrap();
+                check_tiles(&map, map.height());
+                let bbox = map.bbox();
+                // Up to a tenth of a pixel can be left out
+                let e = 0.1 * 360. / (tile_size << zoom) as f32;
+                assert!(bbox.top() >= 55.7 - e && bbox.bottom() <= 51.11 + e);
+                assert!(bbox.left() <= -11.32 + e && bbox.right() >= -4.97 - e);
+                let mut expected: Vec<Tile> = ireland.tiles_for_zoom(zoom).collect();
+                let mut tiles: Vec<Tile> = map.tiles().iter().map(|t| t.tile()).collect();
+                expected.sort();
+                tiles.sort();
+                assert_eq!(tiles, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn antimeridian() {
+        let fiji = BBox::new(-15., 176., -20., -178.).unwrap();
+        let map = StaticMap::for_bbox(&fiji, 5, 256).unwrap();
+        check_tiles(&map, map.height());
+        let bbox = map.bbox();
+        assert!(bbox.left() > bbox.right());
+        let e = 0.1 * 360. / 8192.;
+        assert!(bbox.left() <= 176. + e && bbox.right() >= -178. - e);
+        let xs: Vec<u32> = map.tiles().iter().map(|t| t.tile().x()).collect();
+        assert!(xs.contains(&31) && xs.contains(&0));
+
+        // Wider & taller than the world, so tiles repeat, and there are rows with no tiles
+        let map = StaticMap::for_centre(&LatLon::new(0., 0.).unwrap(), 0, 600, 300, 256).unwrap();
+        check_tiles(&map, 256);
+        assert_eq!(map.tiles().len(), 3);
+        let bbox = map.bbox();
+        assert_eq!((bbox.left(), bbox.right()), (-180., 180.));
+
+        assert_eq!(
+            StaticMap::for_centre(&LatLon::new(0., 0.).unwrap(), 3, 0, 300, 256),
+            None
+        );
+        assert_eq!(StaticMap::for_bbox(&fiji, 32, 256), None);
+    }
+
+    #[cfg(feature = "world_file")]
+    #[test]
+    fn world_file() {
+        let tile = Tile::new(6, 33, 21).unwrap();
+        let map = StaticMap::for_bbox(&tile.bbox(), 6, 256).unwrap();
+        assert_eq!(map.world_file(), tile.world_file());
+
+        // The coordinates are of the centre of the top left pixel
+        let wf = tile.world_file();
+        let extent = tile.merc_bbox();
+        assert!((wf.x_coord - (extent.min_x() + wf.x_scale / 2.)).abs() < 1e-6);
+        assert!((wf.y_coord - (extent.max_y() + wf.y_scale / 2.)).abs() < 1e-6);
+
+        let map =
+            StaticMap::for_centre(&LatLon::new(53.35, -6.26).unwrap(), 12, 800, 600, 512).unwrap();
+        let wf = map.world_file();
+        let extent = map.bbox().to_3857().unwrap();
+        // The outside corners of the corner pixels. The bbox is f32, so only accurate to ~1 m
+        let (x, y) = wf.image_to_world((-0.5, -0.5));
+        assert!((x - extent.min_x()).abs() < 2. && (y - extent.max_y()).abs() < 2.);
+        let (x, y) = wf.image_to_world((799.5, 599.5));
+        assert!((x - extent.max_x()).abs() < 2. && (y - extent.min_y()).abs() < 2.);
+    }
+}
+
+#[cfg(feature = "image")]
+mod stitch {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+    use staticmap::StaticMap;
+    use std::path::PathBuf;
+    use stitch::{split_metatile, StitchError, TileDir, TileLayout};
+
+    /// A new empty directory for this test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("slippy-map-tiles-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// An image where every pixel is different
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([
+                (x / 256) as u8,
+                (y / 256) as u8,
+                (x % 256) as u8,
+                (y % 256) as u8,
+            ])
+        })
+    }
+
+    #[test]
+    fn split() {
+        let metatile = Metatile::new(8, 10, 8, 16).unwrap();
+        let image = gradient(2048, 2048);
+        let tiles = split_metatile(&metatile, &image, 256).unwrap();
+        assert_eq!(
+            tiles.iter().map(|t| t.0).collect::<Vec<_>>(),
+            metatile.tiles()
+        );
+        for (tile, tile_image) in tiles.iter() {
+            let (x, y) = (tile.x() - 8, tile.y() - 16);
+            assert_eq!(tile_image.dimensions(), (256, 256));
+            assert_eq!(tile_image.get_pixel(0, 0), &Rgba([x as u8, y as u8, 0, 0]));
+            assert_eq!(
+                tile_image.get_pixel(255, 10),
+                &Rgba([x as u8, y as u8, 255, 10])
+            );
+        }
+
+        // Metatiles at low zooms are smaller
+        let metatile = Metatile::new(8, 1, 0, 0).unwrap();
+        assert_eq!(
+            split_metatile(&metatile, &gradient(512, 512), 256)
+                .unwrap()
+                .len(),
+            4
+        );
+        match split_metatile(&metatile, &gradient(2048, 2048), 256) {
+            Err(StitchError::WrongSize {
+                path: None,
+                expected: (512, 512),
+                found: (2048, 2048),
+            }) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn write_and_compose() {
+        for &layout in [
+            TileLayout::Zxy,
+            TileLayout::Tc,
+            TileLayout::Mp,
+            TileLayout::Ts,
+        ]
+        .iter()
+        {
+            let dir = test_dir(&format!("{:?}", layout));
+            let tiles = TileDir::new(&dir, layout, "png");
+            let metatile = Metatile::new(8, 12, 2048, 1360).unwrap();
+            let image = gradient(2048, 2048);
+            let paths = tiles.write_metatile(&metatile, &image).unwrap();
+            assert_eq!(paths.len(), 64);
+            assert_eq!(paths[9], dir.join(layout.path(&metatile.tiles()[9], "png")));
+            assert!(paths.iter().all(|p| p.is_file()));
+
+            let tile = metatile.tiles()[9];
+            assert_eq!(
+                tiles.read_tile(&tile).unwrap().unwrap(),
+                split_metatile(&metatile, &image, 256).unwrap()[9].1
+            );
+            assert_eq!(
+                tiles.read_tile(&Tile::new(12, 0, 0).unwrap()).unwrap(),
+                None
+            );
+
+            // Some of the metatile, and some to the east which is missing
+            let map = StaticMap::for_centre(&metatile.se_corner(), 12, 300, 200, 256).unwrap();
+            let composed = tiles.compose(&map).unwrap();
+            assert_eq!(composed.dimensions(), (300, 200));
+            // The top left is in the metatile, the bottom right isn't
+            assert_eq!(
+                composed.get_pixel(0, 0),
+                image.get_pixel(2048 - 150, 2048 - 100)
+            );
+            assert_eq!(composed.get_pixel(149, 99), image.get_pixel(2047, 2047));
+            assert_eq!(composed.get_pixel(150, 100), &Rgba([0, 0, 0, 0]));
+
+            let (map, composed) = tiles.compose_bbox(&metatile.tiles()[9].bbox(), 12).unwrap();
+            assert_eq!((map.width(), map.height()), (256, 256));
+            assert_eq!(composed, tiles.read_tile(&tile).unwrap().unwrap());
+
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let dir = test_dir("errors");
+        let tiles = TileDir::new(&dir, TileLayout::Zxy, "png")
+            .with_tile_size(512)
+            .unwrap();
+        assert!(TileDir::new(&dir, TileLayout::Zxy, "png")
+            .with_tile_size(0)
+            .is_none());
+        let tile = Tile::new(3, 1, 2).unwrap();
+        TileDir::new(&dir, TileLayout::Zxy, "png")
+            .write_tile(&tile, &gradient(256, 256))
+            .unwrap();
+        match tiles.read_tile(&tile) {
+            Err(StitchError::WrongSize {
+                path: Some(ref path),
+                expected: (512, 512),
+                found: (256, 256),
+            }) if path == &dir.join("3/1/2.png") => {}
+            other => panic!("{:?}", other),
+        }
+
+        std::fs::write(dir.join("3/1/2.png"), b"not a png").unwrap();
+        match tiles.read_tile(&tile) {
+            Err(e @ StitchError::Image { .. }) => {
+                assert!(e.to_string().starts_with("Image error with "));
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let map = StaticMap::for_centre(&tile.centre_point(), 3, 100, 100, 256).unwrap();
+        assert!(tiles.compose(&map).is_err());
+        assert!(tiles.compose_bbox(&tile.bbox(), 32).is_err());
+
+        // Too big to allocate
+        let map = StaticMap::for_centre(&tile.centre_point(), 20, 100_000, 100_000, 512).unwrap();
+        match tiles.compose(&map) {
+            Err(StitchError::Invalid(_)) => {}
+            other => panic!("{:?}", other.map(|image| image.dimensions())),
+        }
+        let metatile = Metatile::new(8, 10, 0, 0).unwrap();
+        match split_metatile(&metatile, &gradient(256, 256), u32::MAX / 4) {
+            Err(StitchError::Invalid(_)) => {}
+            other => panic!("{:?}", other.map(|tiles| tiles.len())),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
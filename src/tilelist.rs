@@ -0,0 +1,282 @@
+//! Read & write lists of tiles & metatiles, one per line, as used by render queues and expiry
+//! tools.
+//!
+//! Each line can be:
+//!
+//! * a tile, as `Z/X/Y`, e.g. `10/511/340`
+//! * a metatile, as `SCALE Z/X/Y`, e.g. `8 10/504/336`. The scale can be followed by a tab
+//! * a tile as a [quadkey](https://docs.microsoft.com/en-us/bingmaps/articles/bing-maps-tile-system), e.g. `0231`
+//!
+//! Empty lines are ignored, as is anything after a `#`.
+//!
+//! ```
+//! # use slippy_map_tiles::Tile;
+//! # use slippy_map_tiles::tilelist::{TileListEntry, TileListReader};
+//! let list = "# expired\n10/511/340\n8 10/504/336\nnonsense\n";
+//! let entries: Vec<_> = TileListReader::new(list.as_bytes()).collect();
+//! assert_eq!(entries.len(), 3);
+//! assert_eq!(entries[0].as_ref().unwrap(), &TileListEntry::Tile(Tile::new(10, 511, 340).unwrap()));
+//! assert_eq!(entries[2].as_ref().unwrap_err().line(), 4);
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use super::{open_file, Metatile, Tile};
+
+/// One line from a tile list
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum TileListEntry {
+    Tile(Tile),
+    Metatile(Metatile),
+}
+
+impl TileListEntry {
+    /// Returns all the tiles this entry covers, i.e. just the tile, or all the tiles in the
+    /// metatile.
+    pub fn tiles(&self) -> Vec<Tile> {
+        match *self {
+            TileListEntry::Tile(t) => vec![t],
+            TileListEntry::Metatile(mt) => mt.tiles(),
+        }
+    }
+}
+
+impl From<Tile> for TileListEntry {
+    fn from(t: Tile) -> Self {
+        TileListEntry::Tile(t)
+    }
+}
+
+impl From<Metatile> for TileListEntry {
+    fn from(mt: Metatile) -> Self {
+        TileListEntry::Metatile(mt)
+    }
+}
+
+impl fmt::Display for TileListEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TileListEntry::Tile(t) => write!(f, "{}", t.zxy()),
+            TileListEntry::Metatile(mt) => {
+                write!(f, "{} {}/{}/{}", mt.scale(), mt.zoom(), mt.x(), mt.y())
+            }
+        }
+    }
+}
+
+/// Parse one line of a tile list. Returns `Ok(None)` for empty or comment only lines.
+pub fn parse_line(line: &str) -> Result<Option<TileListEntry>, &'static str> {
+    let line = match line.find('#') {
+        None => line,
+        Some(i) => &line[..i],
+    };
+    let line = line.trim();
+
+    if line.is_empty() {
+        Ok(None)
+    } else if line.contains(char::is_whitespace) {
+        // The scale can be separated from the Z/X/Y by any whitespace, e.g. tabs
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        line.parse::<Metatile>()
+            .map(|mt| Some(TileListEntry::Metatile(mt)))
+            .map_err(|_| "Invalid metatile")
+    } else if line.contains('/') {
+        line.parse::<Tile>().map(|t| Some(TileListEntry::Tile(t)))
+    } else {
+        Tile::from_quadkey(line)
+            .map(|t| Some(TileListEntry::Tile(t)))
+            .ok_or("Invalid quadkey")
+    }
+}
+
+/// An error from reading a tile list, with the line number (starting at 1) it happened on.
+#[derive(Debug)]
+pub enum TileListError {
+    /// An I/O error happened reading this line
+    Io { line: usize, error: io::Error },
+
+    /// This line couldn't be parsed
+    Parse {
+        line: usize,
+        text: String,
+        reason: &'static str,
+    },
+}
+
+impl TileListError {
+    /// The line number (starting at 1) that this error happened on
+    pub fn line(&self) -> usize {
+        match *self {
+            TileListError::Io { line, .. } => line,
+            TileListError::Parse { line, .. } => line,
+        }
+    }
+}
+
+impl fmt::Display for TileListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TileListError::Io { line, ref error } => {
+                write!(f, "I/O error at line {}: {}", line, error)
+            }
+            TileListError::Parse {
+                line,
+                ref text,
+                reason,
+            } => write!(f, "{} at line {}: {:?}", reason, line, text),
+        }
+    }
+}
+
+impl Error for TileListError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            TileListError::Io { ref error, .. } => Some(error),
+            TileListError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Reads a tile list from anything which implements `BufRead`, e.g. stdin or a (possibly
+/// decompressed) file.
+///
+/// Each unparsable line is returned as an error, and reading continues with the next line, so one
+/// bad line doesn't stop the whole list. After an I/O error, the iterator ends.
+pub struct TileListReader<R: BufRead> {
+    rdr: R,
+    line_no: usize,
+    buf: String,
+    finished: bool,
+}
+
+impl<R: BufRead> TileListReader<R> {
+    pub fn new(rdr: R) -> Self {
+        TileListReader {
+            rdr,
+            line_no: 0,
+            buf: String::new(),
+            finished: false,
+        }
+    }
+
+    /// The number of the last line read (starting at 1)
+    pub fn line_no(&self) -> usize {
+        self.line_no
+    }
+
+    /// Only return the tiles & metatiles, skipping any lines with errors.
+    pub fn ok(self) -> impl Iterator<Item = TileListEntry> {
+        self.filter_map(Result::ok)
+    }
+
+    /// Returns the inner reader
+    pub fn into_inner(self) -> R {
+        self.rdr
+    }
+}
+
+impl TileListReader<Box<dyn BufRead>> {
+    /// Opens this file as a tile list. If the file is gzip compressed, and the `gzip` feature is
+    /// enabled, it will be transparently decompressed.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(TileListReader::new(open_file(path)?))
+    }
+}
+
+impl<R: BufRead> Iterator for TileListReader<R> {
+    type Item = Result<TileListEntry, TileListError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            self.buf.clear();
+            self.line_no += 1;
+            match self.rdr.read_line(&mut self.buf) {
+                Ok(0) => {
+                    self.finished = true;
+                }
+                Err(error) => {
+                    self.finished = true;
+                    return Some(Err(TileListError::Io {
+                        line: self.line_no,
+                        error,
+                    }));
+                }
+                Ok(_) => match parse_line(&self.buf) {
+                    Ok(None) => {}
+                    Ok(Some(entry)) => return Some(Ok(entry)),
+                    Err(reason) => {
+                        return Some(Err(TileListError::Parse {
+                            line: self.line_no,
+                            text: self.buf.trim_end().to_string(),
+                            reason,
+                        }));
+                    }
+                },
+            }
+        }
+
+        None
+    }
+}
+
+/// Writes a tile list, which can be read by `TileListReader`.
+pub struct TileListWriter<W: Write> {
+    wtr: W,
+    quadkeys: bool,
+}
+
+impl<W: Write> TileListWriter<W> {
+    /// Tiles will be written as `Z/X/Y`
+    pub fn new(wtr: W) -> Self {
+        TileListWriter {
+            wtr,
+            quadkeys: false,
+        }
+    }
+
+    /// Tiles will be written as quadkeys. Metatiles are still written as `SCALE Z/X/Y`, and
+    /// `0/0/0` is written as `0/0/0`, since it's quadkey is the empty string.
+    pub fn new_quadkeys(wtr: W) -> Self {
+        TileListWriter {
+            wtr,
+            quadkeys: true,
+        }
+    }
+
+    pub fn write_tile(&mut self, tile: &Tile) -> io::Result<()> {
+        if self.quadkeys && tile.zoom() > 0 {
+            writeln!(self.wtr, "{}", tile.quadkey())
+        } else {
+            writeln!(self.wtr, "{}", tile.zxy())
+        }
+    }
+
+    pub fn write_metatile(&mut self, metatile: &Metatile) -> io::Result<()> {
+        writeln!(self.wtr, "{}", TileListEntry::Metatile(*metatile))
+    }
+
+    pub fn write_entry(&mut self, entry: &TileListEntry) -> io::Result<()> {
+        match *entry {
+            TileListEntry::Tile(ref t) => self.write_tile(t),
+            TileListEntry::Metatile(ref mt) => self.write_metatile(mt),
+        }
+    }
+
+    /// Write a comment line. Any newlines in `comment` are replaced with spaces.
+    pub fn write_comment(&mut self, comment: &str) -> io::Result<()> {
+        writeln!(self.wtr, "# {}", comment.replace(['\r', '\n'], " "))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+
+    /// Returns the inner writer
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+}
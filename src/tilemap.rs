@@ -0,0 +1,188 @@
+//! A map with `Tile` keys, which can find a tile's nearest stored ancestor, all the stored
+//! descendants of a tile, and all the stored tiles in an area.
+//!
+//! Tiles are stored in a `BTreeMap` keyed by `Tile::packed_id()`, so the descendants of a tile are
+//! one range of keys.
+//!
+//! ```
+//! # use slippy_map_tiles::Tile;
+//! # use slippy_map_tiles::tilemap::TileMap;
+//! let mut map = TileMap::new();
+//! map.insert(Tile::new(4, 8, 5).unwrap(), "a");
+//! map.insert(Tile::new(6, 33, 21).unwrap(), "b");
+//!
+//! // For overzooming, use the nearest tile we have
+//! let (tile, value) = map.nearest_ancestor(&Tile::new(10, 530, 340).unwrap()).unwrap();
+//! assert_eq!(tile, Tile::new(6, 33, 21).unwrap());
+//! assert_eq!(value, &"b");
+//!
+//! assert_eq!(map.descendants(&Tile::new(3, 4, 2).unwrap()).count(), 2);
+//! ```
+
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+use std::iter;
+use std::iter::FromIterator;
+
+use super::{zorder_to_xy, BBox, Tile, TileRange};
+
+fn tile_from_key(key: u64) -> Tile {
+    Tile::from_packed_id(key).unwrap()
+}
+
+/// A map from `Tile` to `V`, iterated in `Tile` order (i.e. depth first).
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TileMap<V> {
+    map: BTreeMap<u64, V>,
+}
+
+impl<V> Default for TileMap<V> {
+    fn default() -> Self {
+        TileMap::new()
+    }
+}
+
+impl<V> TileMap<V> {
+    /// An empty map
+    pub fn new() -> Self {
+        TileMap {
+            map: BTreeMap::new(),
+        }
+    }
+
+    /// Number of tiles stored
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// True if no tiles are stored
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Store this value for this tile. Returns the previous value, if there was one.
+    pub fn insert(&mut self, tile: Tile, value: V) -> Option<V> {
+        self.map.insert(tile.packed_id(), value)
+    }
+
+    /// The value for this tile. Values for its ancestors aren't returned, see `nearest_ancestor`.
+    pub fn get(&self, tile: &Tile) -> Option<&V> {
+        self.map.get(&tile.packed_id())
+    }
+
+    /// The value for this tile, which can be changed
+    pub fn get_mut(&mut self, tile: &Tile) -> Option<&mut V> {
+        self.map.get_mut(&tile.packed_id())
+    }
+
+    /// True if this tile is stored
+    pub fn contains_key(&self, tile: &Tile) -> bool {
+        self.map.contains_key(&tile.packed_id())
+    }
+
+    /// Remove this tile, returning its value. Descendants of this tile are not removed.
+    pub fn remove(&mut self, tile: &Tile) -> Option<V> {
+        self.map.remove(&tile.packed_id())
+    }
+
+    /// Iterate over all the tiles & values, in `Tile` order.
+    pub fn iter(&self) -> impl Iterator<Item = (Tile, &V)> + '_ {
+        self.map.iter().map(|(&k, v)| (tile_from_key(k), v))
+    }
+
+    /// Iterate over all the tiles, in `Tile` order.
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        self.map.keys().map(|&k| tile_from_key(k))
+    }
+
+    /// This tile if it's stored, otherwise the stored ancestor of this tile with the highest
+    /// zoom. None if neither the tile, nor any ancestor, is stored.
+    pub fn nearest_ancestor(&self, tile: &Tile) -> Option<(Tile, &V)> {
+        let mut tile = *tile;
+        loop {
+            if let Some(v) = self.get(&tile) {
+                return Some((tile, v));
+            }
+            tile = tile.parent()?;
+        }
+    }
+
+    /// Iterate over this tile (if stored) and all the stored tiles inside it, at any zoom, in
+    /// `Tile` order.
+    pub fn descendants(&self, tile: &Tile) -> impl Iterator<Item = (Tile, &V)> + '_ {
+        self.map
+            .range(tile.descendant_id_range(31))
+            .map(|(&k, v)| (tile_from_key(k), v))
+    }
+
+    /// Iterate over the stored tiles at this range's zoom which are in this range, in z-order.
+    pub fn in_tile_range(&self, range: &TileRange) -> impl Iterator<Item = (Tile, &V)> + '_ {
+        let zoom = range.zoom();
+        let key = move |zorder| {
+            let (x, y) = zorder_to_xy(zorder);
+            Tile::new(zoom, x, y).unwrap().packed_id()
+        };
+        // Each interval of z-orders is one range of keys, which also includes tiles at other
+        // zooms. Descendants are skipped over by jumping to the end of their subtree.
+        range
+            .zorder_ranges()
+            .into_iter()
+            .flat_map(move |(first, last)| {
+                let (mut from, to) = (key(first), key(last));
+                iter::from_fn(move || loop {
+                    if from > to {
+                        return None;
+                    }
+                    let (&k, v) = self.map.range(from..=to).next()?;
+                    let tile = tile_from_key(k);
+                    if tile.zoom() < zoom {
+                        // An ancestor of a later tile in this interval
+                        from = k + 1;
+                        continue;
+                    }
+                    let shift = tile.zoom() - zoom;
+                    let at_zoom = Tile::new(zoom, tile.x() >> shift, tile.y() >> shift).unwrap();
+                    from = at_zoom.descendant_id_range(31).end().saturating_add(1);
+                    if shift == 0 {
+                        return Some((tile, v));
+                    }
+                })
+            })
+    }
+
+    /// Iterate over the stored tiles at `zoom` which overlap this bbox, in z-order.
+    pub fn in_bbox(&self, bbox: &BBox, zoom: u8) -> impl Iterator<Item = (Tile, &V)> + '_ {
+        self.in_tile_range(&bbox.tile_range(zoom))
+    }
+}
+
+impl<V> IntoIterator for TileMap<V> {
+    type Item = (Tile, V);
+    type IntoIter = std::iter::Map<btree_map::IntoIter<u64, V>, fn((u64, V)) -> (Tile, V)>;
+
+    /// Iterate over all the tiles & values, in `Tile` order.
+    fn into_iter(self) -> Self::IntoIter {
+        fn convert<V>((k, v): (u64, V)) -> (Tile, V) {
+            (tile_from_key(k), v)
+        }
+        self.map
+            .into_iter()
+            .map(convert as fn((u64, V)) -> (Tile, V))
+    }
+}
+
+impl<V> Extend<(Tile, V)> for TileMap<V> {
+    fn extend<I: IntoIterator<Item = (Tile, V)>>(&mut self, iter: I) {
+        for (t, v) in iter {
+            self.insert(t, v);
+        }
+    }
+}
+
+impl<V> FromIterator<(Tile, V)> for TileMap<V> {
+    fn from_iter<I: IntoIterator<Item = (Tile, V)>>(iter: I) -> Self {
+        let mut map = TileMap::new();
+        map.extend(iter);
+        map
+    }
+}
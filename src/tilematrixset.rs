@@ -0,0 +1,922 @@
+//! Tile matrix sets, i.e. tile grids in other projections, as defined by the
+//! [OGC Two Dimensional Tile Matrix Set](https://docs.ogc.org/is/17-083r4/17-083r4.html) standard.
+//!
+//! The rest of this crate uses the usual slippy map grid (`WebMercatorQuad`). A `TileMatrixSet`
+//! has a CRS, and a `TileMatrix` for each zoom, with its own origin, resolution and number of tiles.
+//! Tiles are given as zoom, column (x) & row (y), since some grids don't have `2^zoom` tiles in
+//! each direction, e.g. `WorldCRS84Quad` has 2 tiles at zoom 0.
+//!
+//! `Tile::extent_in()`, `Tile::bbox_in()`, `LatLon::tile_in()` & `BBox::tiles_for_zoom_in()` do
+//! the tile maths for any tile matrix set, and are the same as `Tile::merc_bbox()`, `Tile::bbox()`,
+//! `LatLon::tile()` & `BBox::tiles_for_zoom()` for `WebMercatorQuad` (the `Default`). A `Tile` can't be more than
+//! `2^zoom` tiles from the origin, so for other grids, use the methods here.
+//!
+//! ```
+//! # use slippy_map_tiles::tilematrixset::TileMatrixSet;
+//! let tms = TileMatrixSet::world_crs84_quad();
+//! assert_eq!(tms.lat_lon_to_tile(53.35, -6.26, 0), Some((0, 0)));
+//! assert_eq!(tms.lat_lon_to_tile(53.35, 6.26, 0), Some((1, 0)));
+//!
+//! let bbox = tms.tile_bbox(1, 3, 0).unwrap();
+//! assert_eq!((bbox.left(), bbox.top(), bbox.right(), bbox.bottom()), (90., 90., 180., 0.));
+//! ```
+
+#[cfg(feature = "json")]
+use serde_json::Value;
+#[cfg(feature = "json")]
+use std::error::Error;
+#[cfg(feature = "json")]
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use super::mercator::{self, Extent, STANDARD_PIXEL_SIZE};
+use super::BBox;
+
+/// The eccentricity of the WGS84 ellipsoid
+fn wgs84_eccentricity() -> f64 {
+    let flattening: f64 = 1. / 298.257_223_563;
+    (2. * flattening - flattening * flattening).sqrt()
+}
+
+/// The coordinate reference system of a tile matrix set
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub enum Crs {
+    /// Spherical (Web) Mercator, EPSG:3857
+    WebMercator,
+    /// Longitude & latitude on WGS84, in that order, OGC:CRS84
+    Crs84,
+    /// Ellipsoidal Mercator on WGS84, EPSG:3395
+    WorldMercator,
+    /// Any other CRS, by URI. Lat/lons can't be converted to or from it.
+    Other(String),
+}
+
+impl Crs {
+    /// The OGC URI for this CRS, e.g. `http://www.opengis.net/def/crs/EPSG/0/3857`
+    pub fn uri(&self) -> &str {
+        match *self {
+            Crs::WebMercator => "http://www.opengis.net/def/crs/EPSG/0/3857",
+            Crs::Crs84 => "http://www.opengis.net/def/crs/OGC/1.3/CRS84",
+            Crs::WorldMercator => "http://www.opengis.net/def/crs/EPSG/0/3395",
+            Crs::Other(ref uri) => uri,
+        }
+    }
+
+    /// The CRS for this URI. `EPSG:3857`, `EPSG:3395` & `OGC:CRS84` are also understood.
+    pub fn from_uri(uri: &str) -> Crs {
+        let uri = uri.trim_end_matches('/');
+        match uri {
+            "http://www.opengis.net/def/crs/EPSG/0/3857" | "EPSG:3857" => Crs::WebMercator,
+            "http://www.opengis.net/def/crs/OGC/1.3/CRS84"
+            | "http://www.opengis.net/def/crs/OGC/0/CRS84"
+            | "OGC:CRS84"
+            | "CRS84" => Crs::Crs84,
+            "http://www.opengis.net/def/crs/EPSG/0/3395" | "EPSG:3395" => Crs::WorldMercator,
+            _ => Crs::Other(uri.to_string()),
+        }
+    }
+
+    /// The number of metres in one unit of this CRS, at the equator. None for `Other`.
+    pub fn meters_per_unit(&self) -> Option<f64> {
+        match *self {
+            Crs::WebMercator | Crs::WorldMercator => Some(1.),
+            Crs::Crs84 => Some(2. * std::f64::consts::PI * mercator::EARTH_RADIUS / 360.),
+            Crs::Other(_) => None,
+        }
+    }
+
+    /// Convert a lat/lon to x/y in this CRS. Latitudes outside a Mercator projection are clamped.
+    /// None for `Other`.
+    pub fn project(&self, lat: f64, lon: f64) -> Option<(f64, f64)> {
+        match *self {
+            Crs::WebMercator => Some(mercator::lat_lon_to_merc(lat, lon)),
+            Crs::Crs84 => Some((lon, lat)),
+            Crs::WorldMercator => {
+                let e = wgs84_eccentricity();
+                let max_lat = world_mercator_max_lat();
+                let lat = lat.max(-max_lat).min(max_lat).to_radians();
+                let e_sin = e * lat.sin();
+                let y = (std::f64::consts::FRAC_PI_4 + lat / 2.).tan()
+                    * ((1. - e_sin) / (1. + e_sin)).powf(e / 2.);
+
+                Some((
+                    lon.to_radians() * mercator::EARTH_RADIUS,
+                    y.ln() * mercator::EARTH_RADIUS,
+                ))
+            }
+            Crs::Other(_) => None,
+        }
+    }
+
+    /// Convert an x/y in this CRS to a lat/lon, the opposite of `project`. None for `Other`.
+    pub fn unproject(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        match *self {
+            Crs::WebMercator => Some(mercator::merc_to_lat_lon(x, y)),
+            Crs::Crs84 => Some((y, x)),
+            Crs::WorldMercator => {
+                // There's no closed form, so iterate
+                let e = wgs84_eccentricity();
+                let t = (-y / mercator::EARTH_RADIUS).exp();
+                let mut lat = std::f64::consts::FRAC_PI_2 - 2. * t.atan();
+                for _ in 0..15 {
+                    let e_sin = e * lat.sin();
+                    let next = std::f64::consts::FRAC_PI_2
+                        - 2. * (t * ((1. - e_sin) / (1. + e_sin)).powf(e / 2.)).atan();
+                    let done = (next - lat).abs() < 1e-12;
+                    lat = next;
+                    if done {
+                        break;
+                    }
+                }
+
+                Some((lat.to_degrees(), (x / mercator::EARTH_RADIUS).to_degrees()))
+            }
+            Crs::Other(_) => None,
+        }
+    }
+}
+
+/// The latitude where EPSG:3395 y is the same as the edge of the world in EPSG:3857 (~85.084°)
+fn world_mercator_max_lat() -> f64 {
+    Crs::WorldMercator
+        .unproject(0., mercator::EXTENT)
+        .unwrap()
+        .0
+}
+
+/// Which corner of a tile matrix its origin is, and so where row 0 is
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
+pub enum CornerOfOrigin {
+    /// Row 0 is at the top, and rows go down, as with slippy map tiles
+    #[default]
+    TopLeft,
+    /// Row 0 is at the bottom, and rows go up, as with TMS
+    BottomLeft,
+}
+
+/// Rows of a `TileMatrix` where `coalesce` tiles are merged into one wider tile, e.g. near the
+/// poles. The merged tile has the column of its left most tile, so only every `coalesce`th column
+/// exists in these rows.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct VariableMatrixWidth {
+    coalesce: u32,
+    min_tile_row: u32,
+    max_tile_row: u32,
+}
+
+impl VariableMatrixWidth {
+    /// Returns None if `coalesce` is 0, or `min_tile_row` is more than `max_tile_row`
+    pub fn new(coalesce: u32, min_tile_row: u32, max_tile_row: u32) -> Option<Self> {
+        if coalesce == 0 || min_tile_row > max_tile_row {
+            None
+        } else {
+            Some(VariableMatrixWidth {
+                coalesce,
+                min_tile_row,
+                max_tile_row,
+            })
+        }
+    }
+
+    pub fn coalesce(&self) -> u32 {
+        self.coalesce
+    }
+    pub fn min_tile_row(&self) -> u32 {
+        self.min_tile_row
+    }
+    pub fn max_tile_row(&self) -> u32 {
+        self.max_tile_row
+    }
+}
+
+/// The grid of tiles for one zoom of a `TileMatrixSet`
+#[derive(PartialEq, Debug, Clone)]
+pub struct TileMatrix {
+    id: String,
+    scale_denominator: f64,
+    cell_size: f64,
+    corner_of_origin: CornerOfOrigin,
+    point_of_origin: (f64, f64),
+    tile_width: u32,
+    tile_height: u32,
+    matrix_width: u32,
+    matrix_height: u32,
+    variable_matrix_widths: Vec<VariableMatrixWidth>,
+}
+
+impl TileMatrix {
+    /// A tile matrix with `matrix_width` x `matrix_height` tiles, each `tile_width` x
+    /// `tile_height` pixels. Each pixel is `cell_size` CRS units wide. `point_of_origin` is the
+    /// x/y (in CRS units) of the top left corner of the matrix.
+    ///
+    /// Returns None if there are no tiles, or `cell_size` isn't positive.
+    pub fn new(
+        id: &str,
+        scale_denominator: f64,
+        cell_size: f64,
+        point_of_origin: (f64, f64),
+        tile_size: (u32, u32),
+        matrix_size: (u32, u32),
+    ) -> Option<TileMatrix> {
+        if cell_size <= 0. || tile_size.0 == 0 || tile_size.1 == 0 {
+            return None;
+        }
+        if matrix_size.0 == 0 || matrix_size.1 == 0 {
+            return None;
+        }
+        Some(TileMatrix {
+            id: id.to_string(),
+            scale_denominator,
+            cell_size,
+            corner_of_origin: CornerOfOrigin::TopLeft,
+            point_of_origin,
+            tile_width: tile_size.0,
+            tile_height: tile_size.1,
+            matrix_width: matrix_size.0,
+            matrix_height: matrix_size.1,
+            variable_matrix_widths: Vec::new(),
+        })
+    }
+
+    /// Put the origin at this corner. `point_of_origin` is then the x/y of that corner.
+    pub fn with_corner_of_origin(mut self, corner_of_origin: CornerOfOrigin) -> Self {
+        self.corner_of_origin = corner_of_origin;
+        self
+    }
+
+    /// Merge tiles in these rows
+    pub fn with_variable_matrix_widths(
+        mut self,
+        variable_matrix_widths: Vec<VariableMatrixWidth>,
+    ) -> Self {
+        self.variable_matrix_widths = variable_matrix_widths;
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    pub fn scale_denominator(&self) -> f64 {
+        self.scale_denominator
+    }
+    /// The size of a pixel in CRS units
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+    pub fn corner_of_origin(&self) -> CornerOfOrigin {
+        self.corner_of_origin
+    }
+    pub fn point_of_origin(&self) -> (f64, f64) {
+        self.point_of_origin
+    }
+    /// The width & height of a tile in pixels
+    pub fn tile_size(&self) -> (u32, u32) {
+        (self.tile_width, self.tile_height)
+    }
+    /// The number of columns & rows of tiles
+    pub fn matrix_size(&self) -> (u32, u32) {
+        (self.matrix_width, self.matrix_height)
+    }
+    pub fn variable_matrix_widths(&self) -> &[VariableMatrixWidth] {
+        &self.variable_matrix_widths
+    }
+
+    /// The number of columns merged into one tile in this row
+    fn coalesce(&self, row: u32) -> u32 {
+        self.variable_matrix_widths
+            .iter()
+            .find(|v| v.min_tile_row <= row && row <= v.max_tile_row)
+            .map_or(1, |v| v.coalesce)
+    }
+
+    /// The width & height of a tile in CRS units
+    pub fn tile_span(&self) -> (f64, f64) {
+        (
+            self.cell_size * self.tile_width as f64,
+            self.cell_size * self.tile_height as f64,
+        )
+    }
+
+    /// The extent of this tile in CRS units. None if it's not in this matrix, or it's been merged
+    /// into the tile on its left (see `VariableMatrixWidth`).
+    // `is_multiple_of` needs a much newer Rust than the rest of the crate
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn tile_extent(&self, col: u32, row: u32) -> Option<Extent> {
+        if col >= self.matrix_width || row >= self.matrix_height {
+            return None;
+        }
+        let coalesce = self.coalesce(row);
+        if col % coalesce != 0 {
+            return None;
+        }
+        let (span_x, span_y) = self.tile_span();
+        let width = span_x * std::cmp::min(coalesce, self.matrix_width - col) as f64;
+        let (origin_x, origin_y) = self.point_of_origin;
+        let min_x = origin_x + col as f64 * span_x;
+        let min_y = match self.corner_of_origin {
+            CornerOfOrigin::TopLeft => origin_y - (row + 1) as f64 * span_y,
+            CornerOfOrigin::BottomLeft => origin_y + row as f64 * span_y,
+        };
+
+        Extent::new(min_x, min_y, min_x + width, min_y + span_y)
+    }
+
+    /// The extent of all the tiles in this matrix
+    pub fn extent(&self) -> Extent {
+        let (span_x, span_y) = self.tile_span();
+        let (origin_x, origin_y) = self.point_of_origin;
+        let width = span_x * self.matrix_width as f64;
+        let height = span_y * self.matrix_height as f64;
+        let min_y = match self.corner_of_origin {
+            CornerOfOrigin::TopLeft => origin_y - height,
+            CornerOfOrigin::BottomLeft => origin_y,
+        };
+
+        Extent::new(origin_x, min_y, origin_x + width, min_y + height).unwrap()
+    }
+
+    /// The column & row (which may be outside the matrix) of the point `x`,`y`
+    fn col_row(&self, x: f64, y: f64) -> (f64, f64) {
+        let (span_x, span_y) = self.tile_span();
+        let (origin_x, origin_y) = self.point_of_origin;
+        let row = match self.corner_of_origin {
+            CornerOfOrigin::TopLeft => (origin_y - y) / span_y,
+            CornerOfOrigin::BottomLeft => (y - origin_y) / span_y,
+        };
+
+        ((x - origin_x) / span_x, row)
+    }
+
+    /// The column & row of the tile which this point (in CRS units) is in. Points on the
+    /// right/bottom edge of the matrix are in the last tile. None if it's outside this matrix.
+    pub fn tile_at(&self, x: f64, y: f64) -> Option<(u32, u32)> {
+        let (col, row) = self.col_row(x, y);
+        let col = cell_index(col, self.matrix_width)?;
+        let row = cell_index(row, self.matrix_height)?;
+        let coalesce = self.coalesce(row);
+
+        Some((col / coalesce * coalesce, row))
+    }
+
+    /// The columns & rows of the tiles which overlap this extent (in CRS units). None if it's
+    /// outside this matrix. Merged tiles (see `VariableMatrixWidth`) aren't taken into account,
+    /// see `tiles` for that.
+    pub fn tile_ranges(
+        &self,
+        extent: &Extent,
+    ) -> Option<(RangeInclusive<u32>, RangeInclusive<u32>)> {
+        let (col1, row1) = self.col_row(extent.min_x(), extent.min_y());
+        let (col2, row2) = self.col_row(extent.max_x(), extent.max_y());
+        let cols = cell_span(col1.min(col2), col1.max(col2), self.matrix_width)?;
+        let rows = cell_span(row1.min(row2), row1.max(row2), self.matrix_height)?;
+
+        Some((cols, rows))
+    }
+
+    /// The column & row of each tile which overlaps this extent (in CRS units), row by row.
+    pub fn tiles<'a>(&'a self, extent: &Extent) -> impl Iterator<Item = (u32, u32)> + 'a {
+        self.tile_ranges(extent)
+            .into_iter()
+            .flat_map(move |(cols, rows)| {
+                rows.flat_map(move |row| {
+                    let coalesce = self.coalesce(row);
+                    let first = cols.start() / coalesce * coalesce;
+                    (first..=*cols.end())
+                        .step_by(coalesce as usize)
+                        .map(move |col| (col, row))
+                })
+            })
+    }
+}
+
+/// The cell which position `pos` is in, if it's in `0..=size`
+fn cell_index(pos: f64, size: u32) -> Option<u32> {
+    if !(0. ..=size as f64).contains(&pos) {
+        None
+    } else {
+        Some((pos.floor() as u32).min(size - 1))
+    }
+}
+
+/// The cells which overlap `start..end`
+fn cell_span(start: f64, end: f64, size: u32) -> Option<RangeInclusive<u32>> {
+    if end < 0. || start > size as f64 {
+        return None;
+    }
+    let first = start.max(0.).floor() as u32;
+    let last = (end.min(size as f64).ceil() as u32).max(first + 1) - 1;
+
+    Some(first.min(size - 1)..=last.min(size - 1))
+}
+
+/// A set of tile matrices, one per zoom, in one CRS
+#[derive(PartialEq, Debug, Clone)]
+pub struct TileMatrixSet {
+    id: String,
+    title: Option<String>,
+    uri: Option<String>,
+    crs: Crs,
+    ordered_axes: Option<(String, String)>,
+    tile_matrices: Vec<TileMatrix>,
+}
+
+/// `WebMercatorQuad`, the grid the rest of the crate uses
+impl Default for TileMatrixSet {
+    fn default() -> Self {
+        TileMatrixSet::web_mercator_quad()
+    }
+}
+
+impl TileMatrixSet {
+    /// A tile matrix set, with `tile_matrices[zoom]` for each zoom. Returns None if there are no
+    /// tile matrices, or more than 32.
+    pub fn new(id: &str, crs: Crs, tile_matrices: Vec<TileMatrix>) -> Option<TileMatrixSet> {
+        if tile_matrices.is_empty() || tile_matrices.len() > 32 {
+            return None;
+        }
+        Some(TileMatrixSet {
+            id: id.to_string(),
+            title: None,
+            uri: None,
+            crs,
+            ordered_axes: None,
+            tile_matrices,
+        })
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// The URI which identifies this tile matrix set
+    pub fn with_uri(mut self, uri: &str) -> Self {
+        self.uri = Some(uri.to_string());
+        self
+    }
+
+    /// The names of the CRS axes, in the CRS's order, e.g. `("Lat", "Lon")`. If the first one is
+    /// latitude or northing, points are in y/x order in JSON. Points in this crate are always x/y.
+    pub fn with_ordered_axes(mut self, first: &str, second: &str) -> Self {
+        self.ordered_axes = Some((first.to_string(), second.to_string()));
+        self
+    }
+
+    /// A quadtree, where each zoom has twice as many columns & rows as the one before
+    fn quad(
+        id: &str,
+        crs: Crs,
+        origin: (f64, f64),
+        zoom_0_cell_size: f64,
+        zoom_0_matrix_size: (u32, u32),
+        max_zoom: u8,
+    ) -> TileMatrixSet {
+        let meters_per_unit = crs.meters_per_unit().unwrap();
+        let tile_matrices = (0..=max_zoom)
+            .map(|zoom| {
+                let cell_size = zoom_0_cell_size / 2f64.powi(zoom as i32);
+                TileMatrix::new(
+                    &zoom.to_string(),
+                    cell_size * meters_per_unit / STANDARD_PIXEL_SIZE,
+                    cell_size,
+                    origin,
+                    (256, 256),
+                    (zoom_0_matrix_size.0 << zoom, zoom_0_matrix_size.1 << zoom),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        TileMatrixSet::new(id, crs, tile_matrices)
+            .unwrap()
+            .with_uri(&format!(
+                "http://www.opengis.net/def/tilematrixset/OGC/1.0/{}",
+                id
+            ))
+    }
+
+    /// The usual slippy map grid in Web Mercator (EPSG:3857), zooms 0 to 24
+    pub fn web_mercator_quad() -> TileMatrixSet {
+        TileMatrixSet::quad(
+            "WebMercatorQuad",
+            Crs::WebMercator,
+            (-mercator::EXTENT, mercator::EXTENT),
+            2. * mercator::EXTENT / 256.,
+            (1, 1),
+            24,
+        )
+        .with_title("Google Maps Compatible for the World")
+        .with_ordered_axes("X", "Y")
+    }
+
+    /// Lon/lat (OGC:CRS84) with 2 tiles at zoom 0, zooms 0 to 23
+    pub fn world_crs84_quad() -> TileMatrixSet {
+        TileMatrixSet::quad(
+            "WorldCRS84Quad",
+            Crs::Crs84,
+            (-180., 90.),
+            180. / 256.,
+            (2, 1),
+            23,
+        )
+        .with_title("CRS84 for the World")
+        .with_ordered_axes("Lon", "Lat")
+    }
+
+    /// Ellipsoidal World Mercator (EPSG:3395), as used by Yandex, zooms 0 to 24
+    pub fn world_mercator_wgs84_quad() -> TileMatrixSet {
+        TileMatrixSet::quad(
+            "WorldMercatorWGS84Quad",
+            Crs::WorldMercator,
+            (-mercator::EXTENT, mercator::EXTENT),
+            2. * mercator::EXTENT / 256.,
+            (1, 1),
+            24,
+        )
+        .with_title("World Mercator WGS84 (ellipsoid)")
+        .with_ordered_axes("E", "N")
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+    pub fn uri(&self) -> Option<&str> {
+        self.uri.as_deref()
+    }
+    pub fn crs(&self) -> &Crs {
+        &self.crs
+    }
+    pub fn ordered_axes(&self) -> Option<(&str, &str)> {
+        self.ordered_axes
+            .as_ref()
+            .map(|(first, second)| (first.as_str(), second.as_str()))
+    }
+
+    /// True if the CRS axes are y then x, e.g. latitude then longitude
+    #[cfg(feature = "json")]
+    fn y_first(&self) -> bool {
+        self.ordered_axes().is_some_and(|(first, _)| {
+            ["lat", "latitude", "y", "n", "northing"].contains(&first.to_lowercase().as_str())
+        })
+    }
+
+    pub fn tile_matrices(&self) -> &[TileMatrix] {
+        &self.tile_matrices
+    }
+    /// The tile matrix for this zoom
+    pub fn tile_matrix(&self, zoom: u8) -> Option<&TileMatrix> {
+        self.tile_matrices.get(zoom as usize)
+    }
+    pub fn max_zoom(&self) -> u8 {
+        (self.tile_matrices.len() - 1) as u8
+    }
+
+    /// The extent of this tile in CRS units. None if the tile doesn't exist.
+    pub fn tile_extent(&self, zoom: u8, col: u32, row: u32) -> Option<Extent> {
+        self.tile_matrix(zoom)?.tile_extent(col, row)
+    }
+
+    /// The lat/lon bbox of this tile. None if the tile doesn't exist, or the CRS is `Other`.
+    pub fn tile_bbox(&self, zoom: u8, col: u32, row: u32) -> Option<BBox> {
+        let extent = self.tile_extent(zoom, col, row)?;
+        let (bottom, left) = self.crs.unproject(extent.min_x(), extent.min_y())?;
+        let (top, right) = self.crs.unproject(extent.max_x(), extent.max_y())?;
+
+        BBox::new(top as f32, left as f32, bottom as f32, right as f32)
+    }
+
+    /// The column & row of the tile at this zoom which this lat/lon is in. None if the CRS is
+    /// `Other`, or it's outside the tile matrix.
+    pub fn lat_lon_to_tile(&self, lat: f64, lon: f64, zoom: u8) -> Option<(u32, u32)> {
+        let (x, y) = self.crs.project(lat, lon)?;
+        self.tile_matrix(zoom)?.tile_at(x, y)
+    }
+
+    /// The extent of this bbox in CRS units. None if the CRS is `Other`, or the bbox goes over the
+    /// antimeridian (or the top is below the bottom), since an `Extent` can't.
+    pub fn bbox_extent(&self, bbox: &BBox) -> Option<Extent> {
+        let (min_x, min_y) = self.crs.project(bbox.bottom() as f64, bbox.left() as f64)?;
+        let (max_x, max_y) = self.crs.project(bbox.top() as f64, bbox.right() as f64)?;
+
+        Extent::new(min_x, min_y, max_x, max_y)
+    }
+
+    /// The columns & rows of the tiles at this zoom which overlap this bbox. None if the CRS is
+    /// `Other`, the bbox goes over the antimeridian, or the bbox is outside the tile matrix.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::BBox;
+    /// # use slippy_map_tiles::tilematrixset::TileMatrixSet;
+    /// let bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+    /// let tms = TileMatrixSet::world_crs84_quad();
+    /// assert_eq!(tms.tile_ranges_for_bbox(&bbox, 5), Some((29..=31, 6..=6)));
+    /// ```
+    pub fn tile_ranges_for_bbox(
+        &self,
+        bbox: &BBox,
+        zoom: u8,
+    ) -> Option<(RangeInclusive<u32>, RangeInclusive<u32>)> {
+        let extent = self.bbox_extent(bbox)?;
+        self.tile_matrix(zoom)?.tile_ranges(&extent)
+    }
+
+    /// The column & row of each tile at this zoom which overlaps this bbox, row by row. Nothing if
+    /// the CRS is `Other`, or the bbox goes over the antimeridian.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::BBox;
+    /// # use slippy_map_tiles::tilematrixset::TileMatrixSet;
+    /// let bbox = BBox::new(55.7, -11.32, 51.11, -4.97).unwrap();
+    /// let tms = TileMatrixSet::world_crs84_quad();
+    /// let tiles: Vec<_> = tms.tiles_for_bbox(&bbox, 5).collect();
+    /// assert_eq!(tiles, vec![(29, 6), (30, 6), (31, 6)]);
+    /// ```
+    pub fn tiles_for_bbox<'a>(
+        &'a self,
+        bbox: &BBox,
+        zoom: u8,
+    ) -> impl Iterator<Item = (u32, u32)> + 'a {
+        let extent = self.bbox_extent(bbox);
+        self.tile_matrix(zoom)
+            .into_iter()
+            .zip(extent)
+            .flat_map(|(matrix, extent)| matrix.tiles(&extent))
+    }
+}
+
+/// An error reading a tile matrix set from JSON
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub enum JsonError {
+    /// The text isn't JSON
+    Json(serde_json::Error),
+    /// The JSON isn't a valid tile matrix set. The message says what's wrong.
+    Invalid(String),
+}
+
+#[cfg(feature = "json")]
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JsonError::Json(ref error) => write!(f, "Invalid JSON: {}", error),
+            JsonError::Invalid(ref msg) => write!(f, "Invalid tile matrix set: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            JsonError::Json(ref error) => Some(error),
+            JsonError::Invalid(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for JsonError {
+    fn from(error: serde_json::Error) -> Self {
+        JsonError::Json(error)
+    }
+}
+
+#[cfg(feature = "json")]
+fn invalid<T>(msg: &str) -> Result<T, JsonError> {
+    Err(JsonError::Invalid(msg.to_string()))
+}
+
+#[cfg(feature = "json")]
+fn json_str<'a>(object: &'a Value, key: &str) -> Result<Option<&'a str>, JsonError> {
+    match object.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(ref s)) => Ok(Some(s)),
+        Some(_) => invalid(&format!("{} isn't a string", key)),
+    }
+}
+
+#[cfg(feature = "json")]
+fn json_f64(object: &Value, key: &str) -> Result<Option<f64>, JsonError> {
+    match object.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => match v.as_f64() {
+            Some(n) => Ok(Some(n)),
+            None => invalid(&format!("{} isn't a number", key)),
+        },
+    }
+}
+
+#[cfg(feature = "json")]
+fn json_u32(object: &Value, key: &str) -> Result<u32, JsonError> {
+    match object.get(key).and_then(|v| v.as_u64()) {
+        Some(n) if n <= u32::MAX as u64 => Ok(n as u32),
+        _ => invalid(&format!("{} is missing, or isn't a whole number", key)),
+    }
+}
+
+#[cfg(feature = "json")]
+impl TileMatrixSet {
+    /// Read a tile matrix set from an OGC Two Dimensional Tile Matrix Set (version 2) JSON
+    /// document.
+    ///
+    /// The tile matrices are the zooms, in the order they are in the document. If `cellSize` is
+    /// missing, it's calculated from `scaleDenominator` (for known CRSs). Other members, e.g.
+    /// `boundingBox`, are ignored.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::tilematrixset::{Crs, TileMatrixSet};
+    /// let tms = TileMatrixSet::from_json(r#"{
+    ///     "id": "Local",
+    ///     "crs": "http://www.opengis.net/def/crs/EPSG/0/2157",
+    ///     "tileMatrices": [{
+    ///         "id": "0", "scaleDenominator": 357142.86, "cellSize": 100,
+    ///         "pointOfOrigin": [400000, 1000000],
+    ///         "tileWidth": 256, "tileHeight": 256, "matrixWidth": 4, "matrixHeight": 5
+    ///     }]
+    /// }"#).unwrap();
+    /// assert_eq!(tms.crs().uri(), "http://www.opengis.net/def/crs/EPSG/0/2157");
+    /// assert_eq!(tms.tile_extent(0, 1, 0).unwrap().min_x(), 425600.);
+    /// ```
+    pub fn from_json(json: &str) -> Result<TileMatrixSet, JsonError> {
+        let doc: Value = serde_json::from_str(json)?;
+        if !doc.is_object() {
+            return invalid("not an object");
+        }
+
+        let id = match json_str(&doc, "id")? {
+            Some(id) => id,
+            None => return invalid("id is missing"),
+        };
+        let crs = match doc.get("crs") {
+            Some(Value::String(ref uri)) => Crs::from_uri(uri),
+            Some(crs) => match json_str(crs, "uri")? {
+                Some(uri) => Crs::from_uri(uri),
+                None => return invalid("crs has no uri"),
+            },
+            None => return invalid("crs is missing"),
+        };
+        let ordered_axes = match doc.get("orderedAxes") {
+            None | Some(Value::Null) => None,
+            Some(axes) => match axes.as_array().map(|a| a.as_slice()) {
+                Some([Value::String(ref first), Value::String(ref second)]) => {
+                    Some((first.clone(), second.clone()))
+                }
+                _ => return invalid("orderedAxes isn't 2 strings"),
+            },
+        };
+
+        let tile_matrices = match doc.get("tileMatrices").and_then(|m| m.as_array()) {
+            Some(m) => m,
+            None => return invalid("tileMatrices is missing"),
+        };
+        let mut tms = TileMatrixSet {
+            id: id.to_string(),
+            title: json_str(&doc, "title")?.map(|t| t.to_string()),
+            uri: json_str(&doc, "uri")?.map(|u| u.to_string()),
+            crs,
+            ordered_axes,
+            tile_matrices: Vec::new(),
+        };
+        if tile_matrices.is_empty() || tile_matrices.len() > 32 {
+            return invalid("there must be 1 to 32 tileMatrices");
+        }
+        for matrix in tile_matrices.iter() {
+            let tile_matrix = tms.tile_matrix_from_json(matrix)?;
+            tms.tile_matrices.push(tile_matrix);
+        }
+
+        Ok(tms)
+    }
+
+    fn tile_matrix_from_json(&self, matrix: &Value) -> Result<TileMatrix, JsonError> {
+        let id = match json_str(matrix, "id")? {
+            Some(id) => id,
+            None => return invalid("tile matrix id is missing"),
+        };
+        let scale_denominator = match json_f64(matrix, "scaleDenominator")? {
+            Some(s) => s,
+            None => return invalid("scaleDenominator is missing"),
+        };
+        let cell_size = match json_f64(matrix, "cellSize")? {
+            Some(c) => c,
+            None => match self.crs.meters_per_unit() {
+                Some(m) => scale_denominator * STANDARD_PIXEL_SIZE / m,
+                None => return invalid("cellSize is missing"),
+            },
+        };
+        let corner_of_origin = match json_str(matrix, "cornerOfOrigin")? {
+            None | Some("topLeft") => CornerOfOrigin::TopLeft,
+            Some("bottomLeft") => CornerOfOrigin::BottomLeft,
+            Some(_) => return invalid("unknown cornerOfOrigin"),
+        };
+        let point_of_origin = match matrix.get("pointOfOrigin").and_then(|p| p.as_array()) {
+            Some(p) if p.len() == 2 && p.iter().all(|n| n.is_number()) => {
+                let (a, b) = (p[0].as_f64().unwrap(), p[1].as_f64().unwrap());
+                if self.y_first() {
+                    (b, a)
+                } else {
+                    (a, b)
+                }
+            }
+            _ => return invalid("pointOfOrigin isn't 2 numbers"),
+        };
+        let tile_size = (
+            json_u32(matrix, "tileWidth")?,
+            json_u32(matrix, "tileHeight")?,
+        );
+        let matrix_size = (
+            json_u32(matrix, "matrixWidth")?,
+            json_u32(matrix, "matrixHeight")?,
+        );
+
+        let mut variable_matrix_widths = Vec::new();
+        if let Some(widths) = matrix.get("variableMatrixWidths") {
+            let widths = match widths.as_array() {
+                Some(w) => w,
+                None => return invalid("variableMatrixWidths isn't an array"),
+            };
+            for width in widths.iter() {
+                match VariableMatrixWidth::new(
+                    json_u32(width, "coalesce")?,
+                    json_u32(width, "minTileRow")?,
+                    json_u32(width, "maxTileRow")?,
+                ) {
+                    Some(w) => variable_matrix_widths.push(w),
+                    None => return invalid("invalid variableMatrixWidth"),
+                }
+            }
+        }
+
+        match TileMatrix::new(
+            id,
+            scale_denominator,
+            cell_size,
+            point_of_origin,
+            tile_size,
+            matrix_size,
+        ) {
+            Some(m) => Ok(m
+                .with_corner_of_origin(corner_of_origin)
+                .with_variable_matrix_widths(variable_matrix_widths)),
+            None => invalid(&format!("tile matrix {} has no tiles, or no cellSize", id)),
+        }
+    }
+
+    /// This tile matrix set as an OGC Two Dimensional Tile Matrix Set (version 2) JSON document
+    pub fn to_json(&self) -> String {
+        let tile_matrices: Vec<Value> = self
+            .tile_matrices
+            .iter()
+            .map(|m| {
+                let (x, y) = m.point_of_origin;
+                let point_of_origin = if self.y_first() { [y, x] } else { [x, y] };
+                let mut matrix = json!({
+                    "id": m.id,
+                    "scaleDenominator": m.scale_denominator,
+                    "cellSize": m.cell_size,
+                    "cornerOfOrigin": match m.corner_of_origin {
+                        CornerOfOrigin::TopLeft => "topLeft",
+                        CornerOfOrigin::BottomLeft => "bottomLeft",
+                    },
+                    "pointOfOrigin": point_of_origin,
+                    "tileWidth": m.tile_width,
+                    "tileHeight": m.tile_height,
+                    "matrixWidth": m.matrix_width,
+                    "matrixHeight": m.matrix_height,
+                });
+                if !m.variable_matrix_widths.is_empty() {
+                    matrix["variableMatrixWidths"] = m
+                        .variable_matrix_widths
+                        .iter()
+                        .map(|v| {
+                            json!({
+                                "coalesce": v.coalesce,
+                                "minTileRow": v.min_tile_row,
+                                "maxTileRow": v.max_tile_row,
+                            })
+                        })
+                        .collect();
+                }
+                matrix
+            })
+            .collect();
+
+        let mut doc = json!({ "id": self.id });
+        if let Some(ref title) = self.title {
+            doc["title"] = json!(title);
+        }
+        if let Some(ref uri) = self.uri {
+            doc["uri"] = json!(uri);
+        }
+        doc["crs"] = json!(self.crs.uri());
+        if let Some((ref first, ref second)) = self.ordered_axes {
+            doc["orderedAxes"] = json!([first, second]);
+        }
+        doc["tileMatrices"] = Value::Array(tile_matrices);
+
+        serde_json::to_string_pretty(&doc).unwrap()
+    }
+}
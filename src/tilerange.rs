@@ -0,0 +1,714 @@
+//! Rectangular ranges of tiles at one zoom level.
+
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use super::{
+    hilbert_to_xy, lat_lon_to_tile, xy_to_zorder, zorder_bigmin, zorder_litmax, zorder_to_xy, BBox,
+    Tile, TileOrder,
+};
+
+/// All the tiles at one zoom, with x from `x_min` to `x_max` (inclusive), and y from `y_min` to
+/// `y_max` (inclusive).
+///
+/// ```
+/// # use slippy_map_tiles::{Tile, TileRange};
+/// let range: TileRange = "3/1-2/5-7".parse().unwrap();
+/// assert_eq!(range.count(), 6);
+/// assert!(range.contains(&Tile::new(3, 2, 6).unwrap()));
+/// assert_eq!(range.to_string(), "3/1-2/5-7");
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct TileRange {
+    zoom: u8,
+    x_min: u32,
+    x_max: u32,
+    y_min: u32,
+    y_max: u32,
+}
+
+impl TileRange {
+    /// Constructs a TileRange. Returns None if a min is greater than it's max, or the x/y are
+    /// invalid for this zoom.
+    pub fn new(zoom: u8, x_min: u32, x_max: u32, y_min: u32, y_max: u32) -> Option<TileRange> {
+        if zoom >= 32 || x_min > x_max || y_min > y_max {
+            return None;
+        }
+        let max_tile_no = (1u64 << zoom) - 1;
+        if x_max as u64 > max_tile_no || y_max as u64 > max_tile_no {
+            return None;
+        }
+
+        Some(TileRange {
+            zoom,
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+        })
+    }
+
+    /// All the tiles at this zoom
+    pub fn whole_world(zoom: u8) -> Option<TileRange> {
+        if zoom >= 32 {
+            return None;
+        }
+        let max_tile_no = ((1u64 << zoom) - 1) as u32;
+        TileRange::new(zoom, 0, max_tile_no, 0, max_tile_no)
+    }
+
+    /// The range with just this one tile
+    pub fn from_tile(tile: &Tile) -> TileRange {
+        TileRange {
+            zoom: tile.zoom(),
+            x_min: tile.x(),
+            x_max: tile.x(),
+            y_min: tile.y(),
+            y_max: tile.y(),
+        }
+    }
+
+    /// The tiles at this zoom which cover this bbox. Panics if `zoom` is 32 or more.
+    pub fn from_bbox(bbox: &BBox, zoom: u8) -> TileRange {
+        assert!(zoom < 32);
+        let max_tile_no = ((1u64 << zoom) - 1) as u32;
+        let (x1, y1) = lat_lon_to_tile(bbox.top(), bbox.left(), zoom);
+        let (x2, y2) = lat_lon_to_tile(bbox.bottom(), bbox.right(), zoom);
+        // A point on the right or bottom edge of the world is in a tile that doesn't exist
+        let (x1, y1) = (x1.min(max_tile_no), y1.min(max_tile_no));
+        let (x2, y2) = (x2.min(max_tile_no), y2.min(max_tile_no));
+
+        TileRange {
+            zoom,
+            x_min: x1.min(x2),
+            x_max: x1.max(x2),
+            y_min: y1.min(y2),
+            y_max: y1.max(y2),
+        }
+    }
+
+    /// The BBox which this range covers
+    pub fn to_bbox(&self) -> BBox {
+        let nw = Tile::new(self.zoom, self.x_min, self.y_min)
+            .unwrap()
+            .nw_corner();
+        let se = Tile::new(self.zoom, self.x_max, self.y_max)
+            .unwrap()
+            .se_corner();
+        BBox::new_from_points(&nw, &se)
+    }
+
+    pub fn zoom(&self) -> u8 {
+        self.zoom
+    }
+
+    pub fn x_min(&self) -> u32 {
+        self.x_min
+    }
+
+    pub fn x_max(&self) -> u32 {
+        self.x_max
+    }
+
+    pub fn y_min(&self) -> u32 {
+        self.y_min
+    }
+
+    pub fn y_max(&self) -> u32 {
+        self.y_max
+    }
+
+    /// Number of tiles across
+    pub fn width(&self) -> u32 {
+        self.x_max - self.x_min + 1
+    }
+
+    /// Number of tiles down
+    pub fn height(&self) -> u32 {
+        self.y_max - self.y_min + 1
+    }
+
+    /// Number of tiles in this range. This can't overflow, since there are at most 2^62 tiles at
+    /// a zoom.
+    pub fn count(&self) -> u64 {
+        self.width() as u64 * self.height() as u64
+    }
+
+    /// True iff this tile is in this range
+    pub fn contains(&self, tile: &Tile) -> bool {
+        tile.zoom() == self.zoom
+            && self.x_min <= tile.x()
+            && tile.x() <= self.x_max
+            && self.y_min <= tile.y()
+            && tile.y() <= self.y_max
+    }
+
+    /// True iff all of `other` is in this range. Always false for different zooms.
+    pub fn contains_range(&self, other: &TileRange) -> bool {
+        self.zoom == other.zoom
+            && self.x_min <= other.x_min
+            && other.x_max <= self.x_max
+            && self.y_min <= other.y_min
+            && other.y_max <= self.y_max
+    }
+
+    /// The tiles which are in both ranges. None if they don't overlap, or are at different zooms.
+    pub fn intersection(&self, other: &TileRange) -> Option<TileRange> {
+        if self.zoom != other.zoom {
+            return None;
+        }
+        TileRange::new(
+            self.zoom,
+            self.x_min.max(other.x_min),
+            self.x_max.min(other.x_max),
+            self.y_min.max(other.y_min),
+            self.y_max.min(other.y_max),
+        )
+    }
+
+    /// The smallest range which includes both ranges. None if they are at different zooms.
+    pub fn union_bounds(&self, other: &TileRange) -> Option<TileRange> {
+        if self.zoom != other.zoom {
+            return None;
+        }
+        TileRange::new(
+            self.zoom,
+            self.x_min.min(other.x_min),
+            self.x_max.max(other.x_max),
+            self.y_min.min(other.y_min),
+            self.y_max.max(other.y_max),
+        )
+    }
+
+    /// The range at `zoom - 1` which covers this range. None at zoom 0.
+    pub fn parent_range(&self) -> Option<TileRange> {
+        if self.zoom == 0 {
+            return None;
+        }
+        self.at_zoom(self.zoom - 1)
+    }
+
+    /// The range at `zoom + 1` which covers this range. None if that zoom is too large.
+    pub fn child_range(&self) -> Option<TileRange> {
+        self.at_zoom(self.zoom + 1)
+    }
+
+    /// The range at `zoom` which covers the same area as this range. For lower zooms, this range
+    /// will be inside the new range. None if the zoom is too large.
+    pub fn at_zoom(&self, zoom: u8) -> Option<TileRange> {
+        if zoom >= 32 {
+            None
+        } else if zoom <= self.zoom {
+            let shift = self.zoom - zoom;
+            TileRange::new(
+                zoom,
+                self.x_min >> shift,
+                self.x_max >> shift,
+                self.y_min >> shift,
+                self.y_max >> shift,
+            )
+        } else {
+            let shift = zoom - self.zoom;
+            let scale = (1u64 << shift) as u32;
+            TileRange::new(
+                zoom,
+                self.x_min << shift,
+                (self.x_max << shift) + (scale - 1),
+                self.y_min << shift,
+                (self.y_max << shift) + (scale - 1),
+            )
+        }
+    }
+
+    /// Iterate over all the tiles in this range, going down each column (all the y's for the
+    /// first x, then the next x), like `BBox::tiles_for_zoom`.
+    pub fn iter(&self) -> TileRangeIter {
+        TileRangeIter {
+            range: *self,
+            next_x: self.x_min,
+            next_y: self.y_min,
+            remaining: self.count(),
+        }
+    }
+
+    /// Iterate over all the tiles in this range, in the order of the Hilbert curve for this
+    /// zoom (see `xy_to_hilbert`).
+    pub fn iter_hilbert(&self) -> TileRangeHilbertIter {
+        TileRangeHilbertIter {
+            range: *self,
+            next_index: Some(0),
+            remaining: self.count(),
+        }
+    }
+
+    /// Returns the smallest Hilbert index (see `xy_to_hilbert`), which is greater than or equal
+    /// to `from`, and is in this range. None if there are none.
+    ///
+    /// This can be used to skip over parts of the Hilbert curve which are outside this range.
+    pub fn next_hilbert_index(&self, from: u64) -> Option<u64> {
+        // Fast path, for when we are going along the curve inside the range
+        if from < (1u64 << (2 * self.zoom as u64)) {
+            let (x, y) = hilbert_to_xy(from, self.zoom);
+            if self.x_min <= x && x <= self.x_max && self.y_min <= y && y <= self.y_max {
+                return Some(from);
+            }
+        }
+
+        self.next_hilbert_index_in(0, 0, from)
+    }
+
+    /// Look for the next Hilbert index, in the cell at `depth` with Hilbert index `index`
+    fn next_hilbert_index_in(&self, depth: u8, index: u64, from: u64) -> Option<u64> {
+        let shift = self.zoom - depth;
+        let first = index << (2 * shift as u64);
+        let last = first + ((1u64 << (2 * shift as u64)) - 1);
+        if last < from {
+            return None;
+        }
+
+        let (x, y) = hilbert_to_xy(index, depth);
+        let (x1, y1) = ((x as u64) << shift, (y as u64) << shift);
+        let (x2, y2) = (
+            (((x as u64) + 1) << shift) - 1,
+            (((y as u64) + 1) << shift) - 1,
+        );
+        if x2 < self.x_min as u64
+            || x1 > self.x_max as u64
+            || y2 < self.y_min as u64
+            || y1 > self.y_max as u64
+        {
+            // Not in the range
+            return None;
+        }
+
+        if x1 >= self.x_min as u64
+            && x2 <= self.x_max as u64
+            && y1 >= self.y_min as u64
+            && y2 <= self.y_max as u64
+        {
+            // This cell is completely inside the range, so everything in it is OK
+            return Some(first.max(from));
+        }
+
+        (0..4).find_map(|i| self.next_hilbert_index_in(depth + 1, index * 4 + i, from))
+    }
+
+    /// The z-order (see `xy_to_zorder`) of the top left tile, which is the smallest z-order in
+    /// this range.
+    pub fn min_zorder(&self) -> u64 {
+        xy_to_zorder(self.x_min, self.y_min)
+    }
+
+    /// The z-order of the bottom right tile, which is the largest z-order in this range.
+    pub fn max_zorder(&self) -> u64 {
+        xy_to_zorder(self.x_max, self.y_max)
+    }
+
+    /// True iff the tile with this z-order is in this range
+    pub fn contains_zorder(&self, zorder: u64) -> bool {
+        let (x, y) = zorder_to_xy(zorder);
+        zorder < (1u64 << (2 * self.zoom as u64))
+            && self.x_min <= x
+            && x <= self.x_max
+            && self.y_min <= y
+            && y <= self.y_max
+    }
+
+    /// Returns the smallest z-order which is greater than or equal to `from`, and is in this
+    /// range. None if there are none. Uses `zorder_bigmin` to skip over the gaps.
+    pub fn next_zorder_index(&self, from: u64) -> Option<u64> {
+        let (min, max) = (self.min_zorder(), self.max_zorder());
+        if from <= min {
+            Some(min)
+        } else if from > max {
+            None
+        } else if self.contains_zorder(from) {
+            Some(from)
+        } else {
+            Some(zorder_bigmin(from, min, max))
+        }
+    }
+
+    /// The z-orders of the tiles in this range, as the smallest list of intervals (inclusive),
+    /// in order. e.g. to do `WHERE zorder BETWEEN a AND b` queries on a database.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::TileRange;
+    /// let range = TileRange::new(2, 1, 2, 0, 1).unwrap();
+    /// assert_eq!(range.zorder_ranges(), vec![(1, 1), (3, 4), (6, 6)]);
+    /// ```
+    pub fn zorder_ranges(&self) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        self.zorder_ranges_in(0, 0, &mut ranges);
+        ranges
+    }
+
+    /// Add the z-order intervals for the cell at `depth` with z-order `index`
+    fn zorder_ranges_in(&self, depth: u8, index: u64, ranges: &mut Vec<(u64, u64)>) {
+        match self.zorder_cell(depth, index) {
+            None => {}
+            Some((first, last, true)) => push_zorder_interval(ranges, (first, last)),
+            Some(_) => {
+                for i in 0..4 {
+                    self.zorder_ranges_in(depth + 1, index * 4 + i, ranges);
+                }
+            }
+        }
+    }
+
+    /// The first & last z-order (at this range's zoom) of the cell at `depth` with z-order
+    /// `index`, and whether the cell is completely inside this range. None if the cell doesn't
+    /// overlap this range.
+    fn zorder_cell(&self, depth: u8, index: u64) -> Option<(u64, u64, bool)> {
+        let shift = self.zoom - depth;
+        let (x, y) = zorder_to_xy(index);
+        let (x1, y1) = ((x as u64) << shift, (y as u64) << shift);
+        let (x2, y2) = (
+            (((x as u64) + 1) << shift) - 1,
+            (((y as u64) + 1) << shift) - 1,
+        );
+        if x2 < self.x_min as u64
+            || x1 > self.x_max as u64
+            || y2 < self.y_min as u64
+            || y1 > self.y_max as u64
+        {
+            // Not in the range
+            return None;
+        }
+
+        let inside = x1 >= self.x_min as u64
+            && x2 <= self.x_max as u64
+            && y1 >= self.y_min as u64
+            && y2 <= self.y_max as u64;
+        let first = index << (2 * shift as u64);
+        let last = first + ((1u64 << (2 * shift as u64)) - 1);
+        Some((first, last, inside))
+    }
+
+    /// Like `zorder_ranges`, but returns at most `max_ranges` intervals, so some z-orders outside
+    /// this range can be included.
+    ///
+    /// The quadtree is refined one level at a time, and refining stops before a level would need
+    /// much more than `max_ranges` intervals, so this doesn't calculate all the intervals first,
+    /// e.g. for a long thin range at a high zoom. The intervals with the smallest gaps between
+    /// them are then merged. If `zorder_ranges` has at most `max_ranges` intervals, this returns
+    /// the same.
+    pub fn zorder_ranges_bounded(&self, max_ranges: usize) -> Vec<(u64, u64)> {
+        let max_ranges = max_ranges.max(1);
+        // Each partially inside cell has the end of an interval in it, and each run of inside
+        // cells ends at an interval end or a partially inside cell, so if `zorder_ranges` has at
+        // most `max_ranges` intervals, no level has more than this
+        let max_cells = max_ranges.saturating_mul(6);
+
+        // Runs of cells completely inside this range, and single cells which are partially
+        // inside, as (first, last, inside), in z-order
+        let mut cells = Vec::new();
+        cells.extend(self.zorder_cell(0, 0));
+        'levels: for depth in 0..self.zoom {
+            if cells.iter().all(|c| c.2) {
+                break;
+            }
+            let mut next: Vec<(u64, u64, bool)> = Vec::new();
+            for &(first, last, inside) in cells.iter() {
+                if inside {
+                    match next.last_mut() {
+                        Some(prev) if prev.2 && prev.1 + 1 == first => prev.1 = last,
+                        _ => next.push((first, last, true)),
+                    }
+                } else {
+                    let index = first >> (2 * (self.zoom - depth) as u64);
+                    for i in 0..4 {
+                        match self.zorder_cell(depth + 1, index * 4 + i) {
+                            Some((first, last, true)) => match next.last_mut() {
+                                Some(prev) if prev.2 && prev.1 + 1 == first => prev.1 = last,
+                                _ => next.push((first, last, true)),
+                            },
+                            Some(cell) => next.push(cell),
+                            None => {}
+                        }
+                    }
+                }
+                if next.len() > max_cells {
+                    break 'levels;
+                }
+            }
+            cells = next;
+        }
+
+        // Partially inside cells only need to go from their first to last z-order in the range
+        let (min, max) = (self.min_zorder(), self.max_zorder());
+        let mut ranges = Vec::with_capacity(cells.len());
+        for (first, last, inside) in cells {
+            if inside {
+                push_zorder_interval(&mut ranges, (first, last));
+            } else {
+                let first = self.next_zorder_index(first).unwrap();
+                let last = if last >= max {
+                    max
+                } else if self.contains_zorder(last) {
+                    last
+                } else {
+                    zorder_litmax(last, min, max)
+                };
+                push_zorder_interval(&mut ranges, (first, last));
+            }
+        }
+
+        if ranges.len() <= max_ranges {
+            return ranges;
+        }
+
+        // Keep the largest (max_ranges - 1) gaps, and merge across the rest
+        let mut gaps: Vec<(u64, usize)> = ranges
+            .windows(2)
+            .enumerate()
+            .map(|(i, w)| (w[1].0 - w[0].1, i))
+            .collect();
+        gaps.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        let mut split_after: Vec<usize> =
+            gaps.iter().take(max_ranges - 1).map(|&(_, i)| i).collect();
+        split_after.sort_unstable();
+
+        let mut result = Vec::with_capacity(split_after.len() + 1);
+        let mut start = 0;
+        for i in split_after.into_iter().chain(Some(ranges.len() - 1)) {
+            result.push((ranges[start].0, ranges[i].1));
+            start = i + 1;
+        }
+        result
+    }
+
+    /// Returns the smallest position along this curve, which is greater than or equal to `from`,
+    /// and is in this range. See `next_zorder_index` & `next_hilbert_index`.
+    pub fn next_index(&self, order: TileOrder, from: u64) -> Option<u64> {
+        match order {
+            TileOrder::ZOrder => self.next_zorder_index(from),
+            TileOrder::Hilbert => self.next_hilbert_index(from),
+        }
+    }
+
+    /// The position along this curve of the `n`th (starting at 0) tile of this range, when the
+    /// tiles are in that order. None if there are fewer tiles. This doesn't go through the tiles,
+    /// so it's fast for large ranges.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{TileOrder, TileRange};
+    /// let range = TileRange::new(2, 1, 2, 0, 1).unwrap();
+    /// assert_eq!(range.nth_index(TileOrder::ZOrder, 2), Some(4));
+    /// ```
+    pub fn nth_index(&self, order: TileOrder, n: u64) -> Option<u64> {
+        if n >= self.count() {
+            return None;
+        }
+
+        // Go down the quadtree, skipping over the cells with too few tiles in this range
+        let mut n = n;
+        let mut index = 0;
+        for depth in 1..=self.zoom {
+            for child in 0..4 {
+                let cell = self.curve_cell(order, index * 4 + child, depth);
+                let in_cell = self.intersection(&cell).map_or(0, |r| r.count());
+                if n < in_cell {
+                    index = index * 4 + child;
+                    break;
+                }
+                n -= in_cell;
+            }
+        }
+
+        Some(index)
+    }
+
+    /// All the tiles at this range's zoom, which are in the quadtree cell at `depth` with this
+    /// position along the curve.
+    fn curve_cell(&self, order: TileOrder, index: u64, depth: u8) -> TileRange {
+        let shift = self.zoom - depth;
+        let (x, y) = order.xy(index, depth);
+        TileRange::new(
+            self.zoom,
+            x << shift,
+            (x << shift) + ((1 << shift) - 1),
+            y << shift,
+            (y << shift) + ((1 << shift) - 1),
+        )
+        .unwrap()
+    }
+
+    /// The number of tiles in this range which are before position `index` along this curve,
+    /// i.e. the opposite of `nth_index`. This doesn't go through the tiles.
+    pub fn count_before(&self, order: TileOrder, index: u64) -> u64 {
+        if index >= 1u64 << (2 * self.zoom as u64) {
+            return self.count();
+        }
+
+        // Go down the quadtree towards `index`, adding up the cells before it
+        let mut count = 0;
+        let mut prefix = 0;
+        for depth in 1..=self.zoom {
+            let shift = self.zoom - depth;
+            let digit = (index >> (2 * shift as u64)) & 3;
+            for child in 0..digit {
+                let cell = self.curve_cell(order, prefix * 4 + child, depth);
+                count += self.intersection(&cell).map_or(0, |r| r.count());
+            }
+            prefix = prefix * 4 + digit;
+        }
+
+        count
+    }
+}
+
+impl IntoIterator for TileRange {
+    type Item = Tile;
+    type IntoIter = TileRangeIter;
+
+    fn into_iter(self) -> TileRangeIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &TileRange {
+    type Item = Tile;
+    type IntoIter = TileRangeIter;
+
+    fn into_iter(self) -> TileRangeIter {
+        self.iter()
+    }
+}
+
+impl fmt::Display for TileRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}-{}/{}-{}",
+            self.zoom, self.x_min, self.x_max, self.y_min, self.y_max
+        )
+    }
+}
+
+impl FromStr for TileRange {
+    type Err = &'static str;
+
+    /// Parses `Z/X1-X2/Y1-Y2`. A single number can be given for X or Y, e.g. `Z/X/Y1-Y2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref TILE_RANGE_RE: Regex = Regex::new(
+                "^(?P<zoom>[0-9]?[0-9])/(?P<x1>[0-9]{1,10})(-(?P<x2>[0-9]{1,10}))?/(?P<y1>[0-9]{1,10})(-(?P<y2>[0-9]{1,10}))?$"
+            )
+            .unwrap();
+        }
+
+        let caps = TILE_RANGE_RE
+            .captures(s)
+            .ok_or("Tile range Z/X1-X2/Y1-Y2 regex didn't match")?;
+
+        let num = |name: &str| -> Result<Option<u32>, &'static str> {
+            match caps.name(name) {
+                None => Ok(None),
+                Some(m) => m
+                    .as_str()
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| "Number is too large"),
+            }
+        };
+
+        // If the regex matches, this can't fail
+        let zoom = caps.name("zoom").unwrap().as_str().parse().unwrap();
+        let x1 = num("x1")?.unwrap();
+        let x2 = num("x2")?.unwrap_or(x1);
+        let y1 = num("y1")?.unwrap();
+        let y2 = num("y2")?.unwrap_or(y1);
+
+        TileRange::new(zoom, x1, x2, y1, y2).ok_or("Invalid tile range for this zoom")
+    }
+}
+
+/// Add this z-order interval to the end of `ranges`, joining it to the last one if they touch
+fn push_zorder_interval(ranges: &mut Vec<(u64, u64)>, (first, last): (u64, u64)) {
+    match ranges.last_mut() {
+        Some(prev) if prev.1 + 1 == first => prev.1 = last,
+        _ => ranges.push((first, last)),
+    }
+}
+
+/// Iterates over the tiles in a `TileRange`
+#[derive(Debug, Clone)]
+pub struct TileRangeIter {
+    range: TileRange,
+    next_x: u32,
+    next_y: u32,
+    remaining: u64,
+}
+
+impl Iterator for TileRangeIter {
+    type Item = Tile;
+
+    fn next(&mut self) -> Option<Tile> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let tile = Tile::new(self.range.zoom, self.next_x, self.next_y);
+        self.remaining -= 1;
+
+        if self.next_y < self.range.y_max {
+            self.next_y += 1;
+        } else {
+            self.next_y = self.range.y_min;
+            self.next_x += 1;
+        }
+
+        tile
+    }
+
+    /// Exact, unless there are more than `usize::MAX` tiles left (only possible on 32 bit
+    /// platforms).
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.remaining > usize::MAX as u64 {
+            (usize::MAX, None)
+        } else {
+            (self.remaining as usize, Some(self.remaining as usize))
+        }
+    }
+}
+
+/// There are more tiles than fit in a 32 bit `usize`, so this is only on 64 bit platforms.
+#[cfg(target_pointer_width = "64")]
+impl ExactSizeIterator for TileRangeIter {}
+
+/// Iterates over the tiles in a `TileRange` in Hilbert curve order
+#[derive(Debug, Clone)]
+pub struct TileRangeHilbertIter {
+    range: TileRange,
+    next_index: Option<u64>,
+    remaining: u64,
+}
+
+impl Iterator for TileRangeHilbertIter {
+    type Item = Tile;
+
+    fn next(&mut self) -> Option<Tile> {
+        let index = self.range.next_hilbert_index(self.next_index?)?;
+        self.next_index = index.checked_add(1);
+        self.remaining -= 1;
+
+        let (x, y) = hilbert_to_xy(index, self.range.zoom);
+        Tile::new(self.range.zoom, x, y)
+    }
+
+    /// Exact, unless there are more than `usize::MAX` tiles left (only possible on 32 bit
+    /// platforms).
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.remaining > usize::MAX as u64 {
+            (usize::MAX, None)
+        } else {
+            (self.remaining as usize, Some(self.remaining as usize))
+        }
+    }
+}
+
+/// There are more tiles than fit in a 32 bit `usize`, so this is only on 64 bit platforms.
+#[cfg(target_pointer_width = "64")]
+impl ExactSizeIterator for TileRangeHilbertIter {}
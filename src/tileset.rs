@@ -0,0 +1,372 @@
+//! A set of tiles, stored as a quadtree.
+//!
+//! Adding a tile means that tile, and all it's subtiles, are in the set. When all 4 subtiles of a
+//! tile are in the set, they are replaced by their parent, so a large, dense, set (like all the
+//! tiles in a country at zoom 18) takes up much less memory than a `HashSet<Tile>`.
+//!
+//! ```
+//! # use slippy_map_tiles::Tile;
+//! # use slippy_map_tiles::tileset::TileSet;
+//! let mut set = TileSet::new();
+//! for t in Tile::new(2, 1, 1).unwrap().subtiles().unwrap().iter() {
+//!     set.insert(t);
+//! }
+//! // The 4 tiles have been merged into their parent
+//! assert_eq!(set.iter().collect::<Vec<_>>(), vec![Tile::new(2, 1, 1).unwrap()]);
+//! assert!(set.contains(&Tile::new(10, 300, 300).unwrap()));
+//! assert_eq!(set.count_at_zoom(5), 64);
+//! ```
+
+use std::iter::FromIterator;
+
+use super::{zorder_to_xy, Tile};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Node {
+    Empty,
+    Full,
+    // Children are in the same order as `Tile::subtiles()`
+    Split(Box<[Node; 4]>),
+}
+
+impl Node {
+    fn new_split(children: [Node; 4]) -> Node {
+        Node::Split(Box::new(children)).normalize()
+    }
+
+    /// If all the children are full (or empty), replace this with one full (or empty) node.
+    fn normalize(self) -> Node {
+        match self {
+            Node::Split(children) => {
+                if children.iter().all(|c| *c == Node::Full) {
+                    Node::Full
+                } else if children.iter().all(|c| *c == Node::Empty) {
+                    Node::Empty
+                } else {
+                    Node::Split(children)
+                }
+            }
+            n => n,
+        }
+    }
+
+    fn union(&self, other: &Node) -> Node {
+        match (self, other) {
+            (Node::Full, _) | (_, Node::Full) => Node::Full,
+            (Node::Empty, n) | (n, Node::Empty) => n.clone(),
+            (Node::Split(a), Node::Split(b)) => Node::new_split([
+                a[0].union(&b[0]),
+                a[1].union(&b[1]),
+                a[2].union(&b[2]),
+                a[3].union(&b[3]),
+            ]),
+        }
+    }
+
+    fn intersection(&self, other: &Node) -> Node {
+        match (self, other) {
+            (Node::Empty, _) | (_, Node::Empty) => Node::Empty,
+            (Node::Full, n) | (n, Node::Full) => n.clone(),
+            (Node::Split(a), Node::Split(b)) => Node::new_split([
+                a[0].intersection(&b[0]),
+                a[1].intersection(&b[1]),
+                a[2].intersection(&b[2]),
+                a[3].intersection(&b[3]),
+            ]),
+        }
+    }
+
+    fn difference(&self, other: &Node) -> Node {
+        match (self, other) {
+            (Node::Empty, _) | (_, Node::Full) => Node::Empty,
+            (n, Node::Empty) => n.clone(),
+            (Node::Full, Node::Split(b)) => Node::new_split([
+                Node::Full.difference(&b[0]),
+                Node::Full.difference(&b[1]),
+                Node::Full.difference(&b[2]),
+                Node::Full.difference(&b[3]),
+            ]),
+            (Node::Split(a), Node::Split(b)) => Node::new_split([
+                a[0].difference(&b[0]),
+                a[1].difference(&b[1]),
+                a[2].difference(&b[2]),
+                a[3].difference(&b[3]),
+            ]),
+        }
+    }
+
+    /// Number of tiles at `depth` levels below this node which are completely covered
+    fn count(&self, depth: u8) -> u64 {
+        match *self {
+            Node::Empty => 0,
+            Node::Full => 1 << (2 * depth as u64),
+            Node::Split(ref children) => {
+                if depth == 0 {
+                    0
+                } else {
+                    children.iter().map(|c| c.count(depth - 1)).sum()
+                }
+            }
+        }
+    }
+
+    fn num_nodes(&self) -> usize {
+        match *self {
+            Node::Empty => 0,
+            Node::Full => 1,
+            Node::Split(ref children) => children.iter().map(|c| c.num_nodes()).sum(),
+        }
+    }
+}
+
+/// Which child of the tile at `depth` is on the path to `tile`
+fn child_index(tile: &Tile, depth: u8) -> usize {
+    let shift = tile.zoom() - depth - 1;
+    (((tile.x() >> shift) & 1) | (((tile.y() >> shift) & 1) << 1)) as usize
+}
+
+/// A set of tiles, stored as a quadtree.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TileSet {
+    root: Node,
+}
+
+impl Default for TileSet {
+    fn default() -> Self {
+        TileSet::new()
+    }
+}
+
+impl TileSet {
+    /// An empty set
+    pub fn new() -> Self {
+        TileSet { root: Node::Empty }
+    }
+
+    /// A set which covers the whole world
+    pub fn full() -> Self {
+        TileSet { root: Node::Full }
+    }
+
+    /// True iff there are no tiles in this set
+    pub fn is_empty(&self) -> bool {
+        self.root == Node::Empty
+    }
+
+    /// Add this tile (and hence all it's subtiles) to the set. Returns false if it was already
+    /// completely in the set.
+    pub fn insert(&mut self, tile: &Tile) -> bool {
+        fn insert(node: &mut Node, tile: &Tile, depth: u8) -> bool {
+            if *node == Node::Full {
+                return false;
+            }
+            if depth == tile.zoom() {
+                *node = Node::Full;
+                return true;
+            }
+            if *node == Node::Empty {
+                *node = Node::Split(Box::new([
+                    Node::Empty,
+                    Node::Empty,
+                    Node::Empty,
+                    Node::Empty,
+                ]));
+            }
+
+            let added = match *node {
+                Node::Split(ref mut children) => {
+                    insert(&mut children[child_index(tile, depth)], tile, depth + 1)
+                }
+                _ => unreachable!(),
+            };
+            if added {
+                let n = std::mem::replace(node, Node::Empty);
+                *node = n.normalize();
+            }
+            added
+        }
+
+        insert(&mut self.root, tile, 0)
+    }
+
+    /// Remove this tile (and all it's subtiles) from the set.
+    pub fn remove(&mut self, tile: &Tile) {
+        let mut other = TileSet::new();
+        other.insert(tile);
+        self.root = self.root.difference(&other.root);
+    }
+
+    /// Walk down the tree towards `tile`, returning the first node which is not split, or the
+    /// node for `tile` itself.
+    fn find(&self, tile: &Tile) -> &Node {
+        let mut node = &self.root;
+        for depth in 0..tile.zoom() {
+            match *node {
+                Node::Split(ref children) => node = &children[child_index(tile, depth)],
+                _ => return node,
+            }
+        }
+        node
+    }
+
+    /// True iff all of this tile is in the set, i.e. it, or an ancestor, was inserted, or all of
+    /// it's subtiles are in the set.
+    pub fn contains(&self, tile: &Tile) -> bool {
+        *self.find(tile) == Node::Full
+    }
+
+    /// True iff some of this tile is in the set.
+    pub fn intersects(&self, tile: &Tile) -> bool {
+        *self.find(tile) != Node::Empty
+    }
+
+    /// All the tiles which are in either set
+    pub fn union(&self, other: &TileSet) -> TileSet {
+        TileSet {
+            root: self.root.union(&other.root),
+        }
+    }
+
+    /// All the tiles which are in both sets
+    pub fn intersection(&self, other: &TileSet) -> TileSet {
+        TileSet {
+            root: self.root.intersection(&other.root),
+        }
+    }
+
+    /// All the tiles which are in this set, but not in `other`
+    pub fn difference(&self, other: &TileSet) -> TileSet {
+        TileSet {
+            root: self.root.difference(&other.root),
+        }
+    }
+
+    /// The number of tiles needed to store this set, i.e. the number of tiles `iter()` returns.
+    pub fn len(&self) -> usize {
+        self.root.num_nodes()
+    }
+
+    /// How many tiles at `zoom` are completely covered by this set. The tiles are not generated,
+    /// so this is fast even for high zooms. There are no tiles above zoom 31, so that's 0.
+    pub fn count_at_zoom(&self, zoom: u8) -> u64 {
+        if zoom > 31 {
+            return 0;
+        }
+        self.root.count(zoom)
+    }
+
+    /// Iterate over the smallest list of tiles which make up this set, in z-order. Tiles are
+    /// at different zooms.
+    pub fn iter(&self) -> TileSetIter<'_> {
+        TileSetIter::new(self, None, false)
+    }
+
+    /// Iterate over all the tiles at `zoom` which are completely covered by this set.
+    pub fn iter_zoom(&self, zoom: u8) -> TileSetIter<'_> {
+        TileSetIter::new(self, Some(zoom), false)
+    }
+
+    /// Iterate over all the tiles at `zoom` which are completely or partially covered by this
+    /// set.
+    pub fn iter_zoom_intersecting(&self, zoom: u8) -> TileSetIter<'_> {
+        TileSetIter::new(self, Some(zoom), true)
+    }
+}
+
+impl<'a> Extend<&'a Tile> for TileSet {
+    fn extend<I: IntoIterator<Item = &'a Tile>>(&mut self, iter: I) {
+        for t in iter {
+            self.insert(t);
+        }
+    }
+}
+
+impl Extend<Tile> for TileSet {
+    fn extend<I: IntoIterator<Item = Tile>>(&mut self, iter: I) {
+        for t in iter {
+            self.insert(&t);
+        }
+    }
+}
+
+impl FromIterator<Tile> for TileSet {
+    fn from_iter<I: IntoIterator<Item = Tile>>(iter: I) -> Self {
+        let mut set = TileSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<'a> FromIterator<&'a Tile> for TileSet {
+    fn from_iter<I: IntoIterator<Item = &'a Tile>>(iter: I) -> Self {
+        let mut set = TileSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+/// Iterates over the tiles in a `TileSet`
+pub struct TileSetIter<'a> {
+    stack: Vec<(Tile, &'a Node)>,
+    zoom: Option<u8>,
+    intersecting: bool,
+
+    // When expanding a full tile to `zoom`, the tile, the next zorder, and the last zorder
+    expanding: Option<(Tile, u64, u64)>,
+}
+
+impl<'a> TileSetIter<'a> {
+    fn new(set: &'a TileSet, zoom: Option<u8>, intersecting: bool) -> Self {
+        TileSetIter {
+            stack: vec![(Tile::new(0, 0, 0).unwrap(), &set.root)],
+            zoom,
+            intersecting,
+            expanding: None,
+        }
+    }
+}
+
+impl<'a> Iterator for TileSetIter<'a> {
+    type Item = Tile;
+
+    fn next(&mut self) -> Option<Tile> {
+        loop {
+            if let Some((base, next_zorder, last_zorder)) = self.expanding {
+                let zoom = self.zoom.unwrap();
+                let shift = zoom - base.zoom();
+                let (i, j) = zorder_to_xy(next_zorder);
+                let tile = Tile::new(zoom, (base.x() << shift) + i, (base.y() << shift) + j);
+                if next_zorder == last_zorder {
+                    self.expanding = None;
+                } else {
+                    self.expanding = Some((base, next_zorder + 1, last_zorder));
+                }
+                return tile;
+            }
+
+            let (tile, node) = self.stack.pop()?;
+            match (node, self.zoom) {
+                (Node::Empty, _) => {}
+                (Node::Full, None) => return Some(tile),
+                (Node::Full, Some(zoom)) => {
+                    if tile.zoom() == zoom {
+                        return Some(tile);
+                    }
+                    let last_zorder = (1u64 << (2 * (zoom - tile.zoom()) as u64)) - 1;
+                    self.expanding = Some((tile, 0, last_zorder));
+                }
+                (Node::Split(_), Some(zoom)) if tile.zoom() == zoom => {
+                    if self.intersecting {
+                        return Some(tile);
+                    }
+                }
+                (Node::Split(children), _) => {
+                    let subtiles = tile.subtiles().unwrap();
+                    for i in (0..4).rev() {
+                        self.stack.push((subtiles[i], &children[i]));
+                    }
+                }
+            }
+        }
+    }
+}
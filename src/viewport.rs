@@ -0,0 +1,518 @@
+//! What a map on a screen shows: a centre, a zoom (which can have a fraction), and a size in
+//! pixels.
+//!
+//! ```
+//! # use slippy_map_tiles::LatLon;
+//! # use slippy_map_tiles::viewport::Viewport;
+//! let viewport = Viewport::new(LatLon::new(53.35, -6.26).unwrap(), 12.5, 800, 600).unwrap();
+//! for visible in viewport.tiles() {
+//!     // Draw visible.tile() at (visible.x(), visible.y()), visible.size() pixels wide
+//! }
+//! ```
+
+use super::mercator::ZoomRounding;
+use super::{lat_lon_to_tile_fraction, tile_fraction_to_lat_lon, BBox, LatLon, Tile};
+
+/// A map view, see the module docs
+#[derive(PartialEq, Debug, Clone)]
+pub struct Viewport {
+    centre: LatLon,
+    zoom: f64,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    zoom_rounding: ZoomRounding,
+}
+
+/// A map view seen by a camera which can be rotated and tilted, as in 3D & navigation maps.
+///
+/// The camera looks at `centre`, with the top of the screen facing `bearing` (in degrees clockwise
+/// from north), tilted `pitch` degrees from looking straight down. The vertical field of view is
+/// about 36.87°, the same as Mapbox GL & MapLibre. At `zoom`, the ground at the centre of the
+/// screen is the same size as in a `Viewport`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Camera {
+    centre: LatLon,
+    zoom: f64,
+    bearing: f64,
+    pitch: f64,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    zoom_rounding: ZoomRounding,
+}
+
+/// The largest pitch a `Camera` can have, in degrees. More than this, and the top of the screen
+/// gets too close to the horizon.
+pub const MAX_PITCH: f64 = 60.;
+
+/// `tan()` of half the vertical field of view
+const HALF_FOV_TAN: f64 = 1. / 3.;
+
+/// A tile which is (at least partly) visible in a `Viewport`, and where it goes
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ViewportTile {
+    tile: Tile,
+    x: f64,
+    y: f64,
+    size: f64,
+}
+
+impl ViewportTile {
+    pub fn tile(&self) -> Tile {
+        self.tile
+    }
+    /// The pixel x of the left of the tile, from the left of the viewport. Can be negative.
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+    /// The pixel y of the top of the tile, from the top of the viewport. Can be negative.
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+    /// The width & height the tile is drawn at, in pixels. With a fractional zoom, this isn't the
+    /// tile size.
+    pub fn size(&self) -> f64 {
+        self.size
+    }
+}
+
+impl Viewport {
+    /// A `width` x `height` pixel view, centred on `centre`, at `zoom` (0 to 31). Tiles are 256
+    /// pixels. Returns None if `zoom` is out of range, or the size is 0.
+    pub fn new(centre: LatLon, zoom: f64, width: u32, height: u32) -> Option<Viewport> {
+        if !(0. ..=31.).contains(&zoom) || width == 0 || height == 0 {
+            return None;
+        }
+        Some(Viewport {
+            centre,
+            zoom,
+            width,
+            height,
+            tile_size: 256,
+            zoom_rounding: ZoomRounding::Nearest,
+        })
+    }
+
+    /// Use tiles which are this many pixels wide, e.g. 512. Returns None if `tile_size` is 0.
+    pub fn with_tile_size(mut self, tile_size: u32) -> Option<Self> {
+        if tile_size == 0 {
+            return None;
+        }
+        self.tile_size = tile_size;
+        Some(self)
+    }
+
+    /// How to pick the tile zoom, when the zoom has a fraction
+    pub fn with_zoom_rounding(mut self, zoom_rounding: ZoomRounding) -> Self {
+        self.zoom_rounding = zoom_rounding;
+        self
+    }
+
+    pub fn centre(&self) -> &LatLon {
+        &self.centre
+    }
+    pub fn zoom(&self) -> f64 {
+        self.zoom
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// The zoom of the tiles which are shown
+    pub fn tile_zoom(&self) -> u8 {
+        round_zoom(self.zoom, self.zoom_rounding)
+    }
+
+    /// The width of the whole world in pixels
+    fn world_size(&self) -> f64 {
+        self.tile_size as f64 * 2f64.powf(self.zoom)
+    }
+
+    /// The global pixel x,y (at this zoom) of the top left corner of the view
+    fn top_left(&self) -> (f64, f64) {
+        let (x, y) =
+            lat_lon_to_tile_fraction(self.centre.lat() as f64, self.centre.lon() as f64, 0);
+        let world_size = self.world_size();
+
+        (
+            x * world_size - self.width as f64 / 2.,
+            y * world_size - self.height as f64 / 2.,
+        )
+    }
+
+    /// True if the view goes over the antimeridian (180° longitude)
+    pub fn crosses_antimeridian(&self) -> bool {
+        let (x, _) = self.top_left();
+        let world_size = self.world_size();
+        self.width as f64 >= world_size || x < 0. || x + self.width as f64 > world_size
+    }
+
+    /// The area which is visible. Parts outside the world (north & south) are left out.
+    ///
+    /// If the view crosses the antimeridian, `left` is more than `right` (as in GeoJSON). If the
+    /// view is wider than the world, it's all longitudes.
+    pub fn bbox(&self) -> BBox {
+        let (x, y) = self.top_left();
+        global_pixel_rect_bbox(
+            (x, y),
+            (self.width as f64, self.height as f64),
+            self.world_size(),
+        )
+    }
+
+    /// The tiles which are visible, row by row, and where they go. Tiles are repeated across the
+    /// antimeridian, so a tile can be shown more than once, at different `x`s.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{LatLon, Tile};
+    /// # use slippy_map_tiles::viewport::Viewport;
+    /// let viewport = Viewport::new(LatLon::new(0., 180.).unwrap(), 1., 256, 256).unwrap();
+    /// let tiles: Vec<_> = viewport.tiles().iter().map(|t| (t.tile(), t.x(), t.y())).collect();
+    /// assert_eq!(tiles, vec![
+    ///     (Tile::new(1, 1, 0).unwrap(), -128., -128.),
+    ///     (Tile::new(1, 0, 0).unwrap(), 128., -128.),
+    ///     (Tile::new(1, 1, 1).unwrap(), -128., 128.),
+    ///     (Tile::new(1, 0, 1).unwrap(), 128., 128.),
+    /// ]);
+    /// ```
+    pub fn tiles(&self) -> Vec<ViewportTile> {
+        let zoom = self.tile_zoom();
+        let num_tiles = 1i64 << zoom;
+        let size = self.world_size() / num_tiles as f64;
+        let (x, y) = self.top_left();
+
+        let first_col = (x / size).floor() as i64;
+        let last_col = ((x + self.width as f64) / size).ceil() as i64 - 1;
+        let first_row = ((y / size).floor() as i64).max(0);
+        let last_row = (((y + self.height as f64) / size).ceil() as i64 - 1).min(num_tiles - 1);
+
+        let mut tiles = Vec::new();
+        for row in first_row..=last_row {
+            for col in first_col..=last_col {
+                let tile_x = col.rem_euclid(num_tiles) as u32;
+                tiles.push(ViewportTile {
+                    tile: Tile::new(zoom, tile_x, row as u32).unwrap(),
+                    x: col as f64 * size - x,
+                    y: row as f64 * size - y,
+                    size,
+                });
+            }
+        }
+
+        tiles
+    }
+
+    /// The centre & zoom which fits `bbox` in this view, with `padding` pixels on each side. The
+    /// zoom is as large as possible, up to 31. A bbox with `left` more than `right` crosses the
+    /// antimeridian. Returns None if the padding doesn't leave any room.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::{BBox, LatLon};
+    /// # use slippy_map_tiles::viewport::Viewport;
+    /// let viewport = Viewport::new(LatLon::new(0., 0.).unwrap(), 0., 1024, 512).unwrap();
+    /// let bbox = BBox::new(10., 170., -10., -170.).unwrap();
+    /// let (centre, zoom) = viewport.fit_bbox(&bbox, 0).unwrap();
+    /// assert_eq!(centre.lon(), -180.);
+    /// assert!((zoom - 5.162563).abs() < 1e-6);
+    /// ```
+    pub fn fit_bbox(&self, bbox: &BBox, padding: u32) -> Option<(LatLon, f64)> {
+        let width = self.width as f64 - 2. * padding as f64;
+        let height = self.height as f64 - 2. * padding as f64;
+        if width <= 0. || height <= 0. {
+            return None;
+        }
+
+        let (left, top) = lat_lon_to_tile_fraction(bbox.top() as f64, bbox.left() as f64, 0);
+        let (right, bottom) =
+            lat_lon_to_tile_fraction(bbox.bottom() as f64, bbox.right() as f64, 0);
+        let bbox_width = if left > right {
+            right + 1. - left
+        } else {
+            right - left
+        };
+        let bbox_height = bottom - top;
+
+        let tile_size = self.tile_size as f64;
+        let zoom_x = (width / (bbox_width * tile_size)).log2();
+        let zoom_y = (height / (bbox_height * tile_size)).log2();
+        let zoom = zoom_x.min(zoom_y).clamp(0., 31.);
+
+        let centre_x = (left + bbox_width / 2.).rem_euclid(1.);
+        let centre = tile_fraction_to_lat_lon(centre_x, (top + bottom) / 2., 0)?;
+
+        Some((centre, zoom))
+    }
+}
+
+impl Camera {
+    /// A `width` x `height` pixel view. Returns None if `zoom` isn't 0 to 31, `pitch` isn't 0 to
+    /// `MAX_PITCH`, or the size is 0.
+    pub fn new(
+        centre: LatLon,
+        zoom: f64,
+        bearing: f64,
+        pitch: f64,
+        width: u32,
+        height: u32,
+    ) -> Option<Camera> {
+        if !(0. ..=31.).contains(&zoom)
+            || !(0. ..=MAX_PITCH).contains(&pitch)
+            || !bearing.is_finite()
+            || width == 0
+            || height == 0
+        {
+            return None;
+        }
+        Some(Camera {
+            centre,
+            zoom,
+            bearing: bearing.rem_euclid(360.),
+            pitch,
+            width,
+            height,
+            tile_size: 256,
+            zoom_rounding: ZoomRounding::Nearest,
+        })
+    }
+
+    /// Use tiles which are this many pixels wide, e.g. 512. Returns None if `tile_size` is 0.
+    pub fn with_tile_size(mut self, tile_size: u32) -> Option<Self> {
+        if tile_size == 0 {
+            return None;
+        }
+        self.tile_size = tile_size;
+        Some(self)
+    }
+
+    /// How to pick the most detailed tile zoom, when the zoom has a fraction
+    pub fn with_zoom_rounding(mut self, zoom_rounding: ZoomRounding) -> Self {
+        self.zoom_rounding = zoom_rounding;
+        self
+    }
+
+    pub fn centre(&self) -> &LatLon {
+        &self.centre
+    }
+    pub fn zoom(&self) -> f64 {
+        self.zoom
+    }
+    /// In degrees, 0 to 360
+    pub fn bearing(&self) -> f64 {
+        self.bearing
+    }
+    pub fn pitch(&self) -> f64 {
+        self.pitch
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// The zoom of the tiles near the centre. Tiles further away have lower zooms.
+    pub fn tile_zoom(&self) -> u8 {
+        round_zoom(self.zoom, self.zoom_rounding)
+    }
+
+    /// The width of the whole world in pixels
+    fn world_size(&self) -> f64 {
+        self.tile_size as f64 * 2f64.powf(self.zoom)
+    }
+
+    /// How far the camera is from the centre, in pixels
+    fn distance(&self) -> f64 {
+        self.height as f64 / 2. / HALF_FOV_TAN
+    }
+
+    /// The x,y (as a fraction of the world, which can be outside 0 to 1 across the
+    /// antimeridian) of a point on the ground, which is `right` & `forward` pixels from the centre
+    fn ground_to_world(&self, right: f64, forward: f64) -> (f64, f64) {
+        let (x, y) =
+            lat_lon_to_tile_fraction(self.centre.lat() as f64, self.centre.lon() as f64, 0);
+        let (sin, cos) = self.bearing.to_radians().sin_cos();
+        let world_size = self.world_size();
+
+        (
+            x + (right * cos + forward * sin) / world_size,
+            y + (right * sin - forward * cos) / world_size,
+        )
+    }
+
+    /// Where the line from the camera through this screen pixel hits the ground, as a fraction of
+    /// the world. None if it doesn't hit the ground.
+    fn screen_to_world(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let right = x - self.width as f64 / 2.;
+        let up = self.height as f64 / 2. - y;
+        let distance = self.distance();
+        let (sin, cos) = self.pitch.to_radians().sin_cos();
+
+        let below = distance * cos - up * sin;
+        if below <= 0. {
+            return None;
+        }
+        let t = distance * cos / below;
+        let forward = -distance * sin + t * (distance * sin + up * cos);
+
+        Some(self.ground_to_world(t * right, forward))
+    }
+
+    /// The location shown at this pixel on the screen (from the top left). None if it's above the
+    /// horizon, or outside the world.
+    pub fn unproject(&self, x: f64, y: f64) -> Option<LatLon> {
+        let (x, y) = self.screen_to_world(x, y)?;
+        tile_fraction_to_lat_lon(x.rem_euclid(1.), y, 0)
+    }
+
+    /// The tiles to show, with more detailed tiles near the camera, and less detailed ones
+    /// further away. The tiles cover everything which is visible, and don't overlap. A tile
+    /// isn't repeated if the view crosses the antimeridian.
+    ///
+    /// Tiles are at `tile_zoom()` up to as far away from the camera as the corners of the screen
+    /// would be with no pitch, and one zoom less for every doubling of that distance.
+    ///
+    /// ```
+    /// # use slippy_map_tiles::LatLon;
+    /// # use slippy_map_tiles::viewport::Camera;
+    /// let camera = Camera::new(LatLon::new(53.35, -6.26).unwrap(), 14., 30., 60., 800, 600).unwrap();
+    /// let tiles = camera.covering_tiles();
+    /// assert!(tiles.iter().any(|t| t.zoom() == 14));
+    /// assert!(tiles.iter().any(|t| t.zoom() < 14));
+    /// ```
+    pub fn covering_tiles(&self) -> Vec<Tile> {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let mut footprint = [(0., 0.); 4];
+        for (corner, &(x, y)) in footprint
+            .iter_mut()
+            .zip([(0., 0.), (width, 0.), (width, height), (0., height)].iter())
+        {
+            // The pitch is limited, so this always hits the ground
+            *corner = self.screen_to_world(x, y).unwrap();
+        }
+        let min_x = footprint.iter().map(|c| c.0).fold(f64::MAX, f64::min);
+        let max_x = footprint.iter().map(|c| c.0).fold(f64::MIN, f64::max);
+        let wraps = min_x.floor() as i64..=max_x.floor() as i64;
+
+        let world_size = self.world_size();
+        let distance = self.distance();
+        let (sin, cos) = self.pitch.to_radians().sin_cos();
+        let camera = self.ground_to_world(0., -distance * sin);
+        let height_squared = (distance * cos).powi(2);
+        let full_detail = (distance.powi(2) + (width / 2.).powi(2) + (height / 2.).powi(2)).sqrt();
+        let tile_zoom = self.tile_zoom();
+
+        let mut tiles = Vec::new();
+        let mut stack = vec![Tile::new(0, 0, 0).unwrap()];
+        while let Some(tile) = stack.pop() {
+            let size = 1. / (1u64 << tile.zoom()) as f64;
+            let (left, top) = (tile.x() as f64 * size, tile.y() as f64 * size);
+
+            let mut visible = false;
+            let mut nearest = f64::MAX;
+            for wrap in wraps.clone() {
+                let left = left + wrap as f64;
+                if !rect_intersects_polygon((left, top, left + size, top + size), &footprint) {
+                    continue;
+                }
+                visible = true;
+                let dx = (camera.0.clamp(left, left + size) - camera.0) * world_size;
+                let dy = (camera.1.clamp(top, top + size) - camera.1) * world_size;
+                nearest = nearest.min((dx * dx + dy * dy + height_squared).sqrt());
+            }
+            if !visible {
+                continue;
+            }
+
+            let less_detail = if nearest <= full_detail {
+                0
+            } else {
+                (nearest / full_detail).log2().ceil().min(31.) as u8
+            };
+            if tile.zoom() < tile_zoom.saturating_sub(less_detail) {
+                stack.extend(tile.subtiles().unwrap().iter());
+            } else {
+                tiles.push(tile);
+            }
+        }
+
+        tiles.sort();
+        tiles
+    }
+}
+
+/// The area of this rectangle of global pixels, `(left, top)` & `(width, height)`, when the world
+/// is `world_size` pixels wide. Parts outside the world (north & south) are left out. If it
+/// crosses the antimeridian, `left` is more than `right`, and if it's wider than the world, it's
+/// all longitudes. Used by `Viewport::bbox` & `StaticMap::bbox`.
+pub(crate) fn global_pixel_rect_bbox(
+    (x, y): (f64, f64),
+    (width, height): (f64, f64),
+    world_size: f64,
+) -> BBox {
+    let lat = |y: f64| {
+        tile_fraction_to_lat_lon(0., (y / world_size).clamp(0., 1.), 0)
+            .unwrap()
+            .lat()
+    };
+    let (left, right) = if width >= world_size {
+        (-180., 180.)
+    } else {
+        let left = (x / world_size).rem_euclid(1.) * 360. - 180.;
+        let right = left + width / world_size * 360.;
+        let right = if right > 180. { right - 360. } else { right };
+        (left as f32, right as f32)
+    };
+
+    BBox::new(lat(y), left, lat(y + height), right).unwrap()
+}
+
+/// Round a fractional zoom to a tile zoom
+fn round_zoom(zoom: f64, rounding: ZoomRounding) -> u8 {
+    let zoom = match rounding {
+        ZoomRounding::Nearest => zoom.round(),
+        ZoomRounding::Coarser => zoom.floor(),
+        ZoomRounding::Finer => zoom.ceil(),
+    };
+    zoom as u8
+}
+
+/// True if the rectangle `(left, top, right, bottom)` overlaps the convex `polygon`
+fn rect_intersects_polygon(rect: (f64, f64, f64, f64), polygon: &[(f64, f64)]) -> bool {
+    let (left, top, right, bottom) = rect;
+    if polygon.iter().all(|p| p.0 <= left)
+        || polygon.iter().all(|p| p.0 >= right)
+        || polygon.iter().all(|p| p.1 <= top)
+        || polygon.iter().all(|p| p.1 >= bottom)
+    {
+        return false;
+    }
+
+    let corners = [(left, top), (right, top), (right, bottom), (left, bottom)];
+    let n = polygon.len() as f64;
+    let middle = (
+        polygon.iter().map(|p| p.0).sum::<f64>() / n,
+        polygon.iter().map(|p| p.1).sum::<f64>() / n,
+    );
+    let side = |a: (f64, f64), b: (f64, f64), p: (f64, f64)| {
+        (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+    };
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let inside = side(a, b, middle);
+        if corners.iter().all(|&c| side(a, b, c) * inside <= 0.) {
+            return false;
+        }
+    }
+
+    true
+}